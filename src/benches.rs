@@ -1,5 +1,6 @@
 use crate::bitboard;
 use crate::board;
+use crate::perft;
 
 extern crate test;
 use test::Bencher;
@@ -30,7 +31,7 @@ fn make_unmake_moves(b: &mut Bencher) {
   b.iter(|| {
     for chess_move in &moves {
       board.make_move(chess_move);
-      if board.side_to_move == board::Color::Black {
+      if board.side_to_move == bitboard::Color::Black {
         c += 1;
       }
       board.undo_move(chess_move);
@@ -48,7 +49,7 @@ fn make_unmake_moves_bb(b: &mut Bencher) {
     for chess_move in &moves {
       let mut new_board = board.clone();
       new_board.make_move(chess_move);
-      if !new_board.white_to_move {
+      if new_board.side_to_move == bitboard::Color::Black {
         c += 1
       }
     }
@@ -59,7 +60,7 @@ fn make_unmake_moves_bb(b: &mut Bencher) {
 #[bench]
 fn legal_moves(b: &mut Bencher) {
   let mut board = board::Board::from_fen("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1");
-  let side = board.side_to_move.clone();
+  let side = board.side_to_move;
   let moves = board.pseudo_legal_moves();
   let mut c = 0;
   b.iter(|| {
@@ -78,7 +79,7 @@ fn legal_moves(b: &mut Bencher) {
 #[bench]
 fn legal_moves_bb(b: &mut Bencher) {
   let board = bitboard::Board::from_fen("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1");
-  let color = if board.white_to_move { 0 } else { 1 };
+  let color = board.side_to_move;
   let moves = board.pseudo_legal_moves();
   let mut c = 0;
 
@@ -155,28 +156,8 @@ fn capture_moves_bb(b: &mut Bencher) {
 fn perft(b: &mut Bencher) {
   let mut board = board::Board::from_fen("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1");
 
-  fn p(depth: usize, board: &mut board::Board) -> u64 {
-    let mut nodes = 0;
-
-    if depth == 0 {
-      return 1;
-    }
-
-    let side_to_move = board.side_to_move.clone();
-
-    for chess_move in board.pseudo_legal_moves() {
-      board.make_move(&chess_move);
-      if !board.in_check(&side_to_move) {
-        nodes += p(depth - 1, board);
-      }
-      board.undo_move(&chess_move);
-    }
-
-    nodes
-  }
-
   b.iter(|| {
-    p(3, &mut board);
+    perft::mailbox_perft(&mut board, 3);
   });
 }
 
@@ -184,27 +165,7 @@ fn perft(b: &mut Bencher) {
 fn perft_bb(b: &mut Bencher) {
   let board = bitboard::Board::from_fen("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1");
 
-  fn p(depth: usize, board: bitboard::Board) -> u64 {
-    let mut nodes = 0;
-
-    if depth == 0 {
-      return 1;
-    }
-
-    let color = if board.white_to_move { 0 } else { 1 };
-
-    for chess_move in board.pseudo_legal_moves() {
-      let mut new_board = board.clone();
-      new_board.make_move(&chess_move);
-      if !new_board.in_check(color) {
-        nodes += p(depth - 1, new_board);
-      }
-    }
-
-    nodes
-  }
-
   b.iter(|| {
-    p(3, board.clone());
+    perft::perft(&board, 3, None);
   });
 }
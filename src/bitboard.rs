@@ -2,255 +2,525 @@ use bitintr::Pext;
 
 use lazy_static::lazy_static;
 use rand::prelude::*;
+use std::{
+  fmt,
+  ops::{BitAnd, BitAndAssign, BitOr, BitOrAssign, BitXor, BitXorAssign, Not},
+  str::FromStr,
+};
 
-const WHITE: usize = 0;
-const BLACK: usize = 1;
-
-const PAWN: usize = 2;
-const KNIGHT: usize = 4;
-const BISHOP: usize = 6;
-const ROOK: usize = 8;
-const QUEEN: usize = 10;
-const KING: usize = 12;
-
-const EMPTY_SQUARE: usize = 14;
-
-// Bitboard representation:
-// 8 | 63 62 61 60 59 58 57 56
-// 7 | 55 54 53 52 51 50 49 48
-// 6 | 47 46 45 44 43 42 41 40
-// 5 | 39 38 37 36 35 34 33 32
-// 4 | 31 30 29 28 27 26 25 24
-// 3 | 23 22 21 20 19 18 17 16
-// 2 | 15 14 13 12 11 10 09 08
-// 1 | 07 06 05 04 03 02 01 00
+use crate::board::{DrawReason, GameStatus};
+use crate::endgame;
+
+// Bitboard representation, a bit for every square:
+// 8 | 56 57 58 59 60 61 62 63
+// 7 | 48 49 50 51 52 53 54 55
+// 6 | 40 41 42 43 44 45 46 47
+// 5 | 32 33 34 35 36 37 38 39
+// 4 | 24 25 26 27 28 29 30 31
+// 3 | 16 17 18 19 20 21 22 23
+// 2 | 08 09 10 11 12 13 14 15
+// 1 | 00 01 02 03 04 05 06 07
 //   +------------------------
 //     A  B  C  D  E  F  G  H
+// Only Square, File and Rank know about the indices, everything else goes through them
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Color {
+  White,
+  Black,
+}
+
+impl Color {
+  pub const ALL: [Color; 2] = [Color::White, Color::Black];
+
+  pub const fn index(self) -> usize {
+    self as usize
+  }
+}
+
+impl Not for Color {
+  type Output = Color;
+
+  fn not(self) -> Color {
+    match self {
+      Color::White => Color::Black,
+      Color::Black => Color::White,
+    }
+  }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum PieceType {
+  Pawn,
+  Knight,
+  Bishop,
+  Rook,
+  Queen,
+  King,
+}
+
+impl PieceType {
+  pub const ALL: [PieceType; 6] = [
+    PieceType::Pawn,
+    PieceType::Knight,
+    PieceType::Bishop,
+    PieceType::Rook,
+    PieceType::Queen,
+    PieceType::King,
+  ];
+
+  pub const fn index(self) -> usize {
+    self as usize
+  }
+
+  // Uppercase, like white pieces in a fen
+  pub const fn to_char(self) -> char {
+    match self {
+      PieceType::Pawn => 'P',
+      PieceType::Knight => 'N',
+      PieceType::Bishop => 'B',
+      PieceType::Rook => 'R',
+      PieceType::Queen => 'Q',
+      PieceType::King => 'K',
+    }
+  }
+
+  // Either case
+  pub fn from_char(c: char) -> Option<PieceType> {
+    PieceType::ALL
+      .into_iter()
+      .find(|piece_type| piece_type.to_char() == c.to_ascii_uppercase())
+  }
+
+  // Material value, the king has none
+  pub(crate) const fn value(self) -> i32 {
+    match self {
+      PieceType::Pawn => PAWN_VALUE,
+      PieceType::Knight => KNIGHT_VALUE,
+      PieceType::Bishop => BISHOP_VALUE,
+      PieceType::Rook => ROOK_VALUE,
+      PieceType::Queen => QUEEN_VALUE,
+      PieceType::King => 0,
+    }
+  }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Piece {
+  pub color: Color,
+  pub piece_type: PieceType,
+}
+
+impl Piece {
+  pub const fn new(color: Color, piece_type: PieceType) -> Piece {
+    Piece { color, piece_type }
+  }
 
-pub const FILE_A: u64 = 0x8080808080808080;
-pub const FILE_B: u64 = 0x4040404040404040;
-pub const FILE_C: u64 = 0x2020202020202020;
-pub const FILE_D: u64 = 0x1010101010101010;
-pub const FILE_E: u64 = 0x0808080808080808;
-pub const FILE_F: u64 = 0x0404040404040404;
-pub const FILE_G: u64 = 0x0202020202020202;
-pub const FILE_H: u64 = 0x0101010101010101;
-
-pub const RANK_1: u64 = 0x00000000000000ff;
-pub const RANK_2: u64 = 0x000000000000ff00;
-pub const RANK_3: u64 = 0x0000000000ff0000;
-pub const RANK_4: u64 = 0x00000000ff000000;
-pub const RANK_5: u64 = 0x000000ff00000000;
-pub const RANK_6: u64 = 0x0000ff0000000000;
-pub const RANK_7: u64 = 0x00ff000000000000;
-pub const RANK_8: u64 = 0xff00000000000000;
-
-pub const A1: u64 = FILE_A & RANK_1;
-pub const A2: u64 = FILE_A & RANK_2;
-pub const A3: u64 = FILE_A & RANK_3;
-pub const A4: u64 = FILE_A & RANK_4;
-pub const A5: u64 = FILE_A & RANK_5;
-pub const A6: u64 = FILE_A & RANK_6;
-pub const A7: u64 = FILE_A & RANK_7;
-pub const A8: u64 = FILE_A & RANK_8;
-pub const B1: u64 = FILE_B & RANK_1;
-pub const B2: u64 = FILE_B & RANK_2;
-pub const B3: u64 = FILE_B & RANK_3;
-pub const B4: u64 = FILE_B & RANK_4;
-pub const B5: u64 = FILE_B & RANK_5;
-pub const B6: u64 = FILE_B & RANK_6;
-pub const B7: u64 = FILE_B & RANK_7;
-pub const B8: u64 = FILE_B & RANK_8;
-pub const C1: u64 = FILE_C & RANK_1;
-pub const C2: u64 = FILE_C & RANK_2;
-pub const C3: u64 = FILE_C & RANK_3;
-pub const C4: u64 = FILE_C & RANK_4;
-pub const C5: u64 = FILE_C & RANK_5;
-pub const C6: u64 = FILE_C & RANK_6;
-pub const C7: u64 = FILE_C & RANK_7;
-pub const C8: u64 = FILE_C & RANK_8;
-pub const D1: u64 = FILE_D & RANK_1;
-pub const D2: u64 = FILE_D & RANK_2;
-pub const D3: u64 = FILE_D & RANK_3;
-pub const D4: u64 = FILE_D & RANK_4;
-pub const D5: u64 = FILE_D & RANK_5;
-pub const D6: u64 = FILE_D & RANK_6;
-pub const D7: u64 = FILE_D & RANK_7;
-pub const D8: u64 = FILE_D & RANK_8;
-pub const E1: u64 = FILE_E & RANK_1;
-pub const E2: u64 = FILE_E & RANK_2;
-pub const E3: u64 = FILE_E & RANK_3;
-pub const E4: u64 = FILE_E & RANK_4;
-pub const E5: u64 = FILE_E & RANK_5;
-pub const E6: u64 = FILE_E & RANK_6;
-pub const E7: u64 = FILE_E & RANK_7;
-pub const E8: u64 = FILE_E & RANK_8;
-pub const F1: u64 = FILE_F & RANK_1;
-pub const F2: u64 = FILE_F & RANK_2;
-pub const F3: u64 = FILE_F & RANK_3;
-pub const F4: u64 = FILE_F & RANK_4;
-pub const F5: u64 = FILE_F & RANK_5;
-pub const F6: u64 = FILE_F & RANK_6;
-pub const F7: u64 = FILE_F & RANK_7;
-pub const F8: u64 = FILE_F & RANK_8;
-pub const G1: u64 = FILE_G & RANK_1;
-pub const G2: u64 = FILE_G & RANK_2;
-pub const G3: u64 = FILE_G & RANK_3;
-pub const G4: u64 = FILE_G & RANK_4;
-pub const G5: u64 = FILE_G & RANK_5;
-pub const G6: u64 = FILE_G & RANK_6;
-pub const G7: u64 = FILE_G & RANK_7;
-pub const G8: u64 = FILE_G & RANK_8;
-pub const H1: u64 = FILE_H & RANK_1;
-pub const H2: u64 = FILE_H & RANK_2;
-pub const H3: u64 = FILE_H & RANK_3;
-pub const H4: u64 = FILE_H & RANK_4;
-pub const H5: u64 = FILE_H & RANK_5;
-pub const H6: u64 = FILE_H & RANK_6;
-pub const H7: u64 = FILE_H & RANK_7;
-pub const H8: u64 = FILE_H & RANK_8;
-
-fn square_to_bitboard(square: &str) -> u64 {
-  match &square.to_lowercase()[..] {
-    "a1" => A1,
-    "a2" => A2,
-    "a3" => A3,
-    "a4" => A4,
-    "a5" => A5,
-    "a6" => A6,
-    "a7" => A7,
-    "a8" => A8,
-    "b1" => B1,
-    "b2" => B2,
-    "b3" => B3,
-    "b4" => B4,
-    "b5" => B5,
-    "b6" => B6,
-    "b7" => B7,
-    "b8" => B8,
-    "c1" => C1,
-    "c2" => C2,
-    "c3" => C3,
-    "c4" => C4,
-    "c5" => C5,
-    "c6" => C6,
-    "c7" => C7,
-    "c8" => C8,
-    "d1" => D1,
-    "d2" => D2,
-    "d3" => D3,
-    "d4" => D4,
-    "d5" => D5,
-    "d6" => D6,
-    "d7" => D7,
-    "d8" => D8,
-    "e1" => E1,
-    "e2" => E2,
-    "e3" => E3,
-    "e4" => E4,
-    "e5" => E5,
-    "e6" => E6,
-    "e7" => E7,
-    "e8" => E8,
-    "f1" => F1,
-    "f2" => F2,
-    "f3" => F3,
-    "f4" => F4,
-    "f5" => F5,
-    "f6" => F6,
-    "f7" => F7,
-    "f8" => F8,
-    "g1" => G1,
-    "g2" => G2,
-    "g3" => G3,
-    "g4" => G4,
-    "g5" => G5,
-    "g6" => G6,
-    "g7" => G7,
-    "g8" => G8,
-    "h1" => H1,
-    "h2" => H2,
-    "h3" => H3,
-    "h4" => H4,
-    "h5" => H5,
-    "h6" => H6,
-    "h7" => H7,
-    "h8" => H8,
-    _ => panic!(),
+  // White pawn, black pawn, white knight, black knight...
+  const fn index(self) -> usize {
+    self.piece_type.index() * 2 + self.color.index()
+  }
+
+  // Like in a fen: uppercase for white, lowercase for black
+  pub const fn to_char(self) -> char {
+    match self.color {
+      Color::White => self.piece_type.to_char(),
+      Color::Black => self.piece_type.to_char().to_ascii_lowercase(),
+    }
+  }
+
+  pub fn from_char(c: char) -> Option<Piece> {
+    let color = if c.is_ascii_uppercase() { Color::White } else { Color::Black };
+    PieceType::from_char(c).map(|piece_type| Piece::new(color, piece_type))
   }
 }
 
-// Converts a bitboard, which has only one 1 bit, to a square, which is a string.
-fn bitboard_to_square(bitboard: u64) -> &'static str {
-  match bitboard {
-    A1 => "a1",
-    A2 => "a2",
-    A3 => "a3",
-    A4 => "a4",
-    A5 => "a5",
-    A6 => "a6",
-    A7 => "a7",
-    A8 => "a8",
-    B1 => "b1",
-    B2 => "b2",
-    B3 => "b3",
-    B4 => "b4",
-    B5 => "b5",
-    B6 => "b6",
-    B7 => "b7",
-    B8 => "b8",
-    C1 => "c1",
-    C2 => "c2",
-    C3 => "c3",
-    C4 => "c4",
-    C5 => "c5",
-    C6 => "c6",
-    C7 => "c7",
-    C8 => "c8",
-    D1 => "d1",
-    D2 => "d2",
-    D3 => "d3",
-    D4 => "d4",
-    D5 => "d5",
-    D6 => "d6",
-    D7 => "d7",
-    D8 => "d8",
-    E1 => "e1",
-    E2 => "e2",
-    E3 => "e3",
-    E4 => "e4",
-    E5 => "e5",
-    E6 => "e6",
-    E7 => "e7",
-    E8 => "e8",
-    F1 => "f1",
-    F2 => "f2",
-    F3 => "f3",
-    F4 => "f4",
-    F5 => "f5",
-    F6 => "f6",
-    F7 => "f7",
-    F8 => "f8",
-    G1 => "g1",
-    G2 => "g2",
-    G3 => "g3",
-    G4 => "g4",
-    G5 => "g5",
-    G6 => "g6",
-    G7 => "g7",
-    G8 => "g8",
-    H1 => "h1",
-    H2 => "h2",
-    H3 => "h3",
-    H4 => "h4",
-    H5 => "h5",
-    H6 => "h6",
-    H7 => "h7",
-    H8 => "h8",
-    _ => panic!(),
+impl fmt::Display for Piece {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(f, "{}", self.to_char())
   }
 }
 
+// A column, from A to H
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct File(u8);
+
+impl File {
+  pub const A: File = File(0);
+  pub const B: File = File(1);
+  pub const C: File = File(2);
+  pub const D: File = File(3);
+  pub const E: File = File(4);
+  pub const F: File = File(5);
+  pub const G: File = File(6);
+  pub const H: File = File(7);
+
+  pub const fn new(index: u8) -> Option<File> {
+    if index < 8 {
+      Some(File(index))
+    } else {
+      None
+    }
+  }
+
+  pub const fn index(self) -> usize {
+    self.0 as usize
+  }
+
+  pub const fn bitboard(self) -> Bitboard {
+    Bitboard(0x0101010101010101 << self.0)
+  }
+}
+
+impl fmt::Display for File {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(f, "{}", (b'a' + self.0) as char)
+  }
+}
+
+// A row, from 1 to 8
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Rank(u8);
+
+impl Rank {
+  pub const R1: Rank = Rank(0);
+  pub const R2: Rank = Rank(1);
+  pub const R3: Rank = Rank(2);
+  pub const R4: Rank = Rank(3);
+  pub const R5: Rank = Rank(4);
+  pub const R6: Rank = Rank(5);
+  pub const R7: Rank = Rank(6);
+  pub const R8: Rank = Rank(7);
+
+  // 0 is the first rank
+  pub const fn new(index: u8) -> Option<Rank> {
+    if index < 8 {
+      Some(Rank(index))
+    } else {
+      None
+    }
+  }
+
+  pub const fn index(self) -> usize {
+    self.0 as usize
+  }
+
+  pub const fn bitboard(self) -> Bitboard {
+    Bitboard(0xff << (self.0 * 8))
+  }
+}
+
+impl fmt::Display for Rank {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(f, "{}", self.0 + 1)
+  }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Square(u8);
+
+impl Square {
+  pub const A1: Square = Square(0);
+  pub const B1: Square = Square(1);
+  pub const C1: Square = Square(2);
+  pub const D1: Square = Square(3);
+  pub const E1: Square = Square(4);
+  pub const F1: Square = Square(5);
+  pub const G1: Square = Square(6);
+  pub const H1: Square = Square(7);
+  pub const A2: Square = Square(8);
+  pub const B2: Square = Square(9);
+  pub const C2: Square = Square(10);
+  pub const D2: Square = Square(11);
+  pub const E2: Square = Square(12);
+  pub const F2: Square = Square(13);
+  pub const G2: Square = Square(14);
+  pub const H2: Square = Square(15);
+  pub const A3: Square = Square(16);
+  pub const B3: Square = Square(17);
+  pub const C3: Square = Square(18);
+  pub const D3: Square = Square(19);
+  pub const E3: Square = Square(20);
+  pub const F3: Square = Square(21);
+  pub const G3: Square = Square(22);
+  pub const H3: Square = Square(23);
+  pub const A4: Square = Square(24);
+  pub const B4: Square = Square(25);
+  pub const C4: Square = Square(26);
+  pub const D4: Square = Square(27);
+  pub const E4: Square = Square(28);
+  pub const F4: Square = Square(29);
+  pub const G4: Square = Square(30);
+  pub const H4: Square = Square(31);
+  pub const A5: Square = Square(32);
+  pub const B5: Square = Square(33);
+  pub const C5: Square = Square(34);
+  pub const D5: Square = Square(35);
+  pub const E5: Square = Square(36);
+  pub const F5: Square = Square(37);
+  pub const G5: Square = Square(38);
+  pub const H5: Square = Square(39);
+  pub const A6: Square = Square(40);
+  pub const B6: Square = Square(41);
+  pub const C6: Square = Square(42);
+  pub const D6: Square = Square(43);
+  pub const E6: Square = Square(44);
+  pub const F6: Square = Square(45);
+  pub const G6: Square = Square(46);
+  pub const H6: Square = Square(47);
+  pub const A7: Square = Square(48);
+  pub const B7: Square = Square(49);
+  pub const C7: Square = Square(50);
+  pub const D7: Square = Square(51);
+  pub const E7: Square = Square(52);
+  pub const F7: Square = Square(53);
+  pub const G7: Square = Square(54);
+  pub const H7: Square = Square(55);
+  pub const A8: Square = Square(56);
+  pub const B8: Square = Square(57);
+  pub const C8: Square = Square(58);
+  pub const D8: Square = Square(59);
+  pub const E8: Square = Square(60);
+  pub const F8: Square = Square(61);
+  pub const G8: Square = Square(62);
+  pub const H8: Square = Square(63);
+
+  pub const fn new(file: File, rank: Rank) -> Square {
+    Square(rank.0 * 8 + file.0)
+  }
+
+  pub const fn from_index(index: usize) -> Option<Square> {
+    if index < 64 {
+      Some(Square(index as u8))
+    } else {
+      None
+    }
+  }
+
+  // From a1 to h8, by ranks
+  pub fn all() -> impl Iterator<Item = Square> {
+    (0..64).map(Square)
+  }
+
+  pub const fn index(self) -> usize {
+    self.0 as usize
+  }
+
+  pub const fn file(self) -> File {
+    File(self.0 % 8)
+  }
+
+  pub const fn rank(self) -> Rank {
+    Rank(self.0 / 8)
+  }
+
+  pub const fn bitboard(self) -> Bitboard {
+    Bitboard(1 << self.0)
+  }
+
+  // The number of king moves between the squares
+  pub fn distance(self, other: Square) -> usize {
+    self
+      .file()
+      .index()
+      .abs_diff(other.file().index())
+      .max(self.rank().index().abs_diff(other.rank().index()))
+  }
+
+  // Moves by a number of squares in the index order, e.g. 8 is one rank up. Has to stay on the board
+  const fn offset(self, delta: i8) -> Square {
+    Square((self.0 as i8 + delta) as u8)
+  }
+}
+
+impl FromStr for Square {
+  type Err = String;
+
+  // Like "e4"
+  fn from_str(square: &str) -> Result<Square, String> {
+    match square.as_bytes() {
+      [file @ b'a'..=b'h', rank @ b'1'..=b'8'] => Ok(Square::new(File(file - b'a'), Rank(rank - b'1'))),
+      _ => Err(format!("invalid square '{square}'")),
+    }
+  }
+}
+
+impl fmt::Display for Square {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(f, "{}{}", self.file(), self.rank())
+  }
+}
+
+// A set of squares
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Bitboard(pub u64);
+
+impl Bitboard {
+  pub const EMPTY: Bitboard = Bitboard(0);
+  pub const FULL: Bitboard = Bitboard(!0);
+
+  pub const fn is_empty(self) -> bool {
+    self.0 == 0
+  }
+
+  pub const fn contains(self, square: Square) -> bool {
+    self.0 & square.bitboard().0 != 0
+  }
+
+  pub const fn count(self) -> u32 {
+    self.0.count_ones()
+  }
+
+  // The square with the lowest index, the bitboard can't be empty
+  pub const fn lsb(self) -> Square {
+    Square(self.0.trailing_zeros() as u8)
+  }
+
+  // Every square moved one step, the ones which would leave the board are dropped
+  pub const fn north(self) -> Bitboard {
+    Bitboard(self.0 << 8)
+  }
+  pub const fn north_east(self) -> Bitboard {
+    Bitboard((self.0 & !FILE_H.0) << 9)
+  }
+  pub const fn east(self) -> Bitboard {
+    Bitboard((self.0 & !FILE_H.0) << 1)
+  }
+  pub const fn south_east(self) -> Bitboard {
+    Bitboard((self.0 & !FILE_H.0) >> 7)
+  }
+  pub const fn south(self) -> Bitboard {
+    Bitboard(self.0 >> 8)
+  }
+  pub const fn south_west(self) -> Bitboard {
+    Bitboard((self.0 & !FILE_A.0) >> 9)
+  }
+  pub const fn west(self) -> Bitboard {
+    Bitboard((self.0 & !FILE_A.0) >> 1)
+  }
+  pub const fn north_west(self) -> Bitboard {
+    Bitboard((self.0 & !FILE_A.0) << 7)
+  }
+
+  // Towards the other side of the board for the color, like its pawns move
+  pub const fn forward(self, color: Color) -> Bitboard {
+    match color {
+      Color::White => self.north(),
+      Color::Black => self.south(),
+    }
+  }
+  pub const fn forward_west(self, color: Color) -> Bitboard {
+    match color {
+      Color::White => self.north_west(),
+      Color::Black => self.south_west(),
+    }
+  }
+  pub const fn forward_east(self, color: Color) -> Bitboard {
+    match color {
+      Color::White => self.north_east(),
+      Color::Black => self.south_east(),
+    }
+  }
+
+  // Index into the attack tables
+  fn pext(self, mask: Bitboard) -> usize {
+    self.0.pext(mask.0) as usize
+  }
+}
+
+impl From<Square> for Bitboard {
+  fn from(square: Square) -> Bitboard {
+    square.bitboard()
+  }
+}
+
+macro_rules! impl_bit_op {
+  ($op:ident, $op_fn:ident, $assign:ident, $assign_fn:ident) => {
+    impl $op for Bitboard {
+      type Output = Bitboard;
+
+      fn $op_fn(self, other: Bitboard) -> Bitboard {
+        Bitboard($op::$op_fn(self.0, other.0))
+      }
+    }
+
+    impl $assign for Bitboard {
+      fn $assign_fn(&mut self, other: Bitboard) {
+        $assign::$assign_fn(&mut self.0, other.0);
+      }
+    }
+  };
+}
+
+impl_bit_op!(BitAnd, bitand, BitAndAssign, bitand_assign);
+impl_bit_op!(BitOr, bitor, BitOrAssign, bitor_assign);
+impl_bit_op!(BitXor, bitxor, BitXorAssign, bitxor_assign);
+
+impl Not for Bitboard {
+  type Output = Bitboard;
+
+  fn not(self) -> Bitboard {
+    Bitboard(!self.0)
+  }
+}
+
+// Goes through the squares from the lowest index
+pub struct Squares(u64);
+
+impl Iterator for Squares {
+  type Item = Square;
+
+  fn next(&mut self) -> Option<Square> {
+    if self.0 == 0 {
+      return None;
+    }
+
+    let square = Bitboard(self.0).lsb();
+    self.0 &= self.0 - 1;
+    Some(square)
+  }
+
+  fn size_hint(&self) -> (usize, Option<usize>) {
+    let count = self.0.count_ones() as usize;
+    (count, Some(count))
+  }
+}
+
+impl IntoIterator for Bitboard {
+  type Item = Square;
+  type IntoIter = Squares;
+
+  fn into_iter(self) -> Squares {
+    Squares(self.0)
+  }
+}
+
+// Like the board is seen by white, @ for the squares in the set
+impl fmt::Display for Bitboard {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    let ranks: Vec<String> = (0..8)
+      .rev()
+      .map(|rank| {
+        (0..8)
+          .map(|file| {
+            if self.contains(Square::new(File(file), Rank(rank))) {
+              "@"
+            } else {
+              "."
+            }
+          })
+          .collect::<Vec<&str>>()
+          .join(" ")
+      })
+      .collect();
+
+    write!(f, "{}", ranks.join("\n"))
+  }
+}
+
+pub const FILE_A: Bitboard = File::A.bitboard();
+pub const FILE_H: Bitboard = File::H.bitboard();
+
 const WHITE_PAWN_EVAL: [i32; 64] = [
   0, 0, 0, 0, 0, 0, 0, 0, //
   3, 3, 3, 3, 3, 3, 3, 3, //
@@ -391,113 +661,89 @@ const BISHOP_VALUE: i32 = 350;
 const ROOK_VALUE: i32 = 525;
 const QUEEN_VALUE: i32 = 1000;
 
-pub fn print_bitboard(b: u64) {
-  for y in 0..8 {
-    let mut line = "".to_string();
-    for x in 0..8 {
-      let i = 63 - (y * 8 + x);
-      let c = if get_bit(b, i) != 0 { "@ " } else { ". " };
-      line += c;
-    }
-    println!("{}", line);
+// The piece-square values, from a8 to h1
+const fn piece_square_table(color: Color, piece_type: PieceType) -> &'static [i32; 64] {
+  match (color, piece_type) {
+    (Color::White, PieceType::Pawn) => &WHITE_PAWN_EVAL,
+    (Color::Black, PieceType::Pawn) => &BLACK_PAWN_EVAL,
+    (Color::White, PieceType::Knight) => &WHITE_KNIGHT_EVAL,
+    (Color::Black, PieceType::Knight) => &BLACK_KNIGHT_EVAL,
+    (Color::White, PieceType::Bishop) => &WHITE_BISHOP_EVAL,
+    (Color::Black, PieceType::Bishop) => &BLACK_BISHOP_EVAL,
+    (Color::White, PieceType::Rook) => &WHITE_ROOK_EVAL,
+    (Color::Black, PieceType::Rook) => &BLACK_ROOK_EVAL,
+    (Color::White, PieceType::Queen) => &WHITE_QUEEN_EVAL,
+    (Color::Black, PieceType::Queen) => &BLACK_QUEEN_EVAL,
+    (Color::White, PieceType::King) => &WHITE_KING_EVAL,
+    (Color::Black, PieceType::King) => &BLACK_KING_EVAL,
   }
 }
 
+// Only for generating the tables below, which are indexed by square
 const fn set_bit(b: &mut u64, i: usize) {
   *b |= 1 << i;
 }
 
-// maybe its better have this be an array
 const fn get_bit(b: u64, i: usize) -> u64 {
   b & (1 << i)
 }
 
-const fn clear_bit(b: &mut u64, i: usize) {
-  *b &= !(1 << i);
-}
-
-const fn get_lsb(b: u64) -> usize {
-  b.trailing_zeros() as usize
-}
-
-const fn pop_lsb(b: &mut u64) -> usize {
-  let i = get_lsb(*b);
-  *b &= *b - 1;
-  i
-}
-
-const NOT_FILE_A: u64 = !FILE_A;
-const NOT_FILE_H: u64 = !FILE_H;
-
-const fn north(b: u64) -> u64 {
-  b << 8
-}
-const fn north_east(b: u64) -> u64 {
-  (b & NOT_FILE_H) << 7
-}
-const fn east(b: u64) -> u64 {
-  (b & NOT_FILE_H) >> 1
-}
-const fn south_east(b: u64) -> u64 {
-  (b & NOT_FILE_H) >> 9
-}
-const fn south(b: u64) -> u64 {
-  b >> 8
-}
-const fn south_west(b: u64) -> u64 {
-  (b & NOT_FILE_A) >> 7
-}
-const fn west(b: u64) -> u64 {
-  (b & NOT_FILE_A) << 1
-}
-const fn north_west(b: u64) -> u64 {
-  (b & NOT_FILE_A) << 9
-}
-
-pub const KNIGHT_MOVES: [u64; 64] = {
-  const fn gen_knight_moves(square: usize) -> u64 {
-    let mut b = 0_u64;
-
-    set_bit(&mut b, square);
-
-    ((b << 15 | b >> 17) & !FILE_A)
-      | ((b << 6 | b >> 10) & !(FILE_A | FILE_B))
-      | ((b << 17 | b >> 15) & !FILE_H)
-      | ((b << 10 | b >> 6) & !(FILE_G | FILE_H))
+const KNIGHT_MOVES: [Bitboard; 64] = {
+  const fn gen_knight_moves(square: Square) -> Bitboard {
+    let b = square.bitboard();
+    let (north, south, east, west) = (b.north(), b.south(), b.east(), b.west());
+
+    Bitboard(
+      north.north_east().0
+        | north.north_west().0
+        | south.south_east().0
+        | south.south_west().0
+        | east.north_east().0
+        | east.south_east().0
+        | west.north_west().0
+        | west.south_west().0,
+    )
   }
 
-  let mut moves = [0_u64; 64];
+  let mut moves = [Bitboard::EMPTY; 64];
 
   let mut i = 0;
   while i < 64 {
-    moves[i] = gen_knight_moves(i);
+    moves[i] = gen_knight_moves(Square(i as u8));
     i += 1;
   }
 
   moves
 };
 
-pub const KING_MOVES: [u64; 64] = {
-  const fn gen_king_moves(square: usize) -> u64 {
-    let mut b = 0_u64;
-
-    set_bit(&mut b, square);
-
-    north(b) | north_east(b) | east(b) | south_east(b) | south(b) | south_west(b) | west(b) | north_west(b)
+const KING_MOVES: [Bitboard; 64] = {
+  const fn gen_king_moves(square: Square) -> Bitboard {
+    let b = square.bitboard();
+
+    Bitboard(
+      b.north().0
+        | b.north_east().0
+        | b.east().0
+        | b.south_east().0
+        | b.south().0
+        | b.south_west().0
+        | b.west().0
+        | b.north_west().0,
+    )
   }
 
-  let mut moves = [0_u64; 64];
+  let mut moves = [Bitboard::EMPTY; 64];
 
   let mut i = 0;
   while i < 64 {
-    moves[i] = gen_king_moves(i);
+    moves[i] = gen_king_moves(Square(i as u8));
     i += 1;
   }
 
   moves
 };
 
-pub const ROOK_BLOCKER_MASKS: [u64; 64] = {
+const ROOK_BLOCKER_MASKS: [Bitboard; 64] = {
   const fn gen_rook_blocker_masks(square: usize) -> u64 {
     let mut board: u64 = 0;
     let square = square as isize;
@@ -529,18 +775,18 @@ pub const ROOK_BLOCKER_MASKS: [u64; 64] = {
     board
   }
 
-  let mut moves = [0_u64; 64];
+  let mut moves = [Bitboard::EMPTY; 64];
 
   let mut i = 0;
   while i < 64 {
-    moves[i] = gen_rook_blocker_masks(i);
+    moves[i] = Bitboard(gen_rook_blocker_masks(i));
     i += 1;
   }
 
   moves
 };
 
-pub const BISHOP_BLOCKER_MASKS: [u64; 64] = {
+const BISHOP_BLOCKER_MASKS: [Bitboard; 64] = {
   const fn gen_bishop_blocker_masks(square: usize) -> u64 {
     let mut board: u64 = 0;
     let square = square as isize;
@@ -580,11 +826,11 @@ pub const BISHOP_BLOCKER_MASKS: [u64; 64] = {
     board
   }
 
-  let mut moves = [0_u64; 64];
+  let mut moves = [Bitboard::EMPTY; 64];
 
   let mut i = 0;
   while i < 64 {
-    moves[i] = gen_bishop_blocker_masks(i);
+    moves[i] = Bitboard(gen_bishop_blocker_masks(i));
     i += 1;
   }
 
@@ -604,141 +850,47 @@ const fn slow_const_pext(value: u64, mut mask: u64) -> u64 {
   res
 }
 
-#[allow(clippy::large_const_arrays)]
-pub const ROOK_MAGICS: [[u64; 4096]; 64] = {
-  let mut magics = [[0_u64; 4096]; 64];
-
-  let mut square = 0;
-  while square < 64 {
-    let set = ROOK_BLOCKER_MASKS[square];
-
-    let mut subset: u64 = 0;
-    loop {
-      let pext_index = slow_const_pext(subset, set);
-
-      let mut moves: u64 = 0;
-
-      let mut x = (square % 8) as isize + 1;
-      let mut y = (square / 8) as isize;
-      while x < 8 {
-        let pos = (x + y * 8) as usize;
-        set_bit(&mut moves, pos);
-        if get_bit(subset, pos) != 0 {
-          break;
-        }
-        x += 1;
-      }
+// The squares a slider sees from a square in the given directions, including the first blocker in each of them
+const fn gen_slider_moves(square: usize, blockers: u64, directions: [(isize, isize); 4]) -> u64 {
+  let mut moves: u64 = 0;
 
-      let mut x = (square % 8) as isize;
-      let mut y = (square / 8) as isize + 1;
-      while y < 8 {
-        let pos = (x + y * 8) as usize;
-        set_bit(&mut moves, pos);
-        if get_bit(subset, pos) != 0 {
-          break;
-        }
-        y += 1;
-      }
-
-      let mut x = (square % 8) as isize - 1;
-      let mut y = (square / 8) as isize;
-      while x >= 0 {
-        let pos = (x + y * 8) as usize;
-        set_bit(&mut moves, pos);
-        if get_bit(subset, pos) != 0 {
-          break;
-        }
-        x -= 1;
-      }
+  let mut d = 0;
+  while d < 4 {
+    let (dx, dy) = directions[d];
+    let mut x = (square % 8) as isize + dx;
+    let mut y = (square / 8) as isize + dy;
 
-      let mut x = (square % 8) as isize;
-      let mut y = (square / 8) as isize - 1;
-      while y >= 0 {
-        let pos = (x + y * 8) as usize;
-        set_bit(&mut moves, pos);
-        if get_bit(subset, pos) != 0 {
-          break;
-        }
-        y -= 1;
-      }
-
-      magics[square][pext_index as usize] = moves;
-
-      subset = subset.wrapping_sub(set) & set;
-      if subset == 0 {
+    while x >= 0 && x < 8 && y >= 0 && y < 8 {
+      let pos = (x + y * 8) as usize;
+      set_bit(&mut moves, pos);
+      if get_bit(blockers, pos) != 0 {
         break;
       }
+      x += dx;
+      y += dy;
     }
 
-    square += 1;
+    d += 1;
   }
 
-  magics
-};
+  moves
+}
 
-#[allow(clippy::large_const_arrays)]
-pub const BISHOP_MAGICS: [[u64; 4096]; 64] = {
-  let mut magics = [[0_u64; 4096]; 64];
+const ROOK_DIRECTIONS: [(isize, isize); 4] = [(1, 0), (0, 1), (-1, 0), (0, -1)];
+const BISHOP_DIRECTIONS: [(isize, isize); 4] = [(1, 1), (-1, 1), (-1, -1), (1, -1)];
+
+// Every subset of the blocker mask of every square, indexed by pext
+const fn gen_magics(blocker_masks: &[Bitboard; 64], directions: [(isize, isize); 4]) -> [[Bitboard; 4096]; 64] {
+  let mut magics = [[Bitboard::EMPTY; 4096]; 64];
 
   let mut square = 0;
   while square < 64 {
-    let set = BISHOP_BLOCKER_MASKS[square];
+    let set = blocker_masks[square].0;
 
     let mut subset: u64 = 0;
     loop {
       let pext_index = slow_const_pext(subset, set);
-
-      let mut moves: u64 = 0;
-
-      let mut x = (square % 8) as isize + 1;
-      let mut y = (square / 8) as isize + 1;
-      while x < 8 && y < 8 {
-        let pos = (x + y * 8) as usize;
-        set_bit(&mut moves, pos);
-        if get_bit(subset, pos) != 0 {
-          break;
-        }
-        x += 1;
-        y += 1;
-      }
-
-      let mut x = (square % 8) as isize - 1;
-      let mut y = (square / 8) as isize + 1;
-      while x >= 0 && y < 8 {
-        let pos = (x + y * 8) as usize;
-        set_bit(&mut moves, pos);
-        if get_bit(subset, pos) != 0 {
-          break;
-        }
-        x -= 1;
-        y += 1;
-      }
-
-      let mut x = (square % 8) as isize - 1;
-      let mut y = (square / 8) as isize - 1;
-      while x >= 0 && y >= 0 {
-        let pos = (x + y * 8) as usize;
-        set_bit(&mut moves, pos);
-        if get_bit(subset, pos) != 0 {
-          break;
-        }
-        x -= 1;
-        y -= 1;
-      }
-
-      let mut x = (square % 8) as isize + 1;
-      let mut y = (square / 8) as isize - 1;
-      while x < 8 && y >= 0 {
-        let pos = (x + y * 8) as usize;
-        set_bit(&mut moves, pos);
-        if get_bit(subset, pos) != 0 {
-          break;
-        }
-        x += 1;
-        y -= 1;
-      }
-
-      magics[square][pext_index as usize] = moves;
+      magics[square][pext_index as usize] = Bitboard(gen_slider_moves(square, subset, directions));
 
       subset = subset.wrapping_sub(set) & set;
       if subset == 0 {
@@ -750,7 +902,33 @@ pub const BISHOP_MAGICS: [[u64; 4096]; 64] = {
   }
 
   magics
-};
+}
+
+#[allow(clippy::large_const_arrays)]
+const ROOK_MAGICS: [[Bitboard; 4096]; 64] = gen_magics(&ROOK_BLOCKER_MASKS, ROOK_DIRECTIONS);
+
+#[allow(clippy::large_const_arrays)]
+const BISHOP_MAGICS: [[Bitboard; 4096]; 64] = gen_magics(&BISHOP_BLOCKER_MASKS, BISHOP_DIRECTIONS);
+
+pub fn knight_attacks(square: Square) -> Bitboard {
+  KNIGHT_MOVES[square.index()]
+}
+
+pub fn king_attacks(square: Square) -> Bitboard {
+  KING_MOVES[square.index()]
+}
+
+pub fn rook_attacks(square: Square, blockers: Bitboard) -> Bitboard {
+  ROOK_MAGICS[square.index()][blockers.pext(ROOK_BLOCKER_MASKS[square.index()])]
+}
+
+pub fn bishop_attacks(square: Square, blockers: Bitboard) -> Bitboard {
+  BISHOP_MAGICS[square.index()][blockers.pext(BISHOP_BLOCKER_MASKS[square.index()])]
+}
+
+pub fn queen_attacks(square: Square, blockers: Bitboard) -> Bitboard {
+  rook_attacks(square, blockers) | bishop_attacks(square, blockers)
+}
 
 // Includes the from square
 // PATH[from][to]
@@ -874,7 +1052,7 @@ pub struct HashTable {
   black_king_castle: u64,
   black_queen_castle: u64,
 
-  // Eight numbers to indicate the file of a valid en passant square
+  // Eight numbers to indicate the file of an en passant square where a capture is possible
   en_passant: [u64; 8],
 }
 
@@ -918,32 +1096,64 @@ impl HashTable {
       en_passant,
     }
   }
+
+  fn piece(&self, piece: Piece, square: Square) -> u64 {
+    self.pieces[square.index()][piece.index()]
+  }
+
+  fn en_passant(&self, square: Square) -> u64 {
+    self.en_passant[square.file().index()]
+  }
 }
 
 lazy_static! {
   pub static ref HASH_TABLE: HashTable = HashTable::new();
+
+  // One number for each piece, added to the material hash once for every such piece on the board.
+  // Shared by both boards, so their material hashes are the same.
+  // White pieces: pawn, knight, bishop, rook, queen, king, and then black pieces
+  pub static ref MATERIAL: [u64; 12] = {
+    let mut rng = StdRng::seed_from_u64(3894021757);
+    [(); 12].map(|_| rng.next_u64())
+  };
+}
+
+fn material(piece: Piece) -> u64 {
+  MATERIAL[piece.color.index() * 6 + piece.piece_type.index()]
+}
+
+// The material hash of a position with these pieces
+pub fn material_key(pieces: &[Piece]) -> u64 {
+  pieces
+    .iter()
+    .fold(0, |key: u64, piece| key.wrapping_add(material(*piece)))
 }
 
-#[derive(Clone)]
+#[derive(Clone, Debug, PartialEq)]
 pub struct BoardMeta {
   white_king_castle: bool,
   white_queen_castle: bool,
   black_king_castle: bool,
   black_queen_castle: bool,
 
-  en_passant_bitboard: u64,
+  en_passant: Option<Square>,
 
   halfmove_clock: u8,
   pub hash: u64,
+  /// Zobrist hash of only the pawns
+  pub pawn_hash: u64,
+  /// Sum of the material numbers of every piece, so it only depends on how many pieces of each kind there are
+  pub material_hash: u64,
 }
 
 /// Chess board
-#[derive(Clone)]
+#[derive(Clone, Debug, PartialEq)]
 pub struct Board {
-  /// To get a specific bitboard: `Color + Piece` or just `Color`
-  /// White pieces, black pieces, white pawns, black pawns, white knights...
-  bitboards: [u64; 14],
-  pub white_to_move: bool,
+  /// To get a specific bitboard: `pieces[color][piece type]`
+  pieces: [[Bitboard; 6]; 2],
+  /// All the pieces of a color
+  colors: [Bitboard; 2],
+  pub side_to_move: Color,
   fullmove_counter: u32,
   pub meta: BoardMeta,
   previous_hashes: Vec<u64>,
@@ -955,141 +1165,109 @@ pub struct Board {
 
 // pub const DOUBLE_CHECK: u64 = 0b10101010101010101010101101011001100;
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MoveKind {
+  Normal,
+  // To the piece type
+  Promotion(PieceType),
+  EnPassant,
+  // The king's move, the rook follows
+  Castling,
+}
+
+// The from square (6 bits), the to square (6 bits), the promotion piece (2 bits) and the move kind (2 bits)
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub struct ChessMove(u16);
 
-pub const NO_PROMOTION: u16 = 0b00 << 12;
-pub const KNIGHT_PROMOTION: u16 = 0b00 << 12;
-pub const BISHOP_PROMOTION: u16 = 0b01 << 12;
-pub const ROOK_PROMOTION: u16 = 0b10 << 12;
-pub const QUEEN_PROMOTION: u16 = 0b11 << 12;
+const NORMAL_MOVE: u16 = 0b00 << 14;
+const PROMOTION_MOVE: u16 = 0b01 << 14;
+const EN_PASSANT_MOVE: u16 = 0b10 << 14;
+const CASTLING_MOVE: u16 = 0b11 << 14;
 
-pub const NORMAL_MOVE: u16 = 0b00 << 14;
-pub const PROMOTION_MOVE: u16 = 0b01 << 14;
-pub const EN_PASSANT_MOVE: u16 = 0b10 << 14;
-pub const CASTLING_MOVE: u16 = 0b11 << 14;
+// Only knights to queens can be promoted to, they are stored from 0
+const PROMOTIONS: [PieceType; 4] = [PieceType::Knight, PieceType::Bishop, PieceType::Rook, PieceType::Queen];
 
 impl ChessMove {
-  pub fn new(from: usize, to: usize, promotion: u16, move_type: u16) -> Self {
-    Self((from as u16) ^ ((to as u16) << 6) ^ promotion ^ move_type)
+  pub fn new(from: Square, to: Square, kind: MoveKind) -> Self {
+    let kind = match kind {
+      MoveKind::Normal => NORMAL_MOVE,
+      MoveKind::Promotion(piece_type) => {
+        debug_assert!(PROMOTIONS.contains(&piece_type), "Can't promote to a pawn or a king");
+        PROMOTION_MOVE | (piece_type.index() as u16 - 1) << 12
+      }
+      MoveKind::EnPassant => EN_PASSANT_MOVE,
+      MoveKind::Castling => CASTLING_MOVE,
+    };
+
+    Self(from.0 as u16 | (to.0 as u16) << 6 | kind)
   }
-  pub fn from(&self) -> usize {
-    (self.0 & 0b111111_u16) as usize
+  pub fn from(&self) -> Square {
+    Square((self.0 & 0b111111) as u8)
   }
-  pub fn to(&self) -> usize {
-    ((self.0 >> 6) & 0b111111_u16) as usize
+  pub fn to(&self) -> Square {
+    Square(((self.0 >> 6) & 0b111111) as u8)
   }
-  fn promotion(&self) -> u16 {
-    self.0 & (0b11_u16 << 12)
+  pub fn kind(&self) -> MoveKind {
+    match self.0 & (0b11 << 14) {
+      NORMAL_MOVE => MoveKind::Normal,
+      PROMOTION_MOVE => MoveKind::Promotion(PROMOTIONS[((self.0 >> 12) & 0b11) as usize]),
+      EN_PASSANT_MOVE => MoveKind::EnPassant,
+      _ => MoveKind::Castling,
+    }
   }
-  fn move_type(&self) -> u16 {
-    self.0 & (0b11_u16 << 14)
+  pub fn promotion(&self) -> Option<PieceType> {
+    match self.kind() {
+      MoveKind::Promotion(piece_type) => Some(piece_type),
+      _ => None,
+    }
   }
   pub fn to_fen(&self) -> String {
-    let from_bb = 1 << self.from();
-    let to_bb = 1 << self.to();
-
-    let mut s = format!("{}{}", bitboard_to_square(from_bb), bitboard_to_square(to_bb));
-
-    if self.move_type() == PROMOTION_MOVE {
-      s += match self.promotion() {
-        KNIGHT_PROMOTION => "n",
-        BISHOP_PROMOTION => "b",
-        ROOK_PROMOTION => "r",
-        QUEEN_PROMOTION => "q",
-        _ => panic!(),
-      }
+    match self.promotion() {
+      Some(piece_type) => format!("{}{}{}", self.from(), self.to(), piece_type.to_char().to_ascii_lowercase()),
+      None => format!("{}{}", self.from(), self.to()),
     }
-
-    s
   }
   // Needs a board before the move was made
   pub fn is_capture(&self, board: &Board) -> bool {
-    if self.move_type() == EN_PASSANT_MOVE {
-      return true;
-    }
-
-    let other_color = if board.white_to_move { 1 } else { 0 };
-    let other_piece = board.piece_on_with_color(self.to(), other_color);
-    // let other_piece = board.piece_on(self.to());
-
-    other_piece != EMPTY_SQUARE
+    self.kind() == MoveKind::EnPassant || board.occupied_by(!board.side_to_move).contains(self.to())
   }
 
   fn evaluate_relative(&self, board: &Board) -> i32 {
-    const fn pv(piece: usize) -> i32 {
-      let c = piece % 2;
-      let piece = piece - c;
-      match piece {
-        PAWN => PAWN_VALUE,
-        KNIGHT => KNIGHT_VALUE,
-        BISHOP => BISHOP_VALUE,
-        ROOK => ROOK_VALUE,
-        QUEEN => QUEEN_VALUE,
-        _ => 0,
-      }
-    }
-
-    let color = if board.white_to_move { 0 } else { 1 };
+    let color = board.side_to_move;
+    let captured_value = board.piece_type_on(self.to(), !color).map_or(0, PieceType::value);
 
-    match self.move_type() {
-      NORMAL_MOVE => {
-        let from_piece = board.piece_on_with_color(self.from(), color);
-        let to_piece = board.piece_on_with_color(self.to(), color ^ 1);
-
-        if to_piece == EMPTY_SQUARE {
+    match self.kind() {
+      MoveKind::Normal => {
+        if captured_value == 0 {
           0
         } else {
-          pv(to_piece) - pv(from_piece)
+          let moved = board.piece_type_on(self.from(), color).expect("No piece to move");
+          captured_value - moved.value()
         }
       }
-      PROMOTION_MOVE => {
-        let to_piece = board.piece_on_with_color(self.to(), color ^ 1);
-        let selected_piece = match self.promotion() {
-          KNIGHT_PROMOTION => KNIGHT,
-          BISHOP_PROMOTION => BISHOP,
-          ROOK_PROMOTION => ROOK,
-          QUEEN_PROMOTION => QUEEN,
-          _ => panic!(),
-        } + color;
-
-        pv(to_piece) + pv(selected_piece)
-      }
-      EN_PASSANT_MOVE => 10,
-      CASTLING_MOVE => 20,
-      _ => panic!(),
+      MoveKind::Promotion(piece_type) => captured_value + piece_type.value(),
+      MoveKind::EnPassant => 10,
+      MoveKind::Castling => 20,
     }
   }
 }
 
+// Like "e2e4" or "e7e8q"
+impl fmt::Display for ChessMove {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    f.write_str(&self.to_fen())
+  }
+}
+
 impl Board {
   pub fn print(&self) {
     let mut lines: Vec<String> = vec![];
-    for y in 0..8 {
+    for rank in (0..8).rev() {
       let mut line: Vec<String> = vec![];
-      for x in 0..8 {
-        let i = 63 - (y * 8 + x);
-        let mut piece = self.piece_on(i);
-
-        let color = piece % 2;
-        piece -= color;
-
-        let mut c = match piece {
-          PAWN => "P",
-          KNIGHT => "N",
-          BISHOP => "B",
-          ROOK => "R",
-          QUEEN => "Q",
-          KING => "K",
-          _ => " ",
-        }
-        .to_string();
-
-        if color == BLACK {
-          c = c.to_lowercase();
-        }
-
-        line.push(c);
+      for file in 0..8 {
+        let square = Square::new(File(file), Rank(rank));
+        let c = self.piece_on(square).map_or(' ', Piece::to_char);
+        line.push(c.to_string());
       }
       lines.push(line.join(" | "));
     }
@@ -1100,106 +1278,216 @@ impl Board {
     Self::from_fen_saved(fen, vec![])
   }
 
+  // from_fen doesn't check the fen, see board::Board::normalize_fen
+  pub fn try_from_fen(fen: &str) -> Result<Self, String> {
+    Ok(Self::from_fen(&crate::board::Board::normalize_fen(fen)?))
+  }
+
   pub fn from_fen_saved(fen: &str, previous_hashes: Vec<u64>) -> Self {
     let fields: Vec<&str> = fen.split(' ').collect();
     let ranks: Vec<&str> = fields[0].split('/').collect();
 
-    let mut bitboards = [0_u64; 14];
-    let mut hash: u64 = 0;
+    let mut board = Board {
+      pieces: [[Bitboard::EMPTY; 6]; 2],
+      colors: [Bitboard::EMPTY; 2],
+      side_to_move: if fields[1] == "w" { Color::White } else { Color::Black },
+      fullmove_counter: fields[5].parse().unwrap(),
+      meta: BoardMeta {
+        white_king_castle: fields[2].contains('K'),
+        white_queen_castle: fields[2].contains('Q'),
+        black_king_castle: fields[2].contains('k'),
+        black_queen_castle: fields[2].contains('q'),
+        en_passant: match fields[3] {
+          "-" => None,
+          square => Some(square.parse().expect("Incorrect fen")),
+        },
+        halfmove_clock: fields[4].parse().unwrap(),
+        hash: 0,
+        pawn_hash: 0,
+        material_hash: 0,
+      },
+      previous_hashes,
+      // seen_squares: 0,
+      // checked_squares: 0,
+      // pinned_hv: 0,
+      // pinned_diag: 0,
+    };
 
-    for (y, s) in ranks.iter().enumerate() {
-      let mut x = 0;
+    // The first rank in a fen is the 8th
+    for (rank, s) in ranks.iter().rev().enumerate() {
+      let mut file = 0;
 
       for c in s.chars() {
-        if c.is_alphabetic() {
-          let color = if c.is_uppercase() { WHITE } else { BLACK };
+        if let Some(digit) = c.to_digit(10) {
+          file += digit as u8;
+        } else {
+          let piece = Piece::from_char(c).expect("Incorrect fen");
+          board.toggle_piece(piece, Square::new(File(file), Rank(rank as u8)));
+          file += 1;
+        }
+      }
+    }
+
+    board.meta.hash = board.compute_hash_from_scratch();
+    board.meta.pawn_hash = board.compute_pawn_hash_from_scratch();
+    board.meta.material_hash = board.compute_material_hash_from_scratch();
 
-          let piece = match c.to_uppercase().to_string().as_str() {
-            "P" => PAWN,
-            "N" => KNIGHT,
-            "B" => BISHOP,
-            "R" => ROOK,
-            "Q" => QUEEN,
-            "K" => KING,
-            _ => panic!("Incorrect fen"),
-          };
+    // board.seen_squares = board.gen_seen_squares();
+    // board.checked_squares = board.gen_checked_squares();
+
+    board
+  }
 
-          let i = 63 - (y * 8 + x);
+  pub fn pieces(&self, color: Color, piece_type: PieceType) -> Bitboard {
+    self.pieces[color.index()][piece_type.index()]
+  }
 
-          set_bit(&mut bitboards[color + piece], i);
-          set_bit(&mut bitboards[color], i);
+  pub fn occupied_by(&self, color: Color) -> Bitboard {
+    self.colors[color.index()]
+  }
 
-          hash ^= HASH_TABLE.pieces[i][color + piece - 2];
+  pub fn occupied(&self) -> Bitboard {
+    self.colors[0] | self.colors[1]
+  }
 
-          x += 1;
-        } else {
-          let digit = c.to_digit(10).expect("Incorrect fen") as usize;
-          x += digit;
+  pub fn piece_on(&self, square: Square) -> Option<Piece> {
+    Color::ALL.into_iter().find_map(|color| {
+      self
+        .piece_type_on(square, color)
+        .map(|piece_type| Piece::new(color, piece_type))
+    })
+  }
+
+  fn piece_type_on(&self, square: Square, color: Color) -> Option<PieceType> {
+    PieceType::ALL
+      .into_iter()
+      .find(|piece_type| self.pieces(color, *piece_type).contains(square))
+  }
+
+  // Puts the piece on the square or takes it from there, the hashes included
+  fn toggle_piece(&mut self, piece: Piece, square: Square) {
+    let key = HASH_TABLE.piece(piece, square);
+    if self.pieces(piece.color, piece.piece_type).contains(square) {
+      self.meta.material_hash = self.meta.material_hash.wrapping_sub(material(piece));
+    } else {
+      self.meta.material_hash = self.meta.material_hash.wrapping_add(material(piece));
+    }
+
+    self.pieces[piece.color.index()][piece.piece_type.index()] ^= square.bitboard();
+    self.colors[piece.color.index()] ^= square.bitboard();
+    self.meta.hash ^= key;
+    if piece.piece_type == PieceType::Pawn {
+      self.meta.pawn_hash ^= key;
+    }
+  }
+
+  pub fn compute_hash_from_scratch(&self) -> u64 {
+    let mut hash = 0;
+
+    for color in Color::ALL {
+      for piece_type in PieceType::ALL {
+        for square in self.pieces(color, piece_type) {
+          hash ^= HASH_TABLE.piece(Piece::new(color, piece_type), square);
         }
       }
     }
 
-    let white_to_move = fields[1] == "w";
-
-    if !white_to_move {
+    if self.side_to_move == Color::Black {
       hash ^= HASH_TABLE.black_to_move;
     }
-
-    let white_king_castle = fields[2].contains('K');
-    let white_queen_castle = fields[2].contains('Q');
-    let black_king_castle = fields[2].contains('k');
-    let black_queen_castle = fields[2].contains('q');
-
-    if white_king_castle {
+    if self.meta.white_king_castle {
       hash ^= HASH_TABLE.white_king_castle;
     }
-    if white_queen_castle {
+    if self.meta.white_queen_castle {
       hash ^= HASH_TABLE.white_queen_castle;
     }
-    if black_king_castle {
+    if self.meta.black_king_castle {
       hash ^= HASH_TABLE.black_king_castle;
     }
-    if black_queen_castle {
+    if self.meta.black_queen_castle {
       hash ^= HASH_TABLE.black_queen_castle;
     }
 
-    let en_passant_bitboard = match fields[3] {
-      "-" => 0,
-      square => square_to_bitboard(square),
-    };
+    hash ^ self.en_passant_key()
+  }
+
+  pub fn compute_pawn_hash_from_scratch(&self) -> u64 {
+    Color::ALL
+      .into_iter()
+      .flat_map(|color| {
+        let pawn = Piece::new(color, PieceType::Pawn);
+        self
+          .pieces(color, PieceType::Pawn)
+          .into_iter()
+          .map(move |square| HASH_TABLE.piece(pawn, square))
+      })
+      .fold(0, |hash, key| hash ^ key)
+  }
+
+  pub fn compute_material_hash_from_scratch(&self) -> u64 {
+    let mut hash: u64 = 0;
 
-    if en_passant_bitboard != 0 {
-      let x = get_lsb(en_passant_bitboard) % 8;
-      hash ^= HASH_TABLE.en_passant[x];
+    for color in Color::ALL {
+      for piece_type in PieceType::ALL {
+        let count = self.pieces(color, piece_type).count() as u64;
+        hash = hash.wrapping_add(material(Piece::new(color, piece_type)).wrapping_mul(count));
+      }
     }
 
-    let halfmove_clock = fields[4].parse().unwrap();
-    let fullmove_counter = fields[5].parse().unwrap();
+    hash
+  }
 
-    let mut board = Board {
-      bitboards,
-      white_to_move,
-      fullmove_counter,
-      meta: BoardMeta {
-        white_king_castle,
-        white_queen_castle,
-        black_king_castle,
-        black_queen_castle,
-        en_passant_bitboard,
-        halfmove_clock,
-        hash,
-      },
-      previous_hashes,
-      // seen_squares: 0,
-      // checked_squares: 0,
-      // pinned_hv: 0,
-      // pinned_diag: 0,
+  // The hashes are updated move by move, debug builds check them against the ones computed from scratch
+  fn debug_check_hashes(&self) {
+    debug_assert_eq!(self.meta.hash, self.compute_hash_from_scratch(), "{}", self.to_fen());
+    debug_assert_eq!(
+      self.meta.pawn_hash,
+      self.compute_pawn_hash_from_scratch(),
+      "{}",
+      self.to_fen()
+    );
+    debug_assert_eq!(
+      self.meta.material_hash,
+      self.compute_material_hash_from_scratch(),
+      "{}",
+      self.to_fen()
+    );
+  }
+
+  // Whether the side to move has a legal en passant capture. Only then the en passant square is in the hash,
+  // so that the same position after a double push or after any other move has the same hash
+  fn can_capture_en_passant(&self) -> bool {
+    let Some(to) = self.meta.en_passant else {
+      return false;
     };
+    let color = self.side_to_move;
+    let target = to.bitboard();
+    let captured = target.forward(!color).lsb();
+    let capturers = (target.forward_west(!color) | target.forward_east(!color)) & self.pieces(color, PieceType::Pawn);
+
+    capturers.into_iter().any(|from| {
+      // Without the previous hashes, they aren't needed to look for a check
+      let mut board = Board {
+        meta: self.meta.clone(),
+        previous_hashes: vec![],
+        ..*self
+      };
+      board.toggle_piece(Piece::new(!color, PieceType::Pawn), captured);
+      board.move_piece(Piece::new(color, PieceType::Pawn), from, to);
+      !board.in_check(color)
+    })
+  }
 
-    // board.seen_squares = board.gen_seen_squares();
-    // board.checked_squares = board.gen_checked_squares();
+  fn en_passant_key(&self) -> u64 {
+    match self.meta.en_passant {
+      Some(square) if self.can_capture_en_passant() => HASH_TABLE.en_passant(square),
+      _ => 0,
+    }
+  }
 
-    board
+  fn move_piece(&mut self, piece: Piece, from: Square, to: Square) {
+    self.toggle_piece(piece, from);
+    self.toggle_piece(piece, to);
   }
 
   // Can be used after .make_move
@@ -1386,365 +1674,167 @@ impl Board {
   // }
 
   // Assuming `color` is the same as the side to move
-  fn pawn_moves(&self, color: usize, chess_moves: &mut Vec<ChessMove>) {
-    if color == WHITE {
-      let empty = !(self.bitboards[WHITE] ^ self.bitboards[BLACK]);
-      let black = self.bitboards[BLACK];
-      let white_pawns = self.bitboards[WHITE + PAWN];
-
-      let mut white_north_pawns = north(white_pawns & (!RANK_7)) & empty;
-      let mut white_double_north_pawns = north(white_north_pawns & RANK_3) & empty;
-
-      let mut white_north_west_captures = north_west(white_pawns & (!RANK_7)) & black;
-      let mut white_north_east_captures = north_east(white_pawns & (!RANK_7)) & black;
-
-      let white_promotions_pawns = white_pawns & RANK_7;
-
-      let mut white_north_promotions = north(white_promotions_pawns) & empty;
-      let mut white_north_west_promotions = north_west(white_promotions_pawns) & black;
-      let mut white_north_east_promotions = north_east(white_promotions_pawns) & black;
-
-      if self.meta.en_passant_bitboard != 0 {
-        let mut en_passant_pawns =
-          (south_west(self.meta.en_passant_bitboard) | south_east(self.meta.en_passant_bitboard)) & white_pawns;
-
-        let to_index = get_lsb(self.meta.en_passant_bitboard);
-        while en_passant_pawns != 0 {
-          let from_index = pop_lsb(&mut en_passant_pawns);
-          chess_moves.push(ChessMove::new(from_index, to_index, NO_PROMOTION, EN_PASSANT_MOVE));
-        }
-      }
-
-      while white_north_pawns != 0 {
-        let to_index = pop_lsb(&mut white_north_pawns);
-        let from_index = to_index - 8;
-        chess_moves.push(ChessMove::new(from_index, to_index, NO_PROMOTION, NORMAL_MOVE))
-      }
-
-      while white_double_north_pawns != 0 {
-        let to_index = pop_lsb(&mut white_double_north_pawns);
-        let from_index = to_index - 16;
-        chess_moves.push(ChessMove::new(from_index, to_index, NO_PROMOTION, NORMAL_MOVE))
-      }
-
-      while white_north_west_captures != 0 {
-        let to_index = pop_lsb(&mut white_north_west_captures);
-        let from_index = to_index - 9;
-        chess_moves.push(ChessMove::new(from_index, to_index, NO_PROMOTION, NORMAL_MOVE))
-      }
-
-      while white_north_east_captures != 0 {
-        let to_index = pop_lsb(&mut white_north_east_captures);
-        let from_index = to_index - 7;
-        chess_moves.push(ChessMove::new(from_index, to_index, NO_PROMOTION, NORMAL_MOVE))
-      }
-
-      while white_north_promotions != 0 {
-        let to_index = pop_lsb(&mut white_north_promotions);
-        let from_index = to_index - 8;
-        chess_moves.push(ChessMove::new(from_index, to_index, KNIGHT_PROMOTION, PROMOTION_MOVE));
-        chess_moves.push(ChessMove::new(from_index, to_index, BISHOP_PROMOTION, PROMOTION_MOVE));
-        chess_moves.push(ChessMove::new(from_index, to_index, ROOK_PROMOTION, PROMOTION_MOVE));
-        chess_moves.push(ChessMove::new(from_index, to_index, QUEEN_PROMOTION, PROMOTION_MOVE));
-      }
-
-      while white_north_west_promotions != 0 {
-        let to_index = pop_lsb(&mut white_north_west_promotions);
-        let from_index = to_index - 9;
-        chess_moves.push(ChessMove::new(from_index, to_index, KNIGHT_PROMOTION, PROMOTION_MOVE));
-        chess_moves.push(ChessMove::new(from_index, to_index, BISHOP_PROMOTION, PROMOTION_MOVE));
-        chess_moves.push(ChessMove::new(from_index, to_index, ROOK_PROMOTION, PROMOTION_MOVE));
-        chess_moves.push(ChessMove::new(from_index, to_index, QUEEN_PROMOTION, PROMOTION_MOVE));
-      }
-
-      while white_north_east_promotions != 0 {
-        let to_index = pop_lsb(&mut white_north_east_promotions);
-        let from_index = to_index - 7;
-        chess_moves.push(ChessMove::new(from_index, to_index, KNIGHT_PROMOTION, PROMOTION_MOVE));
-        chess_moves.push(ChessMove::new(from_index, to_index, BISHOP_PROMOTION, PROMOTION_MOVE));
-        chess_moves.push(ChessMove::new(from_index, to_index, ROOK_PROMOTION, PROMOTION_MOVE));
-        chess_moves.push(ChessMove::new(from_index, to_index, QUEEN_PROMOTION, PROMOTION_MOVE));
-      }
-    } else {
-      let empty = !(self.bitboards[WHITE] ^ self.bitboards[BLACK]);
-      let white = self.bitboards[WHITE];
-      let black_pawns = self.bitboards[BLACK + PAWN];
-
-      let mut black_south_pawns = south(black_pawns & (!RANK_2)) & empty;
-      let mut black_double_south_pawns = south(black_south_pawns & RANK_6) & empty;
-
-      // TODO: black_pawns & (!RANK_2)
-      let mut black_south_west_captures = south_west(black_pawns & (!RANK_2)) & white;
-      let mut black_south_east_captures = south_east(black_pawns & (!RANK_2)) & white;
-
-      let black_promotions_pawns = black_pawns & RANK_2;
-
-      let mut black_south_promotions = south(black_promotions_pawns) & empty;
-      let mut black_south_west_promotions = south_west(black_promotions_pawns) & white;
-      let mut black_south_east_promotions = south_east(black_promotions_pawns) & white;
-
-      if self.meta.en_passant_bitboard != 0 {
-        let mut en_passant_pawns =
-          (north_west(self.meta.en_passant_bitboard) | north_east(self.meta.en_passant_bitboard)) & black_pawns;
-
-        let to_index = get_lsb(self.meta.en_passant_bitboard);
-        while en_passant_pawns != 0 {
-          let from_index = pop_lsb(&mut en_passant_pawns);
-          chess_moves.push(ChessMove::new(from_index, to_index, NO_PROMOTION, EN_PASSANT_MOVE));
-        }
-      }
-
-      while black_south_pawns != 0 {
-        let to_index = pop_lsb(&mut black_south_pawns);
-        let from_index = to_index + 8;
-        chess_moves.push(ChessMove::new(from_index, to_index, NO_PROMOTION, NORMAL_MOVE))
-      }
-
-      while black_double_south_pawns != 0 {
-        let to_index = pop_lsb(&mut black_double_south_pawns);
-        let from_index = to_index + 16;
-        chess_moves.push(ChessMove::new(from_index, to_index, NO_PROMOTION, NORMAL_MOVE))
-      }
-
-      while black_south_west_captures != 0 {
-        let to_index = pop_lsb(&mut black_south_west_captures);
-        let from_index = to_index + 7;
-        chess_moves.push(ChessMove::new(from_index, to_index, NO_PROMOTION, NORMAL_MOVE))
-      }
-
-      while black_south_east_captures != 0 {
-        let to_index = pop_lsb(&mut black_south_east_captures);
-        let from_index = to_index + 9;
-        chess_moves.push(ChessMove::new(from_index, to_index, NO_PROMOTION, NORMAL_MOVE))
-      }
-
-      while black_south_promotions != 0 {
-        let to_index = pop_lsb(&mut black_south_promotions);
-        let from_index = to_index + 8;
-        chess_moves.push(ChessMove::new(from_index, to_index, KNIGHT_PROMOTION, PROMOTION_MOVE));
-        chess_moves.push(ChessMove::new(from_index, to_index, BISHOP_PROMOTION, PROMOTION_MOVE));
-        chess_moves.push(ChessMove::new(from_index, to_index, ROOK_PROMOTION, PROMOTION_MOVE));
-        chess_moves.push(ChessMove::new(from_index, to_index, QUEEN_PROMOTION, PROMOTION_MOVE));
-      }
-
-      while black_south_west_promotions != 0 {
-        let to_index = pop_lsb(&mut black_south_west_promotions);
-        let from_index = to_index + 7;
-        chess_moves.push(ChessMove::new(from_index, to_index, KNIGHT_PROMOTION, PROMOTION_MOVE));
-        chess_moves.push(ChessMove::new(from_index, to_index, BISHOP_PROMOTION, PROMOTION_MOVE));
-        chess_moves.push(ChessMove::new(from_index, to_index, ROOK_PROMOTION, PROMOTION_MOVE));
-        chess_moves.push(ChessMove::new(from_index, to_index, QUEEN_PROMOTION, PROMOTION_MOVE));
-      }
-
-      while black_south_east_promotions != 0 {
-        let to_index = pop_lsb(&mut black_south_east_promotions);
-        let from_index = to_index + 9;
-        chess_moves.push(ChessMove::new(from_index, to_index, KNIGHT_PROMOTION, PROMOTION_MOVE));
-        chess_moves.push(ChessMove::new(from_index, to_index, BISHOP_PROMOTION, PROMOTION_MOVE));
-        chess_moves.push(ChessMove::new(from_index, to_index, ROOK_PROMOTION, PROMOTION_MOVE));
-        chess_moves.push(ChessMove::new(from_index, to_index, QUEEN_PROMOTION, PROMOTION_MOVE));
-      }
-    }
-  }
-
-  fn knight_moves(&self, color: usize, chess_moves: &mut Vec<ChessMove>) {
-    let target_squares = if color == 0 {
-      !self.bitboards[WHITE]
-    } else {
-      !self.bitboards[BLACK]
+  fn pawn_moves(&self, color: Color, chess_moves: &mut Vec<ChessMove>) {
+    // How the index changes when a pawn of the color goes forward, or captures to the west or the east
+    let (forward, west, east): (i8, i8, i8) = match color {
+      Color::White => (8, 7, 9),
+      Color::Black => (-8, -9, -7),
     };
-
-    let mut knights = self.bitboards[color + KNIGHT];
-
-    while knights != 0 {
-      let from_index = pop_lsb(&mut knights);
-
-      let mut moves = KNIGHT_MOVES[from_index] & target_squares;
-
-      while moves != 0 {
-        let to_index = pop_lsb(&mut moves);
-
-        let chess_move = ChessMove::new(from_index, to_index, NO_PROMOTION, NORMAL_MOVE);
-        chess_moves.push(chess_move);
-      }
-    }
-  }
-
-  fn bishop_moves(&self, color: usize, chess_moves: &mut Vec<ChessMove>) {
-    let target_squares = if color == 0 {
-      !self.bitboards[WHITE]
-    } else {
-      !self.bitboards[BLACK]
+    let (double_push_rank, promotion_rank) = match color {
+      Color::White => (Rank::R3, Rank::R7),
+      Color::Black => (Rank::R6, Rank::R2),
     };
-    let blockers = self.bitboards[WHITE] ^ self.bitboards[BLACK];
-
-    let mut bishops = self.bitboards[color + BISHOP];
 
-    while bishops != 0 {
-      let from_index = pop_lsb(&mut bishops);
-      let pext_index = blockers.pext(BISHOP_BLOCKER_MASKS[from_index]) as usize;
+    let empty = !self.occupied();
+    let enemies = self.occupied_by(!color);
+    let pawns = self.pieces(color, PieceType::Pawn);
+    let promoting_pawns = pawns & promotion_rank.bitboard();
+    let other_pawns = pawns & !promoting_pawns;
 
-      let mut moves = BISHOP_MAGICS[from_index][pext_index] & target_squares;
+    if let Some(en_passant) = self.meta.en_passant {
+      // The pawns which attack the en passant square, which are the squares an enemy pawn would attack from there
+      let square = en_passant.bitboard();
+      let en_passant_pawns = (square.forward_west(!color) | square.forward_east(!color)) & pawns;
 
-      while moves != 0 {
-        let to_index = pop_lsb(&mut moves);
-
-        let chess_move = ChessMove::new(from_index, to_index, NO_PROMOTION, NORMAL_MOVE);
-        chess_moves.push(chess_move);
+      for from in en_passant_pawns {
+        chess_moves.push(ChessMove::new(from, en_passant, MoveKind::EnPassant));
       }
     }
-  }
-
-  fn rook_moves(&self, color: usize, chess_moves: &mut Vec<ChessMove>) {
-    let target_squares = if color == 0 {
-      !self.bitboards[WHITE]
-    } else {
-      !self.bitboards[BLACK]
-    };
-    let blockers = self.bitboards[WHITE] ^ self.bitboards[BLACK];
-
-    let mut rooks = self.bitboards[color + ROOK];
 
-    while rooks != 0 {
-      let from_index = pop_lsb(&mut rooks);
-      let pext_index = blockers.pext(ROOK_BLOCKER_MASKS[from_index]) as usize;
+    let pushes = other_pawns.forward(color) & empty;
+    let double_pushes = (pushes & double_push_rank.bitboard()).forward(color) & empty;
 
-      let mut moves = ROOK_MAGICS[from_index][pext_index] & target_squares;
-
-      while moves != 0 {
-        let to_index = pop_lsb(&mut moves);
+    for to in pushes {
+      chess_moves.push(ChessMove::new(to.offset(-forward), to, MoveKind::Normal));
+    }
+    for to in double_pushes {
+      chess_moves.push(ChessMove::new(to.offset(-2 * forward), to, MoveKind::Normal));
+    }
+    for to in other_pawns.forward_west(color) & enemies {
+      chess_moves.push(ChessMove::new(to.offset(-west), to, MoveKind::Normal));
+    }
+    for to in other_pawns.forward_east(color) & enemies {
+      chess_moves.push(ChessMove::new(to.offset(-east), to, MoveKind::Normal));
+    }
 
-        let chess_move = ChessMove::new(from_index, to_index, NO_PROMOTION, NORMAL_MOVE);
-        chess_moves.push(chess_move);
+    let promotions = [
+      (promoting_pawns.forward(color) & empty, forward),
+      (promoting_pawns.forward_west(color) & enemies, west),
+      (promoting_pawns.forward_east(color) & enemies, east),
+    ];
+    for (targets, delta) in promotions {
+      for to in targets {
+        for piece_type in PROMOTIONS {
+          chess_moves.push(ChessMove::new(to.offset(-delta), to, MoveKind::Promotion(piece_type)));
+        }
       }
     }
   }
 
-  fn queen_moves(&self, color: usize, chess_moves: &mut Vec<ChessMove>) {
-    let target_squares = if color == 0 {
-      !self.bitboards[WHITE]
-    } else {
-      !self.bitboards[BLACK]
-    };
-    let blockers = self.bitboards[WHITE] ^ self.bitboards[BLACK];
-
-    let mut queens = self.bitboards[color + QUEEN];
-
-    while queens != 0 {
-      let from_index = pop_lsb(&mut queens);
-      let pext_rook_index = blockers.pext(ROOK_BLOCKER_MASKS[from_index]) as usize;
-      let pext_bishop_index = blockers.pext(BISHOP_BLOCKER_MASKS[from_index]) as usize;
-
-      let mut moves = (ROOK_MAGICS[from_index][pext_rook_index] | BISHOP_MAGICS[from_index][pext_bishop_index]) & target_squares;
-
-      while moves != 0 {
-        let to_index = pop_lsb(&mut moves);
-
-        let chess_move = ChessMove::new(from_index, to_index, NO_PROMOTION, NORMAL_MOVE);
-        chess_moves.push(chess_move);
+  // Knights, bishops, rooks, queens and the king
+  fn piece_moves(&self, color: Color, piece_type: PieceType, chess_moves: &mut Vec<ChessMove>) {
+    let target_squares = !self.occupied_by(color);
+    let blockers = self.occupied();
+
+    for from in self.pieces(color, piece_type) {
+      let moves = match piece_type {
+        PieceType::Knight => knight_attacks(from),
+        PieceType::Bishop => bishop_attacks(from, blockers),
+        PieceType::Rook => rook_attacks(from, blockers),
+        PieceType::Queen => queen_attacks(from, blockers),
+        PieceType::King => king_attacks(from),
+        PieceType::Pawn => unreachable!("Pawns have their own moves"),
+      };
+
+      for to in moves & target_squares {
+        chess_moves.push(ChessMove::new(from, to, MoveKind::Normal));
       }
     }
   }
 
-  // TODO: try using an array with a constant size
-  // Assuming we have only one king
-  fn king_moves(&self, color: usize, chess_moves: &mut Vec<ChessMove>) {
-    let target_squares = if color == 0 {
-      !self.bitboards[WHITE]
-    } else {
-      !self.bitboards[BLACK]
+  pub fn castling_moves(&self, color: Color, chess_moves: &mut Vec<ChessMove>) {
+    // The king can't castle out of, through or into a check
+    let mut castle = |allowed: bool, empty: &[Square], king_path: [Square; 3]| {
+      let empty = empty.iter().fold(Bitboard::EMPTY, |b, square| b | square.bitboard());
+      if allowed && self.squares_are_empty(empty) && king_path.iter().all(|square| self.square_is_safe(*square, color)) {
+        chess_moves.push(ChessMove::new(king_path[0], king_path[2], MoveKind::Castling));
+      }
     };
 
-    let king = self.bitboards[color + KING];
-
-    let from_index = get_lsb(king);
-
-    let mut moves = KING_MOVES[from_index] & target_squares;
-
-    while moves != 0 {
-      let to_index = pop_lsb(&mut moves);
-
-      let chess_move = ChessMove::new(from_index, to_index, NO_PROMOTION, NORMAL_MOVE);
-      chess_moves.push(chess_move);
-    }
-  }
-
-  pub fn castling_moves(&self, color: usize, chess_moves: &mut Vec<ChessMove>) {
-    // TODO: const castling moves with maybe rays, idk
-
-    if color == WHITE {
-      if self.meta.white_king_castle
-        && self.squares_are_empty(F1 | G1)
-        && self.square_is_safe(2, WHITE)
-        && self.square_is_safe(1, WHITE)
-        && self.square_is_safe(3, WHITE)
-      // TODO: repeating
-      {
-        chess_moves.push(ChessMove::new(3, 1, NO_PROMOTION, CASTLING_MOVE));
-      }
-      if self.meta.white_queen_castle
-        && self.squares_are_empty(B1 | C1 | D1)
-        && self.square_is_safe(4, WHITE)
-        && self.square_is_safe(5, WHITE)
-        && self.square_is_safe(3, WHITE)
-      // TODO: repeating
-      {
-        chess_moves.push(ChessMove::new(3, 5, NO_PROMOTION, CASTLING_MOVE));
-      }
-    } else {
-      if self.meta.black_king_castle
-        && self.squares_are_empty(F8 | G8)
-        && self.square_is_safe(58, BLACK)
-        && self.square_is_safe(57, BLACK)
-        && self.square_is_safe(59, BLACK)
-      {
-        chess_moves.push(ChessMove::new(59, 57, NO_PROMOTION, CASTLING_MOVE));
+    match color {
+      Color::White => {
+        castle(
+          self.meta.white_king_castle,
+          &[Square::F1, Square::G1],
+          [Square::E1, Square::F1, Square::G1],
+        );
+        castle(
+          self.meta.white_queen_castle,
+          &[Square::B1, Square::C1, Square::D1],
+          [Square::E1, Square::D1, Square::C1],
+        );
       }
-      if self.meta.black_queen_castle
-        && self.squares_are_empty(B8 | C8 | D8)
-        && self.square_is_safe(60, BLACK)
-        && self.square_is_safe(61, BLACK)
-        && self.square_is_safe(59, BLACK)
-      {
-        chess_moves.push(ChessMove::new(59, 61, NO_PROMOTION, CASTLING_MOVE));
+      Color::Black => {
+        castle(
+          self.meta.black_king_castle,
+          &[Square::F8, Square::G8],
+          [Square::E8, Square::F8, Square::G8],
+        );
+        castle(
+          self.meta.black_queen_castle,
+          &[Square::B8, Square::C8, Square::D8],
+          [Square::E8, Square::D8, Square::C8],
+        );
       }
     }
   }
 
-  fn squares_are_empty(&self, bitboard: u64) -> bool {
-    let all = self.bitboards[WHITE] ^ self.bitboards[BLACK];
-
-    (all & bitboard) == 0
+  fn squares_are_empty(&self, bitboard: Bitboard) -> bool {
+    (self.occupied() & bitboard).is_empty()
   }
 
   pub fn pseudo_legal_moves(&self) -> Vec<ChessMove> {
-    if self.meta.halfmove_clock >= 100 || self.previous_hashes.iter().filter(|h| **h == self.meta.hash).count() >= 2 {
-      return vec![];
-    }
-
-    let color: usize = if self.white_to_move { 0 } else { 1 };
+    let color = self.side_to_move;
 
     // TODO: try changing to const. also try changing capacity
     let mut chess_moves: Vec<ChessMove> = Vec::with_capacity(50);
 
-    self.queen_moves(color, &mut chess_moves);
+    self.piece_moves(color, PieceType::Queen, &mut chess_moves);
     self.castling_moves(color, &mut chess_moves);
-    self.knight_moves(color, &mut chess_moves);
-    self.rook_moves(color, &mut chess_moves);
-    self.bishop_moves(color, &mut chess_moves);
+    self.piece_moves(color, PieceType::Knight, &mut chess_moves);
+    self.piece_moves(color, PieceType::Rook, &mut chess_moves);
+    self.piece_moves(color, PieceType::Bishop, &mut chess_moves);
     self.pawn_moves(color, &mut chess_moves);
-    self.king_moves(color, &mut chess_moves);
+    self.piece_moves(color, PieceType::King, &mut chess_moves);
 
     chess_moves.sort_by_cached_key(|m| -m.evaluate_relative(self));
 
     chess_moves
   }
 
+  // Moving from or to one of these squares loses the castling rights
+  fn update_castling_rights(&mut self, square: Square) {
+    match square {
+      Square::E1 => {
+        self.meta.white_king_castle = false;
+        self.meta.white_queen_castle = false;
+      }
+      Square::E8 => {
+        self.meta.black_king_castle = false;
+        self.meta.black_queen_castle = false;
+      }
+      Square::H1 => self.meta.white_king_castle = false,
+      Square::A1 => self.meta.white_queen_castle = false,
+      Square::H8 => self.meta.black_king_castle = false,
+      Square::A8 => self.meta.black_queen_castle = false,
+      _ => {}
+    }
+  }
+
   pub fn make_move(&mut self, chess_move: &ChessMove) {
     self.previous_hashes.push(self.meta.hash);
 
-    let color: usize = if self.white_to_move { 0 } else { 1 };
-
+    let color = self.side_to_move;
     let from = chess_move.from();
     let to = chess_move.to();
 
@@ -1753,189 +1843,68 @@ impl Board {
     let previous_black_king_castle = self.meta.black_king_castle;
     let previous_black_queen_castle = self.meta.black_queen_castle;
 
-    match chess_move.move_type() {
-      NORMAL_MOVE => {
-        let our_piece_index = self.piece_on_with_color(from, color);
-        let to_bb = 1 << to;
-        let from_bb = 1 << from;
-        let move_bb = from_bb | to_bb;
-        self.bitboards[our_piece_index] ^= move_bb;
-        self.bitboards[color] ^= move_bb;
+    self.meta.hash ^= self.en_passant_key();
+    self.meta.en_passant = None;
 
-        let enemy_piece_index = self.piece_on_with_color(to, color ^ 1);
+    match chess_move.kind() {
+      MoveKind::Normal => {
+        let piece_type = self.piece_type_on(from, color).expect("No piece to move");
+        let captured = self.piece_type_on(to, !color);
 
-        if self.meta.en_passant_bitboard != 0 {
-          let x = get_lsb(self.meta.en_passant_bitboard) % 8;
-          self.meta.hash ^= HASH_TABLE.en_passant[x];
-        }
-        if (our_piece_index == WHITE + PAWN && to - from == 16) || (our_piece_index == BLACK + PAWN && from - to == 16) {
-          self.meta.en_passant_bitboard = 1 << ((to + from) / 2);
-          let x = ((to + from) / 2) % 8;
-          self.meta.hash ^= HASH_TABLE.en_passant[x];
-        } else {
-          self.meta.en_passant_bitboard = 0;
+        if let Some(captured) = captured {
+          self.toggle_piece(Piece::new(!color, captured), to);
         }
+        self.move_piece(Piece::new(color, piece_type), from, to);
 
-        self.meta.hash ^= HASH_TABLE.pieces[from][our_piece_index - 2];
-        self.meta.hash ^= HASH_TABLE.pieces[to][our_piece_index - 2];
-        if enemy_piece_index != EMPTY_SQUARE {
-          self.meta.hash ^= HASH_TABLE.pieces[to][enemy_piece_index - 2];
+        if piece_type == PieceType::Pawn && from.index().abs_diff(to.index()) == 16 {
+          // The square which was jumped over
+          let square = Square::new(from.file(), Rank((from.0 / 8 + to.0 / 8) / 2));
+          self.meta.en_passant = Some(square);
+        }
 
-          self.bitboards[enemy_piece_index] ^= to_bb;
-          self.bitboards[color ^ 1] ^= to_bb;
+        if captured.is_some() || piece_type == PieceType::Pawn {
           self.meta.halfmove_clock = 0;
         } else {
           self.meta.halfmove_clock += 1;
         }
 
-        if from_bb == E1 {
-          self.meta.white_king_castle = false;
-          self.meta.white_queen_castle = false;
-        } else if from_bb == E8 {
-          self.meta.black_king_castle = false;
-          self.meta.black_queen_castle = false;
-        }
-        if to_bb == H1 || from_bb == H1 {
-          self.meta.white_king_castle = false;
-        } else if to_bb == A1 || from_bb == A1 {
-          self.meta.white_queen_castle = false;
-        } else if to_bb == H8 || from_bb == H8 {
-          self.meta.black_king_castle = false;
-        } else if to_bb == A8 || from_bb == A8 {
-          self.meta.black_queen_castle = false;
-        }
+        self.update_castling_rights(from);
+        self.update_castling_rights(to);
       }
-      PROMOTION_MOVE => {
-        // TODO: optimize - we know its a pawn
-        let from_bb = 1 << from;
-        let to_bb = 1 << to;
-        let move_bb = from_bb | to_bb;
-        self.bitboards[PAWN + color] ^= from_bb;
-        self.bitboards[color] ^= move_bb;
-
-        let enemy_piece_index = self.piece_on_with_color(to, color ^ 1);
-
-        let promotion_piece_index = match chess_move.promotion() {
-          KNIGHT_PROMOTION => KNIGHT,
-          BISHOP_PROMOTION => BISHOP,
-          ROOK_PROMOTION => ROOK,
-          QUEEN_PROMOTION => QUEEN,
-          _ => panic!(),
-        };
-
-        self.meta.hash ^= HASH_TABLE.pieces[from][PAWN + color - 2];
-
-        if enemy_piece_index != EMPTY_SQUARE {
-          self.meta.hash ^= HASH_TABLE.pieces[to][enemy_piece_index - 2];
-          self.bitboards[enemy_piece_index] ^= to_bb;
-          self.bitboards[color ^ 1] ^= to_bb;
+      MoveKind::Promotion(piece_type) => {
+        if let Some(captured) = self.piece_type_on(to, !color) {
+          self.toggle_piece(Piece::new(!color, captured), to);
         }
+        self.toggle_piece(Piece::new(color, PieceType::Pawn), from);
+        self.toggle_piece(Piece::new(color, piece_type), to);
 
-        self.bitboards[promotion_piece_index + color] ^= to_bb;
-        self.meta.hash ^= HASH_TABLE.pieces[to][promotion_piece_index + color - 2];
-
-        if to_bb == H1 {
-          self.meta.white_king_castle = false;
-        } else if to_bb == A1 {
-          self.meta.white_queen_castle = false;
-        } else if to_bb == H8 {
-          self.meta.black_king_castle = false;
-        } else if to_bb == A8 {
-          self.meta.black_queen_castle = false;
-        }
-        self.meta.en_passant_bitboard = 0;
+        self.update_castling_rights(to);
         self.meta.halfmove_clock = 0;
       }
-      EN_PASSANT_MOVE => {
-        // TODO: optimize - we know its a pawn
-        let from_bb = 1 << from;
-        let to_bb = 1 << to;
-        let move_bb = from_bb | to_bb;
-
-        let our_piece_index = self.piece_on_with_color(from, color);
-        self.bitboards[our_piece_index] ^= move_bb;
-        self.bitboards[color] ^= move_bb;
-
-        self.meta.hash ^= HASH_TABLE.pieces[from][our_piece_index - 2];
-        self.meta.hash ^= HASH_TABLE.pieces[to][our_piece_index - 2];
-
-        if color == WHITE {
-          let to_remove_bb = 1 << (to - 8);
-          self.bitboards[BLACK + PAWN] ^= to_remove_bb;
-          self.bitboards[BLACK] ^= to_remove_bb;
-          self.meta.hash ^= HASH_TABLE.pieces[to - 8][BLACK + PAWN - 2];
-        } else {
-          let to_remove_bb = 1 << (to + 8);
-          self.bitboards[WHITE + PAWN] ^= to_remove_bb;
-          self.bitboards[WHITE] ^= to_remove_bb;
-          self.meta.hash ^= HASH_TABLE.pieces[to + 8][WHITE + PAWN - 2];
-        }
+      MoveKind::EnPassant => {
+        // The captured pawn is next to the moving one
+        let captured_square = Square::new(to.file(), from.rank());
+
+        self.toggle_piece(Piece::new(!color, PieceType::Pawn), captured_square);
+        self.move_piece(Piece::new(color, PieceType::Pawn), from, to);
 
-        self.meta.en_passant_bitboard = 0;
         self.meta.halfmove_clock = 0;
       }
-      CASTLING_MOVE => {
-        // TODO: | -> ^
-        if to == 1 {
-          // White king castle
-          self.bitboards[WHITE + KING] = G1;
-          self.bitboards[WHITE + ROOK] ^= F1 | H1;
-          self.bitboards[WHITE] ^= E1 | F1 | G1 | H1;
-
-          self.meta.white_king_castle = false;
-          self.meta.white_queen_castle = false;
-
-          self.meta.hash ^= HASH_TABLE.pieces[3][WHITE + KING - 2];
-          self.meta.hash ^= HASH_TABLE.pieces[1][WHITE + KING - 2];
-          self.meta.hash ^= HASH_TABLE.pieces[0][WHITE + ROOK - 2];
-          self.meta.hash ^= HASH_TABLE.pieces[2][WHITE + ROOK - 2];
-        } else if to == 5 {
-          // White queen castle
-          self.bitboards[WHITE + KING] = C1;
-          self.bitboards[WHITE + ROOK] ^= A1 | D1;
-          self.bitboards[WHITE] ^= A1 | C1 | D1 | E1;
-
-          self.meta.white_king_castle = false;
-          self.meta.white_queen_castle = false;
-
-          self.meta.hash ^= HASH_TABLE.pieces[3][WHITE + KING - 2];
-          self.meta.hash ^= HASH_TABLE.pieces[5][WHITE + KING - 2];
-          self.meta.hash ^= HASH_TABLE.pieces[7][WHITE + ROOK - 2];
-          self.meta.hash ^= HASH_TABLE.pieces[4][WHITE + ROOK - 2];
-        } else if to == 57 {
-          // Black king castle
-          self.bitboards[BLACK + KING] = G8;
-          self.bitboards[BLACK + ROOK] ^= F8 | H8;
-          self.bitboards[BLACK] ^= E8 | F8 | G8 | H8;
-
-          self.meta.black_king_castle = false;
-          self.meta.black_queen_castle = false;
-
-          self.meta.hash ^= HASH_TABLE.pieces[59][BLACK + KING - 2];
-          self.meta.hash ^= HASH_TABLE.pieces[57][BLACK + KING - 2];
-          self.meta.hash ^= HASH_TABLE.pieces[56][BLACK + ROOK - 2];
-          self.meta.hash ^= HASH_TABLE.pieces[58][BLACK + ROOK - 2];
-        } else if to == 61 {
-          // Black queen castle
-          self.bitboards[BLACK + KING] = C8;
-          self.bitboards[BLACK + ROOK] ^= A8 | D8;
-          self.bitboards[BLACK] ^= A8 | C8 | D8 | E8;
-
-          self.meta.black_king_castle = false;
-          self.meta.black_queen_castle = false;
-
-          self.meta.hash ^= HASH_TABLE.pieces[59][BLACK + KING - 2];
-          self.meta.hash ^= HASH_TABLE.pieces[61][BLACK + KING - 2];
-          self.meta.hash ^= HASH_TABLE.pieces[63][BLACK + ROOK - 2];
-          self.meta.hash ^= HASH_TABLE.pieces[60][BLACK + ROOK - 2];
-        } else {
-          panic!();
-        }
+      MoveKind::Castling => {
+        let (rook_from, rook_to) = match to {
+          Square::G1 => (Square::H1, Square::F1),
+          Square::C1 => (Square::A1, Square::D1),
+          Square::G8 => (Square::H8, Square::F8),
+          Square::C8 => (Square::A8, Square::D8),
+          _ => panic!("Invalid castling move {chess_move}"),
+        };
+
+        self.move_piece(Piece::new(color, PieceType::King), from, to);
+        self.move_piece(Piece::new(color, PieceType::Rook), rook_from, rook_to);
 
-        self.meta.en_passant_bitboard = 0;
+        self.update_castling_rights(from);
         self.meta.halfmove_clock += 1;
       }
-      _ => panic!(),
     }
 
     // If any of the castling rights changed, update the hash
@@ -1952,92 +1921,107 @@ impl Board {
       self.meta.hash ^= HASH_TABLE.black_queen_castle;
     }
 
-    if color == BLACK {
+    if color == Color::Black {
       self.fullmove_counter += 1;
     }
     self.meta.hash ^= HASH_TABLE.black_to_move;
 
-    self.white_to_move = !self.white_to_move;
+    self.side_to_move = !color;
+    self.meta.hash ^= self.en_passant_key();
+    self.debug_check_hashes();
   }
 
   // Ignores en passant
-  pub fn square_is_safe(&self, index: usize, defending_color: usize) -> bool {
-    let attacking_color = defending_color ^ 1;
-    let blockers = self.bitboards[WHITE] ^ self.bitboards[BLACK];
+  pub fn square_is_safe(&self, square: Square, defending_color: Color) -> bool {
+    let attacking_color = !defending_color;
+    let blockers = self.occupied();
+    let attackers = |piece_type| self.pieces(attacking_color, piece_type);
+
+    let diagonal = attackers(PieceType::Bishop) | attackers(PieceType::Queen);
+    let straight = attackers(PieceType::Rook) | attackers(PieceType::Queen);
+
+    // A pawn attacks the square if a pawn of the other color on the square would attack it
+    let square_bb = square.bitboard();
+    let pawn_attacks = square_bb.forward_west(defending_color) | square_bb.forward_east(defending_color);
+
+    (bishop_attacks(square, blockers) & diagonal).is_empty()
+      && (rook_attacks(square, blockers) & straight).is_empty()
+      && (knight_attacks(square) & attackers(PieceType::Knight)).is_empty()
+      && (king_attacks(square) & attackers(PieceType::King)).is_empty()
+      && (pawn_attacks & attackers(PieceType::Pawn)).is_empty()
+  }
 
-    if BISHOP_MAGICS[index][blockers.pext(BISHOP_BLOCKER_MASKS[index]) as usize]
-      & (self.bitboards[BISHOP + attacking_color] | self.bitboards[QUEEN + attacking_color])
-      != 0
-    {
-      return false;
-    }
+  pub fn in_check(&self, color: Color) -> bool {
+    let king = self.pieces(color, PieceType::King).lsb();
+
+    // TODO: fn without an index but just bitboard
+    !self.square_is_safe(king, color)
+  }
 
-    if ROOK_MAGICS[index][blockers.pext(ROOK_BLOCKER_MASKS[index]) as usize]
-      & (self.bitboards[ROOK + attacking_color] | self.bitboards[QUEEN + attacking_color])
-      != 0
+  // Returns whether the current position has occurred at least `count` times (including the current one).
+  // Only the last halfmove_clock plies are looked at
+  pub fn is_repetition(&self, count: usize) -> bool {
+    let mut occurrences = 1;
+
+    for (i, hash) in self
+      .previous_hashes
+      .iter()
+      .rev()
+      .take(self.meta.halfmove_clock as usize)
+      .enumerate()
     {
-      return false;
+      if i % 2 == 1 && *hash == self.meta.hash {
+        occurrences += 1;
+      }
     }
 
-    if KNIGHT_MOVES[index] & self.bitboards[KNIGHT + attacking_color] != 0 {
-      return false;
-    }
+    occurrences >= count
+  }
 
-    if KING_MOVES[index] & self.bitboards[KING + attacking_color] != 0 {
-      return false;
-    }
+  pub fn is_fifty_move_draw(&self) -> bool {
+    self.meta.halfmove_clock >= 100
+  }
 
-    let square_bb: u64 = 1 << index;
+  // Only kings and a single minor piece, or only kings and bishops on the same colored squares
+  pub fn is_insufficient_material(&self) -> bool {
+    const LIGHT_SQUARES: Bitboard = Bitboard(0x55aa55aa55aa55aa);
 
-    if attacking_color == BLACK
-      && (south_west(self.bitboards[PAWN + BLACK]) | south_east(self.bitboards[PAWN + BLACK])) & square_bb != 0
-    {
-      return false;
-    }
+    let both = |piece_type| self.pieces(Color::White, piece_type) | self.pieces(Color::Black, piece_type);
 
-    if attacking_color == WHITE
-      && (north_west(self.bitboards[PAWN + WHITE]) | north_east(self.bitboards[PAWN + WHITE])) & square_bb != 0
-    {
+    if !(both(PieceType::Pawn) | both(PieceType::Rook) | both(PieceType::Queen)).is_empty() {
       return false;
     }
 
-    true
-  }
-
-  pub fn piece_on_with_color(&self, index: usize, color: usize) -> usize {
-    let bitboard: u64 = 1 << index;
-
-    for i in 0..6 {
-      let p = 2 + i * 2 + color;
-      if self.bitboards[p] & bitboard != 0 {
-        return p;
-      }
-    }
+    let knights = both(PieceType::Knight);
+    let bishops = both(PieceType::Bishop);
 
-    EMPTY_SQUARE
+    (knights | bishops).count() <= 1
+      || (knights.is_empty() && ((bishops & LIGHT_SQUARES).is_empty() || (bishops & !LIGHT_SQUARES).is_empty()))
   }
 
-  fn piece_on(&self, index: usize) -> usize {
-    let bitboard: u64 = 1 << index;
-
-    for i in 2..14 {
-      if self.bitboards[i] & bitboard != 0 {
-        return i;
-      }
+  // Checkmate and stalemate take precedence over the draw rules
+  pub fn game_status(&self) -> GameStatus {
+    if self.legal_moves().is_empty() {
+      return if self.in_check(self.side_to_move) {
+        GameStatus::Checkmate
+      } else {
+        GameStatus::Stalemate
+      };
+    }
+
+    if self.is_fifty_move_draw() {
+      GameStatus::Draw(DrawReason::FiftyMoveRule)
+    } else if self.is_repetition(3) {
+      GameStatus::Draw(DrawReason::Repetition)
+    } else if self.is_insufficient_material() {
+      GameStatus::Draw(DrawReason::InsufficientMaterial)
+    } else {
+      GameStatus::Ongoing
     }
-
-    EMPTY_SQUARE
-  }
-
-  pub fn in_check(&self, color: usize) -> bool {
-    let king_index = get_lsb(self.bitboards[color + KING]);
-
-    // TODO: fn without an index but just bitboard
-    !self.square_is_safe(king_index, color)
   }
 
   pub fn legal_moves(&self) -> Vec<ChessMove> {
-    let color: usize = if self.white_to_move { 0 } else { 1 };
+    let color = self.side_to_move;
 
     self
       .pseudo_legal_moves()
@@ -2054,55 +2038,34 @@ impl Board {
   pub fn to_fen(&self) -> String {
     let mut piece_placement: Vec<String> = vec![];
 
-    for y in 0..8 {
-      let mut rank = "".to_string();
+    for rank in (0..8).rev() {
+      let mut rank_string = "".to_string();
       let mut empty_in_a_row = 0;
 
-      for x in 0..8 {
-        let i = 63 - (y * 8 + x);
-        let mut piece = self.piece_on(i);
-
-        if piece == EMPTY_SQUARE {
+      for file in 0..8 {
+        let Some(piece) = self.piece_on(Square::new(File(file), Rank(rank))) else {
           empty_in_a_row += 1;
           continue;
-        }
+        };
 
         if empty_in_a_row != 0 {
-          rank += &empty_in_a_row.to_string();
+          rank_string += &empty_in_a_row.to_string();
           empty_in_a_row = 0;
         }
 
-        let color = piece % 2;
-        piece -= color;
-
-        let mut piece_char = match piece {
-          PAWN => "P",
-          KNIGHT => "N",
-          BISHOP => "B",
-          ROOK => "R",
-          QUEEN => "Q",
-          KING => "K",
-          _ => panic!("This shouldn't panic"),
-        }
-        .to_string();
-
-        if color == BLACK {
-          piece_char = piece_char.to_lowercase();
-        }
-
-        rank += &piece_char;
+        rank_string.push(piece.to_char());
       }
 
       if empty_in_a_row != 0 {
-        rank += &empty_in_a_row.to_string();
+        rank_string += &empty_in_a_row.to_string();
       }
 
-      piece_placement.push(rank);
+      piece_placement.push(rank_string);
     }
 
     let piece_placement = piece_placement.join("/");
 
-    let side_to_move = if self.white_to_move { "w" } else { "b" };
+    let side_to_move = if self.side_to_move == Color::White { "w" } else { "b" };
 
     let mut castling_ability = "".to_string();
 
@@ -2122,10 +2085,7 @@ impl Board {
       castling_ability += "-";
     }
 
-    let mut en_passant_target_square = "-".to_string();
-    if self.meta.en_passant_bitboard != 0 {
-      en_passant_target_square = bitboard_to_square(self.meta.en_passant_bitboard).to_string();
-    }
+    let en_passant_target_square = self.meta.en_passant.map_or("-".to_string(), |square| square.to_string());
 
     let halfmove_clock = self.meta.halfmove_clock.to_string();
 
@@ -2135,34 +2095,19 @@ impl Board {
   }
 
   pub fn evaluate(&self) -> i32 {
-    // Assuming we aren't checked
-    if self.meta.halfmove_clock >= 100 || self.previous_hashes.iter().filter(|h| **h == self.meta.hash).count() >= 2 {
-      return 0;
-    }
-
     let mut eval = 0;
 
-    for index in 0..64 {
-      let bb = 1 << index;
-      let i = 63 - index;
-
-      eval += (self.bitboards[WHITE + PAWN] & bb).count_ones() as i32 * (PAWN_VALUE + WHITE_PAWN_EVAL[i]);
-      eval -= (self.bitboards[BLACK + PAWN] & bb).count_ones() as i32 * (PAWN_VALUE + BLACK_PAWN_EVAL[i]);
-
-      eval += (self.bitboards[WHITE + KNIGHT] & bb).count_ones() as i32 * (KNIGHT_VALUE + WHITE_KNIGHT_EVAL[i]);
-      eval -= (self.bitboards[BLACK + KNIGHT] & bb).count_ones() as i32 * (KNIGHT_VALUE + BLACK_KNIGHT_EVAL[i]);
-
-      eval += (self.bitboards[WHITE + BISHOP] & bb).count_ones() as i32 * (BISHOP_VALUE + WHITE_BISHOP_EVAL[i]);
-      eval -= (self.bitboards[BLACK + BISHOP] & bb).count_ones() as i32 * (BISHOP_VALUE + BLACK_BISHOP_EVAL[i]);
-
-      eval += (self.bitboards[WHITE + ROOK] & bb).count_ones() as i32 * (ROOK_VALUE + WHITE_ROOK_EVAL[i]);
-      eval -= (self.bitboards[BLACK + ROOK] & bb).count_ones() as i32 * (ROOK_VALUE + BLACK_ROOK_EVAL[i]);
+    for color in Color::ALL {
+      let sign = if color == Color::White { 1 } else { -1 };
 
-      eval += (self.bitboards[WHITE + QUEEN] & bb).count_ones() as i32 * (QUEEN_VALUE + WHITE_QUEEN_EVAL[i]);
-      eval -= (self.bitboards[BLACK + QUEEN] & bb).count_ones() as i32 * (QUEEN_VALUE + BLACK_QUEEN_EVAL[i]);
+      for piece_type in PieceType::ALL {
+        let table = piece_square_table(color, piece_type);
 
-      eval += (self.bitboards[WHITE + KING] & bb).count_ones() as i32 * WHITE_KING_EVAL[i];
-      eval -= (self.bitboards[BLACK + KING] & bb).count_ones() as i32 * BLACK_KING_EVAL[i];
+        for square in self.pieces(color, piece_type) {
+          // The tables start from a8
+          eval += sign * (piece_type.value() + table[square.index() ^ 56]);
+        }
+      }
     }
 
     if self.meta.white_king_castle {
@@ -2178,11 +2123,11 @@ impl Board {
       eval -= POSSIBLE_CASTLING_VALUE;
     }
 
-    eval
+    endgame::evaluate(self.meta.material_hash, &self.pieces, self.side_to_move, eval)
   }
 
   pub fn evaluate_relative(&self) -> i32 {
-    if self.white_to_move {
+    if self.side_to_move == Color::White {
       self.evaluate()
     } else {
       -self.evaluate()
@@ -2198,7 +2143,8 @@ impl Default for Board {
 
 #[cfg(test)]
 mod tests {
-  use crate::bitboard::{bitboard_to_square, square_to_bitboard, Board, ChessMove, NORMAL_MOVE, NO_PROMOTION};
+  use crate::bitboard::{Bitboard, Board, ChessMove, Color, File, MoveKind, Piece, PieceType, Rank, Square};
+  use crate::board::{DrawReason, GameStatus};
 
   #[test]
   fn fen_test() {
@@ -2233,15 +2179,53 @@ mod tests {
       "rnbqkbnr/ppppp1pp/8/8/4PpP1/7N/PPPP1P1P/RNBQKB1R b KQkq e3 0 1",
       Board::from_fen("rnbqkbnr/ppppp1pp/8/8/4PpP1/7N/PPPP1P1P/RNBQKB1R b KQkq e3 0 1").to_fen()
     );
-    assert_eq!("a1", bitboard_to_square(square_to_bitboard("a1")));
-    assert_eq!("a3", bitboard_to_square(square_to_bitboard("a3")));
-    assert_eq!("a8", bitboard_to_square(square_to_bitboard("a8")));
-    assert_eq!("c1", bitboard_to_square(square_to_bitboard("c1")));
-    assert_eq!("c3", bitboard_to_square(square_to_bitboard("c3")));
-    assert_eq!("c8", bitboard_to_square(square_to_bitboard("c8")));
-    assert_eq!("h1", bitboard_to_square(square_to_bitboard("h1")));
-    assert_eq!("h3", bitboard_to_square(square_to_bitboard("h3")));
-    assert_eq!("h8", bitboard_to_square(square_to_bitboard("h8")));
+    for square in ["a1", "a3", "a8", "c1", "c3", "c8", "h1", "h3", "h8"] {
+      assert_eq!(square, square.parse::<Square>().unwrap().to_string());
+    }
+  }
+
+  #[test]
+  fn types_test() {
+    assert_eq!(Square::new(File::E, Rank::R4), Square::E4);
+    assert_eq!("e4".parse::<Square>(), Ok(Square::E4));
+    assert!("e9".parse::<Square>().is_err());
+    assert_eq!((Square::E4.file(), Square::E4.rank()), (File::E, Rank::R4));
+    assert_eq!(Square::from_index(63), Some(Square::H8));
+    assert_eq!(Square::from_index(64), None);
+    assert_eq!(Square::all().count(), 64);
+
+    assert_eq!(Piece::from_char('n'), Some(Piece::new(Color::Black, PieceType::Knight)));
+    assert_eq!(Piece::new(Color::White, PieceType::Queen).to_string(), "Q");
+    assert_eq!(Piece::from_char('x'), None);
+    assert_eq!(!Color::White, Color::Black);
+
+    let b = Square::A1.bitboard() | Square::E4.bitboard() | Square::H8.bitboard();
+    assert_eq!(
+      b.into_iter().collect::<Vec<Square>>(),
+      vec![Square::A1, Square::E4, Square::H8]
+    );
+    assert_eq!(b.count(), 3);
+    assert!(b.contains(Square::E4) && !b.contains(Square::E5));
+    assert_eq!((b & File::E.bitboard()).lsb(), Square::E4);
+    assert_eq!(b ^ b, Bitboard::EMPTY);
+    assert_eq!((!b).count(), 61);
+
+    // Shifts don't wrap around the edges
+    assert_eq!(Square::H4.bitboard().east(), Bitboard::EMPTY);
+    assert_eq!(Square::A4.bitboard().west(), Bitboard::EMPTY);
+    assert_eq!(Square::A4.bitboard().north_east(), Square::B5.bitboard());
+    assert_eq!(Square::E2.bitboard().forward(Color::Black), Square::E1.bitboard());
+    assert_eq!(Rank::R8.bitboard().north(), Bitboard::EMPTY);
+
+    assert_eq!(
+      (Rank::R1.bitboard() | File::A.bitboard()).to_string(),
+      "@ . . . . . . .\n@ . . . . . . .\n@ . . . . . . .\n@ . . . . . . .\n@ . . . . . . .\n@ . . . . . . .\n@ . . . . . . .\n@ @ @ @ @ @ @ @"
+    );
+
+    let promotion = ChessMove::new(Square::B7, Square::A8, MoveKind::Promotion(PieceType::Rook));
+    assert_eq!((promotion.from(), promotion.to()), (Square::B7, Square::A8));
+    assert_eq!(promotion.kind(), MoveKind::Promotion(PieceType::Rook));
+    assert_eq!(promotion.to_string(), "b7a8r");
   }
 
   #[test]
@@ -2303,82 +2287,6 @@ mod tests {
     assert_eq!(expected_moves, generated_moves);
   }
 
-  #[test]
-  fn perft_test() {
-    // https://www.chessprogramming.org/Perft_Results
-    fn test_fen<const N: usize>(fen: &str, depth_nodes: [u64; N]) {
-      let mut board = Board::from_fen(fen);
-
-      fn perft(depth: usize, board: &mut Board) -> u64 {
-        // https://www.chessprogramming.org/Perft
-
-        let mut nodes = 0;
-
-        if depth == 0 {
-          return 1;
-        }
-
-        let color = if board.white_to_move { 0 } else { 1 };
-
-        for chess_move in board.pseudo_legal_moves() {
-          let mut new_board = board.clone();
-          new_board.make_move(&chess_move);
-          if !new_board.in_check(color) {
-            nodes += perft(depth - 1, &mut new_board);
-          }
-        }
-
-        nodes
-      }
-
-      for (i, nodes) in depth_nodes.iter().enumerate() {
-        assert_eq!(perft(i, &mut board), *nodes);
-      }
-    }
-
-    // Position 1
-    test_fen::<7>(
-      "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
-      [1, 20, 400, 8_902, 197_281, 4_865_609, 119_060_324],
-    );
-
-    // Position 2
-    test_fen::<6>(
-      "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
-      [1, 48, 2_039, 97_862, 4_085_603, 193_690_690],
-    );
-
-    // Position 3
-    test_fen::<7>(
-      "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
-      [1, 14, 191, 2_812, 43_238, 674_624, 11_030_083],
-    );
-
-    // Position 4
-    test_fen::<6>(
-      "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
-      [1, 6, 264, 9_467, 422_333, 15_833_292],
-    );
-
-    // Position 4 mirrored
-    test_fen::<6>(
-      "r2q1rk1/pP1p2pp/Q4n2/bbp1p3/Np6/1B3NBn/pPPP1PPP/R3K2R b KQ - 0 1",
-      [1, 6, 264, 9_467, 422_333, 15_833_292],
-    );
-
-    // Position 5
-    test_fen::<6>(
-      "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8",
-      [1, 44, 1_486, 62_379, 2_103_487, 89_941_194],
-    );
-
-    // Position 6
-    test_fen::<6>(
-      "r4rk1/1pp1qppp/p1np1n2/2b1p1B1/2B1P1b1/P1NP1N2/1PP1QPPP/R4RK1 w - - 0 10",
-      [1, 46, 2_079, 89_890, 3_894_594, 164_075_551],
-    );
-  }
-
   #[test]
   fn hash_test() {
     fn test_hashing(fen: &str) {
@@ -2403,7 +2311,7 @@ mod tests {
 
     let mut board = Board::from_fen("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1");
 
-    board.make_move(&ChessMove::new(1, 18, NO_PROMOTION, NORMAL_MOVE));
+    board.make_move(&ChessMove::new(Square::G1, Square::F3, MoveKind::Normal));
     assert_eq!(
       board.meta.hash,
       Board::from_fen("rnbqkbnr/pppppppp/8/8/8/5N2/PPPPPPPP/RNBQKB1R b KQkq - 0 1")
@@ -2411,7 +2319,7 @@ mod tests {
         .hash
     );
 
-    board.make_move(&ChessMove::new(52, 44, NO_PROMOTION, NORMAL_MOVE));
+    board.make_move(&ChessMove::new(Square::D7, Square::D6, MoveKind::Normal));
     assert_eq!(
       board.meta.hash,
       Board::from_fen("rnbqkbnr/ppp1pppp/3p4/8/8/5N2/PPPPPPPP/RNBQKB1R w KQkq - 0 1")
@@ -2419,7 +2327,7 @@ mod tests {
         .hash
     );
 
-    board.make_move(&ChessMove::new(0, 1, NO_PROMOTION, NORMAL_MOVE));
+    board.make_move(&ChessMove::new(Square::H1, Square::G1, MoveKind::Normal));
     assert_eq!(
       board.meta.hash,
       Board::from_fen("rnbqkbnr/ppp1pppp/3p4/8/8/5N2/PPPPPPPP/RNBQKBR1 b Qkq - 0 1")
@@ -2427,7 +2335,7 @@ mod tests {
         .hash
     );
 
-    board.make_move(&ChessMove::new(54, 38, NO_PROMOTION, NORMAL_MOVE));
+    board.make_move(&ChessMove::new(Square::B7, Square::B5, MoveKind::Normal));
     assert_eq!(
       board.meta.hash,
       Board::from_fen("rnbqkbnr/p1p1pppp/3p4/1p6/8/5N2/PPPPPPPP/RNBQKBR1 w Qkq b6 0 1")
@@ -2435,7 +2343,7 @@ mod tests {
         .hash
     );
 
-    board.make_move(&ChessMove::new(13, 29, NO_PROMOTION, NORMAL_MOVE));
+    board.make_move(&ChessMove::new(Square::C2, Square::C4, MoveKind::Normal));
     assert_eq!(
       board.meta.hash,
       Board::from_fen("rnbqkbnr/p1p1pppp/3p4/1p6/2P5/5N2/PP1PPPPP/RNBQKBR1 b Qkq c3 0 1")
@@ -2443,7 +2351,7 @@ mod tests {
         .hash
     );
 
-    board.make_move(&ChessMove::new(59, 52, NO_PROMOTION, NORMAL_MOVE));
+    board.make_move(&ChessMove::new(Square::E8, Square::D7, MoveKind::Normal));
     assert_eq!(
       board.meta.hash,
       Board::from_fen("rnbq1bnr/p1pkpppp/3p4/1p6/2P5/5N2/PP1PPPPP/RNBQKBR1 w Q - 0 1")
@@ -2500,4 +2408,49 @@ mod tests {
     //     .hash
     // );
   }
+
+  #[test]
+  fn game_status_test() {
+    let mut board = Board::default();
+
+    for (i, chess_move) in ["g1f3", "g8f6", "f3g1", "f6g8", "g1f3", "g8f6", "f3g1", "f6g8"]
+      .iter()
+      .enumerate()
+    {
+      assert_eq!(board.is_repetition(2), i >= 4);
+      assert_eq!(board.game_status(), GameStatus::Ongoing);
+
+      let chess_move = board.legal_moves().into_iter().find(|m| m.to_fen() == *chess_move).unwrap();
+      board.make_move(&chess_move);
+    }
+
+    assert!(board.is_repetition(3));
+    assert_eq!(board.game_status(), GameStatus::Draw(DrawReason::Repetition));
+
+    assert_eq!(
+      Board::from_fen("8/8/4k3/8/8/4K3/4P3/8 w - - 100 80").game_status(),
+      GameStatus::Draw(DrawReason::FiftyMoveRule)
+    );
+    assert_eq!(
+      Board::from_fen("8/8/4k3/8/8/4K3/8/8 w - - 0 1").game_status(),
+      GameStatus::Draw(DrawReason::InsufficientMaterial)
+    );
+    assert_eq!(
+      Board::from_fen("8/3b4/4k3/8/8/4K3/4B3/8 w - - 0 1").game_status(),
+      GameStatus::Draw(DrawReason::InsufficientMaterial)
+    );
+    assert_eq!(
+      Board::from_fen("8/4b3/4k3/8/8/4K3/4B3/8 w - - 0 1").game_status(),
+      GameStatus::Ongoing
+    );
+
+    assert_eq!(
+      Board::from_fen("rnb1kbnr/pppp1ppp/8/4p3/6Pq/5P2/PPPPP2P/RNBQKBNR w KQkq - 1 3").game_status(),
+      GameStatus::Checkmate
+    );
+    assert_eq!(
+      Board::from_fen("7k/5Q2/6K1/8/8/8/8/8 b - - 0 1").game_status(),
+      GameStatus::Stalemate
+    );
+  }
 }
@@ -1,5 +1,8 @@
+use crate::bitboard::{self, Color, Piece, PieceType, Square};
+use crate::endgame;
 use lazy_static::lazy_static;
 use rand::prelude::*;
+use std::fmt;
 
 const PAWN: i8 = 1;
 const KNIGHT: i8 = 2;
@@ -209,7 +212,7 @@ pub struct HashTable {
   black_king_castle: u64,
   black_queen_castle: u64,
 
-  // Eight numbers to indicate the file of a valid en passant square
+  // Eight numbers to indicate the file of an en passant square where a capture is possible
   en_passant: [u64; 8],
 }
 
@@ -259,6 +262,23 @@ lazy_static! {
   pub static ref HASH_TABLE: HashTable = HashTable::new();
 }
 
+// Gives the index of the piece in the HASH_TABLE
+fn get_piece_index(piece: i8) -> usize {
+  let mut piece_index = match piece.abs() {
+    PAWN => 0,
+    KNIGHT => 1,
+    BISHOP => 2,
+    ROOK => 3,
+    QUEEN => 4,
+    KING => 5,
+    _ => panic!(),
+  };
+  if piece < 0 {
+    piece_index += 6;
+  }
+  piece_index
+}
+
 // Gives an index of the specified square (of the 10x12 board)
 // For example: square_to_index("a8") == 21
 fn square_to_index(square: &str) -> i8 {
@@ -300,10 +320,25 @@ fn get_color(square: i8) -> Option<Color> {
   }
 }
 
-#[derive(Clone, Debug, PartialEq)]
-pub enum Color {
-  White,
-  Black,
+// Users of the board get the same squares and pieces as the bitboard engine, the board itself uses the 10x12 indices
+fn board_index(square: Square) -> i8 {
+  (9 - square.rank().index() as i8) * 10 + square.file().index() as i8 + 1
+}
+
+fn square_at(index: i8) -> Square {
+  Square::from_index(((9 - index / 10) * 8 + index % 10 - 1) as usize).expect("Board indices are on the board")
+}
+
+fn piece_type(piece: i8) -> Option<PieceType> {
+  match piece.abs() {
+    PAWN => Some(PieceType::Pawn),
+    KNIGHT => Some(PieceType::Knight),
+    BISHOP => Some(PieceType::Bishop),
+    ROOK => Some(PieceType::Rook),
+    QUEEN => Some(PieceType::Queen),
+    KING => Some(PieceType::King),
+    _ => None,
+  }
 }
 
 #[derive(Clone, Debug, PartialEq)]
@@ -314,7 +349,22 @@ pub enum CastlingSide {
   BlackQueen,
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq)]
+pub enum DrawReason {
+  FiftyMoveRule,
+  Repetition,
+  InsufficientMaterial,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum GameStatus {
+  Ongoing,
+  Checkmate,
+  Stalemate,
+  Draw(DrawReason),
+}
+
+#[derive(Clone, Debug, PartialEq)]
 pub enum Move {
   Normal {
     from: i8,
@@ -483,6 +533,44 @@ impl Move {
     }
   }
 
+  // Castling is the king's move
+  pub fn from(&self) -> Square {
+    match *self {
+      Move::Normal { from, .. }
+      | Move::Capture { from, .. }
+      | Move::PawnPush { from, .. }
+      | Move::DoublePawnPush { from, .. }
+      | Move::EnPassant { from, .. }
+      | Move::Promotion { from, .. }
+      | Move::PromotionWithCapture { from, .. } => square_at(from),
+      Move::Castling(CastlingSide::WhiteKing | CastlingSide::WhiteQueen) => Square::E1,
+      Move::Castling(CastlingSide::BlackKing | CastlingSide::BlackQueen) => Square::E8,
+    }
+  }
+
+  pub fn to(&self) -> Square {
+    match *self {
+      Move::Normal { to, .. }
+      | Move::Capture { to, .. }
+      | Move::PawnPush { to, .. }
+      | Move::DoublePawnPush { to, .. }
+      | Move::EnPassant { to, .. }
+      | Move::Promotion { to, .. }
+      | Move::PromotionWithCapture { to, .. } => square_at(to),
+      Move::Castling(CastlingSide::WhiteKing) => Square::G1,
+      Move::Castling(CastlingSide::WhiteQueen) => Square::C1,
+      Move::Castling(CastlingSide::BlackKing) => Square::G8,
+      Move::Castling(CastlingSide::BlackQueen) => Square::C8,
+    }
+  }
+
+  pub fn promoted_piece(&self) -> Option<PieceType> {
+    match *self {
+      Move::Promotion { selected_piece, .. } | Move::PromotionWithCapture { selected_piece, .. } => piece_type(selected_piece),
+      _ => None,
+    }
+  }
+
   // Returns whether a move captures a piece
   pub fn is_capture(&self) -> bool {
     match *self {
@@ -494,8 +582,15 @@ impl Move {
   }
 }
 
+// Like "e2e4" or "e7e8q"
+impl fmt::Display for Move {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    f.write_str(&self.to_fen())
+  }
+}
+
 // Stores additional board information
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq)]
 pub struct BoardMeta {
   // Castling rights. It is true if it is legal to do it.
   white_king_castle: bool,
@@ -504,7 +599,7 @@ pub struct BoardMeta {
   black_queen_castle: bool,
 
   // The en passant target square is specified after a double push of a pawn,
-  // no matter whether an en passant capture is really possible or not
+  // no matter whether an en passant capture is really possible or not (the hash only has it when it is)
   // Note: the rank will always be either 3 or 6.
   en_passant_index: Option<i8>,
 
@@ -514,10 +609,35 @@ pub struct BoardMeta {
 
   // Zobrist hash (https://www.chessprogramming.org/Zobrist_Hashing)
   pub hash: u64,
+
+  // Zobrist hash of only the pawns
+  pub pawn_hash: u64,
+
+  // Sum of the material numbers of every piece, so it only depends on how many pieces of each kind there are
+  pub material_hash: u64,
+}
+
+impl BoardMeta {
+  // Puts the piece on the square or takes it from there, in the hashes
+  fn toggle_piece(&mut self, index: i8, piece: i8) {
+    let key = HASH_TABLE.pieces[BOARD_CONVERT[index as usize]][get_piece_index(piece)];
+    self.hash ^= key;
+    if piece.abs() == PAWN {
+      self.pawn_hash ^= key;
+    }
+  }
+
+  fn add_material(&mut self, piece: i8) {
+    self.material_hash = self.material_hash.wrapping_add(bitboard::MATERIAL[get_piece_index(piece)]);
+  }
+
+  fn remove_material(&mut self, piece: i8) {
+    self.material_hash = self.material_hash.wrapping_sub(bitboard::MATERIAL[get_piece_index(piece)]);
+  }
 }
 
 // Stores all the game information
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq)]
 pub struct Board {
   // The actual piece board. It is 10x12.
   // 2 vertical and 1 horizontal padding.
@@ -537,7 +657,7 @@ pub struct Board {
 
   // The number of the full moves in a game.
   // It starts at 1, and is incremented after each Black's move.
-  fullmove_counter: u32,
+  pub fullmove_counter: u32,
 
   // Stores additional information
   // Gets updated with every move
@@ -561,6 +681,30 @@ impl Board {
     Self::from_fen_saved(fen, vec![])
   }
 
+  // from_fen expects a legal position with all 6 fields, written the way to_fen writes it
+  // (castling rights in KQkq order, no leading zeros). This checks a fen and writes it that way
+  pub fn normalize_fen(fen: &str) -> Result<String, String> {
+    let fields: Vec<&str> = fen.split(' ').collect();
+    if fields.len() != 6 {
+      return Err(format!("expected 6 fen fields, got {}", fields.len()));
+    }
+    if fields[4].parse::<u8>().is_err() {
+      return Err(format!("invalid halfmove clock '{}'", fields[4]));
+    }
+
+    let position = fen
+      .parse::<shakmaty::fen::Fen>()
+      .map_err(|error| error.to_string())?
+      .into_position::<shakmaty::Chess>(shakmaty::CastlingMode::Standard)
+      .map_err(|error| error.to_string())?;
+
+    Ok(shakmaty::fen::Fen::from_position(position, shakmaty::EnPassantMode::Always).to_string())
+  }
+
+  pub fn try_from_fen(fen: &str) -> Result<Board, String> {
+    Ok(Self::from_fen(&Self::normalize_fen(fen)?))
+  }
+
   pub fn from_fen_saved(fen: &str, previous_hashes: Vec<u64>) -> Board {
     let fields: Vec<&str> = fen.split(' ').collect();
     let ranks: Vec<&str> = fields[0].split('/').collect();
@@ -570,8 +714,6 @@ impl Board {
     let mut white_king_index: Option<i8> = None;
     let mut black_king_index: Option<i8> = None;
 
-    let mut hash: u64 = 0;
-
     for (i, s) in ranks.iter().enumerate() {
       let y = 2 + i;
       let mut x = 1;
@@ -600,23 +742,6 @@ impl Board {
             pieces[y * 10 + x] *= -1;
           }
 
-          let index = (y - 2) * 8 + (x - 1);
-          hash ^= match c {
-            'P' => HASH_TABLE.pieces[index][0],
-            'N' => HASH_TABLE.pieces[index][1],
-            'B' => HASH_TABLE.pieces[index][2],
-            'R' => HASH_TABLE.pieces[index][3],
-            'Q' => HASH_TABLE.pieces[index][4],
-            'K' => HASH_TABLE.pieces[index][5],
-            'p' => HASH_TABLE.pieces[index][6],
-            'n' => HASH_TABLE.pieces[index][7],
-            'b' => HASH_TABLE.pieces[index][8],
-            'r' => HASH_TABLE.pieces[index][9],
-            'q' => HASH_TABLE.pieces[index][10],
-            'k' => HASH_TABLE.pieces[index][11],
-            _ => panic!(),
-          };
-
           x += 1;
         } else {
           let digit = c.to_digit(10).expect("Incorrect fen");
@@ -628,30 +753,6 @@ impl Board {
       }
     }
 
-    let side_to_move = if fields[1] == "w" { Color::White } else { Color::Black };
-
-    if side_to_move == Color::Black {
-      hash ^= HASH_TABLE.black_to_move;
-    }
-
-    let white_king_castle = fields[2].contains('K');
-    let white_queen_castle = fields[2].contains('Q');
-    let black_king_castle = fields[2].contains('k');
-    let black_queen_castle = fields[2].contains('q');
-
-    if white_king_castle {
-      hash ^= HASH_TABLE.white_king_castle;
-    }
-    if white_queen_castle {
-      hash ^= HASH_TABLE.white_queen_castle;
-    }
-    if black_king_castle {
-      hash ^= HASH_TABLE.black_king_castle;
-    }
-    if black_queen_castle {
-      hash ^= HASH_TABLE.black_queen_castle;
-    }
-
     let en_passant_index = {
       match fields[3] {
         "-" => None,
@@ -659,29 +760,32 @@ impl Board {
       }
     };
 
-    if let Some(en_passant_index) = en_passant_index {
-      let x = en_passant_index % 10 - 1;
-      hash ^= HASH_TABLE.en_passant[x as usize];
-    }
-
-    Board {
+    let mut board = Board {
       pieces,
-      side_to_move,
+      side_to_move: if fields[1] == "w" { Color::White } else { Color::Black },
       fullmove_counter: fields[5].parse().unwrap(),
       undo_list: vec![],
       meta: BoardMeta {
-        white_king_castle,
-        white_queen_castle,
-        black_king_castle,
-        black_queen_castle,
+        white_king_castle: fields[2].contains('K'),
+        white_queen_castle: fields[2].contains('Q'),
+        black_king_castle: fields[2].contains('k'),
+        black_queen_castle: fields[2].contains('q'),
         en_passant_index,
         halfmove_clock: fields[4].parse().unwrap(),
-        hash,
+        hash: 0,
+        pawn_hash: 0,
+        material_hash: 0,
       },
       white_king_index: white_king_index.expect("Incorrect fen"),
       black_king_index: black_king_index.expect("Incorrect fen"),
       previous_hashes,
-    }
+    };
+
+    board.meta.hash = board.compute_hash_from_scratch();
+    board.meta.pawn_hash = board.compute_pawn_hash_from_scratch();
+    board.meta.material_hash = board.compute_material_hash_from_scratch();
+
+    board
   }
 
   pub fn to_table(&self) -> String {
@@ -994,7 +1098,7 @@ impl Board {
   // To do that it needs to make the move and then undo it.
   // Therefore, pseudo_legal_moves should actually be used and then manually checked if the player is left in check.
   pub fn legal_moves(&mut self) -> Vec<Move> {
-    let side = self.side_to_move.clone();
+    let side = self.side_to_move;
     self
       .pseudo_legal_moves()
       .into_iter()
@@ -1008,18 +1112,99 @@ impl Board {
       .collect()
   }
 
+  // Finds the legal move written like "e2e4" or "e7e8q"
+  pub fn find_move(&mut self, move_fen: &str) -> Option<Move> {
+    self
+      .legal_moves()
+      .into_iter()
+      .find(|chess_move| chess_move.to_fen() == move_fen)
+  }
+
+  pub fn piece_at(&self, square: Square) -> Option<Piece> {
+    let piece = self.pieces[board_index(square) as usize];
+    let color = if piece > 0 { Color::White } else { Color::Black };
+
+    piece_type(piece).map(|piece_type| Piece { color, piece_type })
+  }
+
+  // Standard algebraic notation of a legal move, like "Nbd7", "exd5", "e8=Q+" or "O-O"
+  pub fn to_san(&mut self, chess_move: &Move) -> String {
+    let mut san = match chess_move {
+      Move::Castling(CastlingSide::WhiteKing | CastlingSide::BlackKing) => "O-O".to_string(),
+      Move::Castling(_) => "O-O-O".to_string(),
+      _ => {
+        let (from, to) = (chess_move.from(), chess_move.to());
+        let piece = self.pieces[board_index(from) as usize];
+        let mut san = String::new();
+
+        if piece.abs() == PAWN {
+          if chess_move.is_capture() {
+            san.push_str(&from.file().to_string());
+          }
+        } else {
+          san.push(piece_type(piece).expect("Moving from an empty square").to_char());
+
+          // The same kind of piece which can go to the same square
+          let others: Vec<Square> = self
+            .legal_moves()
+            .iter()
+            .filter(|other| {
+              !matches!(other, Move::Castling(_))
+                && other.to() == to
+                && other.from() != from
+                && self.pieces[board_index(other.from()) as usize] == piece
+            })
+            .map(|other| other.from())
+            .collect();
+
+          if !others.is_empty() {
+            if others.iter().all(|other| other.file() != from.file()) {
+              san.push_str(&from.file().to_string());
+            } else if others.iter().all(|other| other.rank() != from.rank()) {
+              san.push_str(&from.rank().to_string());
+            } else {
+              san.push_str(&from.to_string());
+            }
+          }
+        }
+
+        if chess_move.is_capture() {
+          san.push('x');
+        }
+        san.push_str(&to.to_string());
+        if let Some(piece_type) = chess_move.promoted_piece() {
+          san.push('=');
+          san.push(piece_type.to_char());
+        }
+
+        san
+      }
+    };
+
+    self.make_move(chess_move);
+    if self.in_check(&self.side_to_move) {
+      san.push(if self.legal_moves().is_empty() { '#' } else { '+' });
+    }
+    self.undo_move(chess_move);
+
+    san
+  }
+
+  // Finds the legal move written in SAN. Check marks and annotations are optional, castling can be written with zeros
+  pub fn parse_san(&mut self, san: &str) -> Option<Move> {
+    let clean = |san: &str| san.trim_end_matches(['+', '#', '!', '?']).replace('0', "O");
+    let san = clean(san.trim());
+
+    self
+      .legal_moves()
+      .into_iter()
+      .find(|chess_move| clean(&self.to_san(chess_move)) == san)
+  }
+
   // Generates pseudo-legal moves. It means that it could leave its own king in check.
   // Includes castling (it also can be pseudo-legal)
-  // However, the fifty-move and repetition rules are checked
+  // Note: draw rules are not checked here, see game_status
   pub fn pseudo_legal_moves(&self) -> Vec<Move> {
-    if self.meta.halfmove_clock >= 100
-      || self.undo_list.iter().filter(|m| m.hash == self.meta.hash).count()
-        + self.previous_hashes.iter().filter(|h| **h == self.meta.hash).count()
-        >= 2
-    {
-      return vec![];
-    }
-
     let mut piece_moves: Vec<Move> = Vec::with_capacity(50);
 
     for from in BOARD_INDICES {
@@ -1079,36 +1264,19 @@ impl Board {
     piece_moves
   }
 
-  // Updates meta information: castling rights, en passant square, the halfmove clock, and the hash
+  // Updates meta information: castling rights, en passant square, the halfmove clock, and the hashes
+  // but for their en passant part, which make_move updates
   fn update_meta(&mut self, chess_move: &Move) {
     // Save the current meta information
     self.undo_list.push(self.meta.clone());
 
-    // Get piece's index for the HASH_TABLE
-    fn get_piece_index(piece: i8) -> usize {
-      let mut piece_index = match piece.abs() {
-        PAWN => 0,
-        KNIGHT => 1,
-        BISHOP => 2,
-        ROOK => 3,
-        QUEEN => 4,
-        KING => 5,
-        _ => panic!(),
-      };
-      if piece < 0 {
-        piece_index += 6;
-      }
-      piece_index
-    }
-
-    // Updates board's (just the pieces) part of the hash
+    // Updates board's (just the pieces) part of the hashes
     match *chess_move {
       Move::Normal { from, to } | Move::PawnPush { from, to } | Move::DoublePawnPush { from, to } => {
         let piece = self.pieces[from as usize];
-        let piece_index = get_piece_index(piece);
 
-        self.meta.hash ^= HASH_TABLE.pieces[BOARD_CONVERT[from as usize]][piece_index];
-        self.meta.hash ^= HASH_TABLE.pieces[BOARD_CONVERT[to as usize]][piece_index];
+        self.meta.toggle_piece(from, piece);
+        self.meta.toggle_piece(to, piece);
       }
       Move::Capture {
         from,
@@ -1116,12 +1284,11 @@ impl Board {
         captured_piece,
       } => {
         let piece = self.pieces[from as usize];
-        let piece_index = get_piece_index(piece);
-        let captured_piece_index = get_piece_index(captured_piece);
 
-        self.meta.hash ^= HASH_TABLE.pieces[BOARD_CONVERT[from as usize]][piece_index];
-        self.meta.hash ^= HASH_TABLE.pieces[BOARD_CONVERT[to as usize]][piece_index];
-        self.meta.hash ^= HASH_TABLE.pieces[BOARD_CONVERT[to as usize]][captured_piece_index];
+        self.meta.toggle_piece(from, piece);
+        self.meta.toggle_piece(to, piece);
+        self.meta.toggle_piece(to, captured_piece);
+        self.meta.remove_material(captured_piece);
       }
       Move::EnPassant {
         from,
@@ -1130,12 +1297,11 @@ impl Board {
         captured_piece,
       } => {
         let piece = self.pieces[from as usize];
-        let piece_index = get_piece_index(piece);
-        let captured_piece_index = get_piece_index(captured_piece);
 
-        self.meta.hash ^= HASH_TABLE.pieces[BOARD_CONVERT[from as usize]][piece_index];
-        self.meta.hash ^= HASH_TABLE.pieces[BOARD_CONVERT[to as usize]][piece_index];
-        self.meta.hash ^= HASH_TABLE.pieces[BOARD_CONVERT[captured_index as usize]][captured_piece_index];
+        self.meta.toggle_piece(from, piece);
+        self.meta.toggle_piece(to, piece);
+        self.meta.toggle_piece(captured_index, captured_piece);
+        self.meta.remove_material(captured_piece);
       }
       Move::Promotion {
         from,
@@ -1143,11 +1309,11 @@ impl Board {
         selected_piece,
       } => {
         let piece = self.pieces[from as usize];
-        let piece_index = get_piece_index(piece);
-        let selected_piece_index = get_piece_index(selected_piece);
 
-        self.meta.hash ^= HASH_TABLE.pieces[BOARD_CONVERT[from as usize]][piece_index];
-        self.meta.hash ^= HASH_TABLE.pieces[BOARD_CONVERT[to as usize]][selected_piece_index];
+        self.meta.toggle_piece(from, piece);
+        self.meta.toggle_piece(to, selected_piece);
+        self.meta.remove_material(piece);
+        self.meta.add_material(selected_piece);
       }
       Move::PromotionWithCapture {
         from,
@@ -1156,50 +1322,44 @@ impl Board {
         captured_piece,
       } => {
         let piece = self.pieces[from as usize];
-        let piece_index = get_piece_index(piece);
-        let selected_piece_index = get_piece_index(selected_piece);
-        let captured_piece_index = get_piece_index(captured_piece);
 
-        self.meta.hash ^= HASH_TABLE.pieces[BOARD_CONVERT[from as usize]][piece_index];
-        self.meta.hash ^= HASH_TABLE.pieces[BOARD_CONVERT[to as usize]][selected_piece_index];
-        self.meta.hash ^= HASH_TABLE.pieces[BOARD_CONVERT[to as usize]][captured_piece_index];
+        self.meta.toggle_piece(from, piece);
+        self.meta.toggle_piece(to, selected_piece);
+        self.meta.toggle_piece(to, captured_piece);
+        self.meta.remove_material(piece);
+        self.meta.add_material(selected_piece);
+        self.meta.remove_material(captured_piece);
       }
       Move::Castling(CastlingSide::WhiteKing) => {
-        self.meta.hash ^= HASH_TABLE.pieces[BOARD_CONVERT[95]][5];
-        self.meta.hash ^= HASH_TABLE.pieces[BOARD_CONVERT[98]][3];
+        self.meta.toggle_piece(95, KING);
+        self.meta.toggle_piece(98, ROOK);
 
-        self.meta.hash ^= HASH_TABLE.pieces[BOARD_CONVERT[96]][3];
-        self.meta.hash ^= HASH_TABLE.pieces[BOARD_CONVERT[97]][5];
+        self.meta.toggle_piece(96, ROOK);
+        self.meta.toggle_piece(97, KING);
       }
       Move::Castling(CastlingSide::WhiteQueen) => {
-        self.meta.hash ^= HASH_TABLE.pieces[BOARD_CONVERT[95]][5];
-        self.meta.hash ^= HASH_TABLE.pieces[BOARD_CONVERT[91]][3];
+        self.meta.toggle_piece(95, KING);
+        self.meta.toggle_piece(91, ROOK);
 
-        self.meta.hash ^= HASH_TABLE.pieces[BOARD_CONVERT[94]][3];
-        self.meta.hash ^= HASH_TABLE.pieces[BOARD_CONVERT[93]][5];
+        self.meta.toggle_piece(94, ROOK);
+        self.meta.toggle_piece(93, KING);
       }
       Move::Castling(CastlingSide::BlackKing) => {
-        self.meta.hash ^= HASH_TABLE.pieces[BOARD_CONVERT[25]][11];
-        self.meta.hash ^= HASH_TABLE.pieces[BOARD_CONVERT[28]][9];
+        self.meta.toggle_piece(25, -KING);
+        self.meta.toggle_piece(28, -ROOK);
 
-        self.meta.hash ^= HASH_TABLE.pieces[BOARD_CONVERT[26]][9];
-        self.meta.hash ^= HASH_TABLE.pieces[BOARD_CONVERT[27]][11];
+        self.meta.toggle_piece(26, -ROOK);
+        self.meta.toggle_piece(27, -KING);
       }
       Move::Castling(CastlingSide::BlackQueen) => {
-        self.meta.hash ^= HASH_TABLE.pieces[BOARD_CONVERT[25]][11];
-        self.meta.hash ^= HASH_TABLE.pieces[BOARD_CONVERT[21]][9];
+        self.meta.toggle_piece(25, -KING);
+        self.meta.toggle_piece(21, -ROOK);
 
-        self.meta.hash ^= HASH_TABLE.pieces[BOARD_CONVERT[24]][9];
-        self.meta.hash ^= HASH_TABLE.pieces[BOARD_CONVERT[23]][11];
+        self.meta.toggle_piece(24, -ROOK);
+        self.meta.toggle_piece(23, -KING);
       }
     }
 
-    // If en passant square existed, remove it from the hash
-    if let Some(en_passant_index) = self.meta.en_passant_index {
-      let x = en_passant_index % 10 - 1;
-      self.meta.hash ^= HASH_TABLE.en_passant[x as usize];
-    }
-
     // Updates en passant square and the halfmove clock
     match *chess_move {
       Move::Normal { from: _, to: _ } => {
@@ -1242,12 +1402,6 @@ impl Board {
       }
     }
 
-    // If en passant square exists now, add it to the hash
-    if let Some(en_passant_index) = self.meta.en_passant_index {
-      let x = en_passant_index % 10 - 1;
-      self.meta.hash ^= HASH_TABLE.en_passant[x as usize];
-    }
-
     let previous_white_king_castle = self.meta.white_king_castle;
     let previous_white_queen_castle = self.meta.white_queen_castle;
     let previous_black_king_castle = self.meta.black_king_castle;
@@ -1346,6 +1500,9 @@ impl Board {
   // Makes a move, which updates the board and meta information.
   // Note: it doesn't check if it is legal.
   pub fn make_move(&mut self, chess_move: &Move) {
+    // Before the king indices change, in_check needs them
+    let en_passant_key = self.en_passant_key();
+
     // update king position indices
     match *chess_move {
       Move::Normal { from, to }
@@ -1377,6 +1534,7 @@ impl Board {
     }
 
     self.update_meta(chess_move);
+    self.meta.hash ^= en_passant_key;
 
     // move pieces
     match *chess_move {
@@ -1456,6 +1614,9 @@ impl Board {
         Color::White
       }
     };
+
+    self.meta.hash ^= self.en_passant_key();
+    self.debug_check_hashes();
   }
 
   pub fn undo_move(&mut self, chess_move: &Move) {
@@ -1577,6 +1738,103 @@ impl Board {
         self.pieces[23] = EMPTY;
       }
     };
+
+    self.debug_check_hashes();
+  }
+
+  // Whether the side to move has a legal en passant capture. Only then the en passant square is in the hash,
+  // so that the same position after a double push or after any other move has the same hash
+  fn can_capture_en_passant(&self) -> bool {
+    let Some(to) = self.meta.en_passant_index else {
+      return false;
+    };
+    let (pawn, forward, king_index) = if self.side_to_move == Color::White {
+      (PAWN, -10, self.white_king_index)
+    } else {
+      (-PAWN, 10, self.black_king_index)
+    };
+    let captured_index = to - forward;
+
+    [captured_index - 1, captured_index + 1].into_iter().any(|from| {
+      if self.pieces[from as usize] != pawn {
+        return false;
+      }
+
+      // The capture is made on a copy of the pieces, the king doesn't move
+      let mut pieces = self.pieces;
+      pieces[from as usize] = EMPTY;
+      pieces[to as usize] = pawn;
+      pieces[captured_index as usize] = EMPTY;
+      !Self::square_is_attacked_in(&pieces, king_index, &self.side_to_move)
+    })
+  }
+
+  fn en_passant_key(&self) -> u64 {
+    let en_passant_index = self.meta.en_passant_index;
+    match en_passant_index {
+      Some(en_passant_index) if self.can_capture_en_passant() => HASH_TABLE.en_passant[(en_passant_index % 10 - 1) as usize],
+      _ => 0,
+    }
+  }
+
+  pub fn compute_hash_from_scratch(&self) -> u64 {
+    let mut hash = (21..99)
+      .filter(|index| self.pieces[*index] != EMPTY && self.pieces[*index] != OUTSIDE)
+      .fold(0, |hash, index| {
+        hash ^ HASH_TABLE.pieces[BOARD_CONVERT[index]][get_piece_index(self.pieces[index])]
+      });
+
+    if self.side_to_move == Color::Black {
+      hash ^= HASH_TABLE.black_to_move;
+    }
+    if self.meta.white_king_castle {
+      hash ^= HASH_TABLE.white_king_castle;
+    }
+    if self.meta.white_queen_castle {
+      hash ^= HASH_TABLE.white_queen_castle;
+    }
+    if self.meta.black_king_castle {
+      hash ^= HASH_TABLE.black_king_castle;
+    }
+    if self.meta.black_queen_castle {
+      hash ^= HASH_TABLE.black_queen_castle;
+    }
+
+    hash ^ self.en_passant_key()
+  }
+
+  pub fn compute_pawn_hash_from_scratch(&self) -> u64 {
+    (21..99)
+      .filter(|index| self.pieces[*index].abs() == PAWN)
+      .fold(0, |hash, index| {
+        hash ^ HASH_TABLE.pieces[BOARD_CONVERT[index]][get_piece_index(self.pieces[index])]
+      })
+  }
+
+  pub fn compute_material_hash_from_scratch(&self) -> u64 {
+    (21..99)
+      .map(|index| self.pieces[index])
+      .filter(|piece| *piece != EMPTY && *piece != OUTSIDE)
+      .fold(0, |hash, piece| {
+        hash.wrapping_add(bitboard::MATERIAL[get_piece_index(piece)])
+      })
+  }
+
+  // The hashes are updated move by move, debug builds check them against the ones computed from scratch
+  fn debug_check_hashes(&self) {
+    debug_assert_eq!(self.meta.hash, self.compute_hash_from_scratch(), "{}", self.to_fen());
+    debug_assert_eq!(
+      self.meta.pawn_hash,
+      self.compute_pawn_hash_from_scratch(),
+      "{}",
+      self.to_fen()
+    );
+    debug_assert_eq!(
+      self.meta.material_hash,
+      self.compute_material_hash_from_scratch(),
+      "{}",
+      self.to_fen()
+    );
   }
 
   pub fn in_check(&self, side: &Color) -> bool {
@@ -1587,9 +1845,102 @@ impl Board {
     }
   }
 
+  // Returns whether the current position has occurred at least `count` times (including the current one).
+  // Only the last halfmove_clock plies are looked at, because a capture or a pawn move can't be repeated.
+  pub fn is_repetition(&self, count: usize) -> bool {
+    let mut occurrences = 1;
+
+    // Hashes of the previous positions, the most recent one first
+    let history = self
+      .undo_list
+      .iter()
+      .rev()
+      .map(|meta| meta.hash)
+      .chain(self.previous_hashes.iter().rev().copied());
+
+    for (i, hash) in history.take(self.meta.halfmove_clock as usize).enumerate() {
+      // Only every second position has the same side to move
+      if i % 2 == 1 && hash == self.meta.hash {
+        occurrences += 1;
+      }
+    }
+
+    occurrences >= count
+  }
+
+  pub fn is_fifty_move_draw(&self) -> bool {
+    self.meta.halfmove_clock >= 100
+  }
+
+  // Plies since the last capture or pawn move
+  pub fn halfmove_clock(&self) -> u8 {
+    self.meta.halfmove_clock
+  }
+
+  // Both kings included
+  pub fn piece_count(&self) -> usize {
+    BOARD_INDICES
+      .iter()
+      .filter(|index| self.pieces[**index as usize] != EMPTY)
+      .count()
+  }
+
+  // Returns whether neither side can possibly checkmate:
+  // only kings and a single minor piece, or only kings and bishops on the same colored squares
+  pub fn is_insufficient_material(&self) -> bool {
+    let mut knights = 0;
+    let mut light_square_bishops = 0;
+    let mut dark_square_bishops = 0;
+
+    for index in BOARD_INDICES {
+      match self.pieces[index as usize].abs() {
+        PAWN | ROOK | QUEEN => return false,
+        KNIGHT => knights += 1,
+        BISHOP => {
+          if (index / 10 + index % 10) % 2 == 1 {
+            light_square_bishops += 1;
+          } else {
+            dark_square_bishops += 1;
+          }
+        }
+        _ => {}
+      }
+    }
+
+    knights + light_square_bishops + dark_square_bishops <= 1
+      || (knights == 0 && (light_square_bishops == 0 || dark_square_bishops == 0))
+  }
+
+  // Checkmate and stalemate take precedence over the draw rules
+  pub fn game_status(&mut self) -> GameStatus {
+    if self.legal_moves().is_empty() {
+      let side_to_move = self.side_to_move;
+      return if self.in_check(&side_to_move) {
+        GameStatus::Checkmate
+      } else {
+        GameStatus::Stalemate
+      };
+    }
+
+    if self.is_fifty_move_draw() {
+      GameStatus::Draw(DrawReason::FiftyMoveRule)
+    } else if self.is_repetition(3) {
+      GameStatus::Draw(DrawReason::Repetition)
+    } else if self.is_insufficient_material() {
+      GameStatus::Draw(DrawReason::InsufficientMaterial)
+    } else {
+      GameStatus::Ongoing
+    }
+  }
+
   // Returns whether a square is attacked by any of the other side's pieces.
   // Note: does not take en passant into consideration (although, it shouldn't really matter).
   fn square_is_attacked(&self, index: i8, defending_side: &Color) -> bool {
+    Self::square_is_attacked_in(&self.pieces, index, defending_side)
+  }
+
+  // Same, in another arrangement of the pieces
+  fn square_is_attacked_in(pieces: &[i8; 120], index: i8, defending_side: &Color) -> bool {
     let knight_positions: [i8; 8] = [
       index - 21,
       index - 19,
@@ -1602,7 +1953,7 @@ impl Board {
     ];
 
     if knight_positions.iter().any(|&position| {
-      let square = self.pieces[position as usize];
+      let square = pieces[position as usize];
       (*defending_side == Color::White && square == -KNIGHT) || (*defending_side == Color::Black && square == KNIGHT)
     }) {
       return true;
@@ -1616,7 +1967,7 @@ impl Board {
       loop {
         position += direction;
 
-        let square = self.pieces[position as usize];
+        let square = pieces[position as usize];
 
         if square == OUTSIDE {
           break false;
@@ -1641,7 +1992,7 @@ impl Board {
       loop {
         position += direction;
 
-        let square = self.pieces[position as usize];
+        let square = pieces[position as usize];
 
         if square == OUTSIDE {
           break false;
@@ -1670,7 +2021,7 @@ impl Board {
     ];
 
     if king_positions.iter().any(|&position| {
-      let square = self.pieces[position as usize];
+      let square = pieces[position as usize];
       (*defending_side == Color::White && square == -KING) || (*defending_side == Color::Black && square == KING)
     }) {
       return true;
@@ -1678,20 +2029,20 @@ impl Board {
 
     if *defending_side == Color::White {
       let up_left = index - 11;
-      let up_left_square = self.pieces[up_left as usize];
+      let up_left_square = pieces[up_left as usize];
 
       let up_right = index - 9;
-      let up_right_square = self.pieces[up_right as usize];
+      let up_right_square = pieces[up_right as usize];
 
       if up_left_square == -PAWN || up_right_square == -PAWN {
         return true;
       }
     } else {
       let down_left = index + 9;
-      let down_left_square = self.pieces[down_left as usize];
+      let down_left_square = pieces[down_left as usize];
 
       let down_right = index + 11;
-      let down_right_square = self.pieces[down_right as usize];
+      let down_right_square = pieces[down_right as usize];
 
       if down_left_square == PAWN || down_right_square == PAWN {
         return true;
@@ -1785,22 +2136,41 @@ impl Board {
     format!("{piece_placement} {side_to_move} {castling_ability} {en_passant_target_square} {halfmove_clock} {fullmove_counter}")
   }
 
-  // Positive is better for white, negative for black
-  pub fn evaluate(&self) -> i32 {
-    if self.meta.halfmove_clock >= 100
-      || self.undo_list.iter().filter(|m| m.hash == self.meta.hash).count()
-        + self.previous_hashes.iter().filter(|h| **h == self.meta.hash).count()
-        >= 2
-    {
-      return 0;
+  // The pieces as bitboards, for the endgame evaluation
+  fn bitboards(&self) -> endgame::Pieces {
+    let mut bitboards = endgame::Pieces::default();
+
+    for index in BOARD_INDICES {
+      let square = self.pieces[index as usize];
+
+      if get_color(square).is_some() {
+        let piece_index = get_piece_index(square);
+        // BOARD_CONVERT starts from a8, bitboard squares from a1
+        let bitboard_square = Square::from_index(BOARD_CONVERT[index as usize] ^ 56).expect("Squares are below 64");
+        bitboards[piece_index / 6][piece_index % 6] |= bitboard_square.bitboard();
+      }
     }
 
+    bitboards
+  }
+
+  // Positive is better for white, negative for black
+  pub fn evaluate(&self) -> i32 {
     let mut eval = 0;
+    // Opposite bishops are only possible with one bishop for each side and nothing else but pawns
+    let mut bishops = [0, 0];
+    let mut other_pieces = false;
 
     for index in BOARD_INDICES {
       let square = self.pieces[index as usize];
 
       if get_color(square).is_some() {
+        match square.abs() {
+          BISHOP => bishops[(square < 0) as usize] += 1,
+          KNIGHT | ROOK | QUEEN => other_pieces = true,
+          _ => {}
+        }
+
         let mut piece_value = 0;
         piece_value += match square.abs() {
           PAWN => PAWN_VALUE,
@@ -1846,7 +2216,12 @@ impl Board {
       eval += -POSSIBLE_CASTLING_VALUE;
     }
 
-    eval
+    // Building the bitboards is only worth it when the endgame evaluation can change something
+    if endgame::is_known(self.meta.material_hash) || (bishops == [1, 1] && !other_pieces) {
+      endgame::evaluate(self.meta.material_hash, &self.bitboards(), self.side_to_move, eval)
+    } else {
+      eval
+    }
   }
 
   // Positive is better for who's turn it is
@@ -1861,7 +2236,7 @@ impl Board {
 
 #[cfg(test)]
 mod tests {
-  use crate::board::{index_to_square, square_to_index, Move};
+  use crate::board::{index_to_square, square_to_index, DrawReason, GameStatus, Move};
 
   use super::Board;
 
@@ -1968,87 +2343,11 @@ mod tests {
     assert_eq!(expected_moves, generated_moves);
   }
 
-  #[test]
-  fn perft_test() {
-    // https://www.chessprogramming.org/Perft_Results
-    fn test_fen<const N: usize>(fen: &str, depth_nodes: [u64; N]) {
-      let mut board = Board::from_fen(fen);
-
-      fn perft(depth: usize, board: &mut Board) -> u64 {
-        // https://www.chessprogramming.org/Perft
-
-        let mut nodes = 0;
-
-        if depth == 0 {
-          return 1;
-        }
-
-        let side_to_move = board.side_to_move.clone();
-
-        for chess_move in board.pseudo_legal_moves() {
-          board.make_move(&chess_move);
-          if !board.in_check(&side_to_move) {
-            nodes += perft(depth - 1, board);
-          }
-          board.undo_move(&chess_move);
-        }
-
-        nodes
-      }
-
-      for (i, nodes) in depth_nodes.iter().enumerate() {
-        assert_eq!(perft(i, &mut board), *nodes);
-      }
-    }
-
-    // Position 1
-    test_fen::<7>(
-      "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
-      [1, 20, 400, 8_902, 197_281, 4_865_609, 119_060_324],
-    );
-
-    // Position 2
-    test_fen::<6>(
-      "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
-      [1, 48, 2_039, 97_862, 4_085_603, 193_690_690],
-    );
-
-    // Position 3
-    test_fen::<7>(
-      "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
-      [1, 14, 191, 2_812, 43_238, 674_624, 11_030_083],
-    );
-
-    // Position 4
-    test_fen::<6>(
-      "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
-      [1, 6, 264, 9_467, 422_333, 15_833_292],
-    );
-
-    // Position 4 mirrored
-    test_fen::<6>(
-      "r2q1rk1/pP1p2pp/Q4n2/bbp1p3/Np6/1B3NBn/pPPP1PPP/R3K2R b KQ - 0 1",
-      [1, 6, 264, 9_467, 422_333, 15_833_292],
-    );
-
-    // Position 5
-    test_fen::<6>(
-      "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8",
-      [1, 44, 1_486, 62_379, 2_103_487, 89_941_194],
-    );
-
-    // Position 6
-    test_fen::<6>(
-      "r4rk1/1pp1qppp/p1np1n2/2b1p1B1/2B1P1b1/P1NP1N2/1PP1QPPP/R4RK1 w - - 0 10",
-      [1, 46, 2_079, 89_890, 3_894_594, 164_075_551],
-    );
-  }
-
   #[test]
   fn hash_test() {
     fn test_hashing(fen: &str) {
       let mut board = Board::from_fen(fen);
-      let side_to_move = board.side_to_move.clone();
+      let side_to_move = board.side_to_move;
       let expected_hash = board.meta.hash;
 
       for chess_move in board.pseudo_legal_moves() {
@@ -2171,4 +2470,62 @@ mod tests {
         .hash
     );
   }
+
+  #[test]
+  fn game_status_test() {
+    let mut board = Board::default();
+
+    for (i, chess_move) in ["g1f3", "g8f6", "f3g1", "f6g8", "g1f3", "g8f6", "f3g1", "f6g8"]
+      .iter()
+      .enumerate()
+    {
+      assert_eq!(board.is_repetition(2), i >= 4);
+      assert_eq!(board.game_status(), GameStatus::Ongoing);
+
+      let chess_move = board.legal_moves().into_iter().find(|m| m.to_fen() == *chess_move).unwrap();
+      board.make_move(&chess_move);
+    }
+
+    assert!(board.is_repetition(3));
+    assert_eq!(board.game_status(), GameStatus::Draw(DrawReason::Repetition));
+
+    // A pawn move can't be repeated
+    let mut board = Board::from_fen_saved("8/8/4k3/8/8/4K3/4P3/8 w - - 0 1", vec![0, 0]);
+    assert!(!board.is_repetition(2));
+    assert_eq!(board.game_status(), GameStatus::Ongoing);
+
+    assert_eq!(
+      Board::from_fen("8/8/4k3/8/8/4K3/4P3/8 w - - 100 80").game_status(),
+      GameStatus::Draw(DrawReason::FiftyMoveRule)
+    );
+    assert_eq!(
+      Board::from_fen("8/8/4k3/8/8/4K3/8/8 w - - 0 1").game_status(),
+      GameStatus::Draw(DrawReason::InsufficientMaterial)
+    );
+    assert_eq!(
+      Board::from_fen("8/8/4k3/8/8/4K3/5N2/8 w - - 0 1").game_status(),
+      GameStatus::Draw(DrawReason::InsufficientMaterial)
+    );
+    assert_eq!(
+      Board::from_fen("8/3b4/4k3/8/8/4K3/4B3/8 w - - 0 1").game_status(),
+      GameStatus::Draw(DrawReason::InsufficientMaterial)
+    );
+    assert_eq!(
+      Board::from_fen("8/4b3/4k3/8/8/4K3/4B3/8 w - - 0 1").game_status(),
+      GameStatus::Ongoing
+    );
+    assert_eq!(
+      Board::from_fen("8/4n3/4k3/8/8/4K3/5N2/8 w - - 0 1").game_status(),
+      GameStatus::Ongoing
+    );
+
+    assert_eq!(
+      Board::from_fen("rnb1kbnr/pppp1ppp/8/4p3/6Pq/5P2/PPPPP2P/RNBQKBNR w KQkq - 1 3").game_status(),
+      GameStatus::Checkmate
+    );
+    assert_eq!(
+      Board::from_fen("7k/5Q2/6K1/8/8/8/8/8 b - - 0 1").game_status(),
+      GameStatus::Stalemate
+    );
+  }
 }
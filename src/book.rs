@@ -0,0 +1,139 @@
+use crate::board::Board;
+use crate::config;
+use hashbrown::HashSet;
+use pgn_reader::{BufferedReader, RawHeader, SanPlus, Skip, Visitor};
+use shakmaty::Position;
+use std::{
+  fs::File,
+  io::{BufRead, BufReader, BufWriter, Write},
+};
+
+// Games with a weaker player than book.min_elo are skipped, as well as games not starting from the initial position
+struct Mover {
+  writer: BufWriter<File>,
+  board: Board,
+  will_skip: bool,
+  pos: shakmaty::Chess,
+  selected: i32,
+  total: i32,
+  had: HashSet<u64>,
+}
+
+impl Mover {
+  fn new(file: File) -> Self {
+    Self {
+      writer: BufWriter::new(file),
+      board: Board::default(),
+      will_skip: false,
+      pos: shakmaty::Chess::default(),
+      selected: 0,
+      total: 0,
+      had: HashSet::new(),
+    }
+  }
+}
+
+impl Visitor for Mover {
+  type Result = ();
+
+  fn begin_game(&mut self) {
+    self.will_skip = false;
+    self.board = Board::default();
+    self.pos = shakmaty::Chess::default();
+    self.total += 1;
+  }
+
+  fn end_game(&mut self) -> Self::Result {}
+
+  fn header(&mut self, key: &[u8], value: RawHeader<'_>) {
+    if key == b"FEN"
+      || ((key == b"WhiteElo" || key == b"BlackElo")
+        && value
+          .decode_utf8()
+          .ok()
+          .and_then(|elo| elo.parse::<i32>().ok())
+          .map_or(false, |elo| elo < config::get().book.min_elo))
+    {
+      self.will_skip = true;
+    }
+  }
+
+  fn end_headers(&mut self) -> Skip {
+    if !self.will_skip {
+      self.selected += 1;
+
+      if self.selected % 1000 == 0 {
+        println!("{}/{} | {}", self.selected, self.total, self.had.len());
+      }
+    }
+    Skip(self.will_skip)
+  }
+
+  fn san(&mut self, san_plus: SanPlus) {
+    let fen = shakmaty::fen::Fen::from_setup(self.pos.clone().into_setup(shakmaty::EnPassantMode::Always)).to_string();
+    let hash = Board::from_fen(&fen).meta.hash;
+    let m = san_plus.san.to_move(&self.pos).unwrap();
+
+    if !self.had.contains(&hash) {
+      let uci_move = m.to_uci(shakmaty::CastlingMode::Standard).to_string();
+      let line = hash.to_string() + "|" + &uci_move + "\n";
+      self.writer.write_all(line.as_bytes()).unwrap();
+
+      self.had.insert(hash);
+    }
+
+    self.pos.play_unchecked(&m);
+  }
+}
+
+// Saves the first move played in every position of the PGN games to the book file
+pub fn build(pgn_path: &str) -> Result<(), String> {
+  let book_path = &config::get().book.path;
+  let pgn = File::open(pgn_path).map_err(|error| format!("couldn't open '{pgn_path}': {error}"))?;
+  let book = File::create(book_path).map_err(|error| format!("couldn't create '{book_path}': {error}"))?;
+
+  let mut reader = BufferedReader::new(pgn);
+  let mut mover = Mover::new(book);
+  reader.read_all(&mut mover).map_err(|error| error.to_string())?;
+  mover.writer.flush().map_err(|error| error.to_string())?;
+
+  println!("{}/{} games, {} positions", mover.selected, mover.total, mover.had.len());
+  Ok(())
+}
+
+// "<hash>|<move>", None for anything else
+pub fn parse_line(line: &str) -> Option<(u64, &str)> {
+  let (hash, move_fen) = line.trim_end().split_once('|')?;
+  move_fen.parse::<shakmaty::uci::Uci>().ok()?;
+  Some((hash.parse().ok()?, move_fen))
+}
+
+// Broken lines of the book are skipped
+pub fn try_find_opening(fen: &str) -> Option<String> {
+  let book = &config::get().book;
+  if !book.enabled {
+    return None;
+  }
+
+  log!(Info, "- Trying to find a saved position...");
+  let hash = Board::from_fen(fen).meta.hash;
+
+  let Ok(file) = File::open(&book.path) else {
+    log!(Error, "- Book file '{}' missing", book.path);
+    return None;
+  };
+  let reader = BufReader::new(file);
+
+  for line in reader.lines().map_while(Result::ok) {
+    match parse_line(&line) {
+      Some((h, m)) if h == hash => {
+        log!(Info, "- Found a saved position!");
+        return Some(m.to_string());
+      }
+      _ => {}
+    }
+  }
+
+  log!(Info, "- Could not find a saved position");
+  None
+}
@@ -0,0 +1,579 @@
+use crate::bitboard::Color;
+use crate::board::{Board, GameStatus};
+use crate::config::{self, Config};
+use crate::engine_match::{self, Adjudication, Engine, MatchSettings, Sprt};
+use crate::mate::{self, MateResult, MateSolver};
+use crate::search::{format_score, iterative_deepening, RootMoves, SearchLimits, TranspositionTable};
+use crate::{bench, bitboard, book, differential, epd_test, http, perft, server, tcp, uci};
+use std::str::FromStr;
+
+const CONNECT_ATTEMPTS: u32 = 10;
+const SERVER_PORT: u16 = 6971;
+const HTTP_PORT: u16 = 8080;
+const DEFAULT_SEARCH_DEPTH: i32 = 8;
+const DEFAULT_SELFPLAY_MOVETIME: u128 = 100;
+const DEFAULT_SELFPLAY_MAX_MOVES: usize = 200;
+const DEFAULT_PGN: &str = "db/games.pgn";
+
+struct Command {
+  name: &'static str,
+  usage: &'static str,
+  about: &'static str,
+  // Flags without and with a value
+  flags: &'static [&'static str],
+  options: &'static [&'static str],
+}
+
+const COMMANDS: [Command; 15] = [
+  Command {
+    name: "play-tcp",
+    usage: "",
+    about: "Plays against the interface at --host/--port (6969 or 6970 by side), the default command",
+    flags: &[],
+    options: &[],
+  },
+  Command {
+    name: "uci",
+    usage: "",
+    about: "Speaks UCI on stdin/stdout",
+    flags: &[],
+    options: &[],
+  },
+  Command {
+    name: "serve",
+    usage: "",
+    about: "Serves UCI and interface clients at --host/--port (6971), each with its own game",
+    flags: &[],
+    options: &[],
+  },
+  Command {
+    name: "http",
+    usage: "",
+    about: "Serves the JSON API at --host/--port (8080)",
+    flags: &[],
+    options: &[],
+  },
+  Command {
+    name: "perft",
+    usage: "<fen|startpos> <depth> [--divide] [--jobs <n>] [--table <MB>]",
+    about: "Counts the leaf nodes of the move tree on --jobs threads (all cores), --divide prints the count after every move \
+            and --table counts transpositions once",
+    flags: &["divide"],
+    options: &["jobs", "table"],
+  },
+  Command {
+    name: "perft-epd",
+    usage: "[path] [--depth <n>]",
+    about: "Checks the perft counts of an EPD file (the bundled suite) on both boards, by default at every depth",
+    flags: &[],
+    options: &["depth"],
+  },
+  Command {
+    name: "differential",
+    usage: "[--games <n>] [--seed <n>] [--max-plies <n>]",
+    about: "Plays random games (1000 of up to 300 plies) and compares both boards and shakmaty after every move, \
+            the same games for the same seed",
+    flags: &[],
+    options: &["games", "seed", "max-plies"],
+  },
+  Command {
+    name: "search",
+    usage: "<fen|startpos> [--depth <n>] [--movetime <ms>] [--multipv <n>] [moves to search...]",
+    about: "Searches a position and prints every depth, by default up to depth 8",
+    flags: &[],
+    options: &["depth", "movetime", "multipv"],
+  },
+  Command {
+    name: "mate",
+    usage: "<fen> <moves> [--checks]",
+    about: "Proves or refutes a mate in at most the given number of moves and prints the mating line, \
+            --checks only tries checking moves for the attacker, which is faster but can't refute a mate",
+    flags: &["checks"],
+    options: &[],
+  },
+  Command {
+    name: "epd-test",
+    usage: "<path> [--movetime <ms>] [--depth <n>] [--nodes <n>]",
+    about: "Searches the positions of an EPD test suite (bm, am, id, c0), 1 s each by default, and counts the solved ones",
+    flags: &[],
+    options: &["movetime", "depth", "nodes"],
+  },
+  Command {
+    name: "match",
+    usage: "[--engine1 <self|command>] [--engine2 <self|command>] [--games <n>] [--openings <epd|pgn>] \
+            [--movetime <ms>] [--depth <n>] [--nodes <n>] [--pgn <path>] [--sprt] [--elo0 <elo>] [--elo1 <elo>] \
+            [--alpha <p>] [--beta <p>] [--adjudicate-mate <moves>]",
+    about: "Plays games between two engines, this one (self) or UCI engines, from the openings (the bench positions) \
+            with swapped colours, 100 games of 100 ms per move by default. --adjudicate-mate ends games with a forced \
+            mate in at most that many moves",
+    flags: &["sprt"],
+    options: &[
+      "engine1",
+      "engine2",
+      "games",
+      "openings",
+      "movetime",
+      "depth",
+      "nodes",
+      "pgn",
+      "elo0",
+      "elo1",
+      "alpha",
+      "beta",
+      "adjudicate-mate",
+    ],
+  },
+  Command {
+    name: "bench",
+    usage: "[depth]",
+    about: "Searches the bench positions to a fixed depth (4), the node count is the signature",
+    flags: &[],
+    options: &[],
+  },
+  Command {
+    name: "build-book",
+    usage: "[--pgn <path>]",
+    about: "Builds the book at book.path from the games of a PGN file (db/games.pgn)",
+    flags: &[],
+    options: &["pgn"],
+  },
+  Command {
+    name: "selfplay",
+    usage: "[fen|startpos] [--movetime <ms>] [--depth <n>] [--max-moves <n>]",
+    about: "Plays a game against itself, 100 ms per move and up to 200 moves by default",
+    flags: &[],
+    options: &["movetime", "depth", "max-moves"],
+  },
+  Command {
+    name: "help",
+    usage: "[command]",
+    about: "Prints the help of all commands or of one",
+    flags: &[],
+    options: &[],
+  },
+];
+
+fn print_help() {
+  println!("Usage: rusty_chess [command] [args] [config flags]");
+  println!();
+  println!("Commands:");
+  for command in &COMMANDS {
+    println!("  {:<12}{}", command.name, command.about);
+  }
+  println!();
+  println!(
+    "Config flags (see config.toml): --config <path> {}",
+    config::flags().join(" ")
+  );
+  println!("Run 'rusty_chess <command> --help' for the arguments of a command");
+}
+
+fn print_command_help(command: &Command) {
+  println!("Usage: rusty_chess {} {}", command.name, command.usage);
+  println!();
+  println!("{}", command.about);
+}
+
+fn find_command(name: &str) -> Result<&'static Command, String> {
+  COMMANDS
+    .iter()
+    .find(|command| command.name == name)
+    .ok_or(format!("unknown command '{name}', see 'rusty_chess help'"))
+}
+
+// The args of a command, without the config flags
+struct Args {
+  positional: Vec<String>,
+  flags: Vec<String>,
+  options: Vec<(String, String)>,
+}
+
+impl Args {
+  fn parse(command: &Command, args: &[String]) -> Result<Args, String> {
+    let mut parsed = Args {
+      positional: vec![],
+      flags: vec![],
+      options: vec![],
+    };
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+      let Some(flag) = arg.strip_prefix("--") else {
+        parsed.positional.push(arg.clone());
+        continue;
+      };
+
+      let (name, value) = match flag.split_once('=') {
+        Some((name, value)) => (name, Some(value.to_string())),
+        None => (flag, None),
+      };
+
+      if command.flags.contains(&name) && value.is_none() {
+        parsed.flags.push(name.to_string());
+      } else if command.options.contains(&name) {
+        let value = value
+          .or_else(|| args.next().cloned())
+          .ok_or(format!("--{name} needs a value"))?;
+        parsed.options.push((name.to_string(), value));
+      } else {
+        return Err(format!("unknown flag '{arg}'"));
+      }
+    }
+
+    Ok(parsed)
+  }
+
+  fn flag(&self, name: &str) -> bool {
+    self.flags.iter().any(|flag| flag == name)
+  }
+
+  fn option<T: FromStr>(&self, name: &str) -> Result<Option<T>, String> {
+    match self.options.iter().rev().find(|(option, _)| option == name) {
+      Some((_, value)) => value.parse().map(Some).map_err(|_| format!("invalid --{name} '{value}'")),
+      None => Ok(None),
+    }
+  }
+
+  fn positional<T: FromStr>(&self, index: usize, name: &str) -> Result<Option<T>, String> {
+    match self.positional.get(index) {
+      Some(value) => value.parse().map(Some).map_err(|_| format!("invalid {name} '{value}'")),
+      None => Ok(None),
+    }
+  }
+}
+
+fn parse_position(fen: &str) -> Result<Board, String> {
+  if fen == "startpos" {
+    Ok(Board::default())
+  } else {
+    Board::try_from_fen(fen)
+  }
+}
+
+// The first arg is the command, play-tcp without one
+pub async fn run(args: &[String]) -> Result<(), String> {
+  let (name, args) = match args.split_first() {
+    Some((name, args)) => (name.as_str(), args),
+    None => ("play-tcp", args),
+  };
+
+  if matches!(name, "help" | "--help" | "-h") {
+    match args.first() {
+      Some(name) => print_command_help(find_command(name)?),
+      None => print_help(),
+    }
+    return Ok(());
+  }
+
+  let command = find_command(name)?;
+  if args.iter().any(|arg| arg == "--help" || arg == "-h") {
+    print_command_help(command);
+    return Ok(());
+  }
+
+  let parsed =
+    Args::parse(command, args).map_err(|error| format!("{error}\n\nUsage: rusty_chess {} {}", command.name, command.usage))?;
+  let config = config::get();
+
+  match command.name {
+    "play-tcp" => play_tcp(config).await,
+    "uci" => uci::run(),
+    "serve" => server::run(&config.host, config.port.unwrap_or(SERVER_PORT))
+      .await
+      .map_err(|error| format!("server error: {error}"))?,
+    "http" => http::run(&config.host, config.port.unwrap_or(HTTP_PORT))
+      .await
+      .map_err(|error| format!("HTTP server error: {error}"))?,
+    "perft" => {
+      let (Some(fen), Some(depth)) = (parsed.positional.first(), parsed.positional::<usize>(1, "depth")?) else {
+        return Err(format!("a position and a depth are needed\n\nUsage: rusty_chess perft {}", command.usage));
+      };
+      if depth == 0 {
+        return Err("depth has to be at least 1".to_string());
+      }
+      perft(fen, depth, &parsed)?;
+    }
+    "perft-epd" => {
+      let contents = match parsed.positional.first() {
+        Some(path) => std::fs::read_to_string(path).map_err(|error| format!("couldn't read '{path}': {error}"))?,
+        None => perft::SUITE.to_string(),
+      };
+      if !perft::run_suite(&contents, parsed.option("depth")?.unwrap_or(usize::MAX))? {
+        return Err("some perft counts don't match".to_string());
+      }
+    }
+    "differential" => differential(&parsed)?,
+    "search" => search(&parsed)?,
+    "mate" => {
+      let (Some(fen), Some(moves)) = (parsed.positional.first(), parsed.positional::<usize>(1, "moves")?) else {
+        return Err(format!(
+          "a position and a number of moves are needed\n\nUsage: rusty_chess mate {}",
+          command.usage
+        ));
+      };
+      mate(fen, moves, parsed.flag("checks"))?;
+    }
+    "epd-test" => epd_test(&parsed)?,
+    "match" => engine_match(&parsed)?,
+    "bench" => bench::run(parsed.positional(0, "depth")?.unwrap_or(bench::DEFAULT_DEPTH)),
+    "build-book" => book::build(&parsed.option::<String>("pgn")?.unwrap_or(DEFAULT_PGN.to_string()))?,
+    "selfplay" => selfplay(&parsed)?,
+    _ => unreachable!("help is handled above"),
+  }
+
+  Ok(())
+}
+
+async fn play_tcp(config: &Config) {
+  let default_port = if config.white_side { 6969 } else { 6970 };
+  let port = config.port.unwrap_or(default_port);
+
+  // Every connection is a new game, until the interface can't be reached anymore
+  while let Some(stream) = tcp::connect(&config.host, port, CONNECT_ATTEMPTS).await {
+    log!(Info, "- Connected to the interface");
+    tcp::play(stream).await;
+  }
+
+  log!(Error, "- Couldn't connect to the interface");
+}
+
+fn perft(fen: &str, depth: usize, args: &Args) -> Result<(), String> {
+  let board = if fen == "startpos" {
+    bitboard::Board::default()
+  } else {
+    bitboard::Board::try_from_fen(fen)?
+  };
+
+  let jobs = match args.option("jobs")? {
+    Some(jobs) => jobs,
+    None => std::thread::available_parallelism().map_or(1, |jobs| jobs.get()),
+  };
+  if jobs == 0 {
+    return Err("--jobs has to be at least 1".to_string());
+  }
+
+  let table = args.option("table")?;
+  if table == Some(0) {
+    return Err("--table has to be at least 1 MB".to_string());
+  }
+
+  perft::run(&board, depth, args.flag("divide"), table, jobs);
+  Ok(())
+}
+
+fn differential(args: &Args) -> Result<(), String> {
+  let games = args.option("games")?.unwrap_or(differential::DEFAULT_GAMES);
+  let seed = args.option("seed")?.unwrap_or(0);
+  let max_plies = args.option("max-plies")?.unwrap_or(differential::DEFAULT_MAX_PLIES);
+  let start = std::time::Instant::now();
+
+  let compared = differential::run(games, seed, max_plies).map_err(|divergence| format!("the boards diverge: {divergence}"))?;
+  println!(
+    "- {games} games with seed {seed}, {compared} positions compared in {} ms, no differences",
+    start.elapsed().as_millis()
+  );
+  Ok(())
+}
+
+fn search(args: &Args) -> Result<(), String> {
+  let Some(fen) = args.positional.first() else {
+    return Err("a position is needed".to_string());
+  };
+
+  let mut board = parse_position(fen)?;
+  if board.legal_moves().is_empty() {
+    println!("- Game status: {:?}", board.game_status());
+    return Ok(());
+  }
+
+  // The moves after the position are the only ones searched
+  let search_moves = args
+    .positional
+    .iter()
+    .skip(1)
+    .map(|move_fen| board.find_move(move_fen).ok_or_else(|| format!("invalid move '{move_fen}'")))
+    .collect::<Result<Vec<_>, _>>()?;
+
+  let time = args.option("movetime")?;
+  let depth = args.option("depth")?;
+  let limits = SearchLimits {
+    time,
+    depth: depth.or(time.is_none().then_some(DEFAULT_SEARCH_DEPTH)),
+    multi_pv: args.option("multipv")?.unwrap_or(1),
+    root_moves: RootMoves::only(search_moves),
+    ..Default::default()
+  };
+  let side_to_move = board.side_to_move;
+
+  let (_, best_move) = iterative_deepening(&mut board, &limits, &mut TranspositionTable::new(), &mut |info| {
+    println!("{}", info.to_log(&side_to_move));
+  });
+  println!("- Best move: {}", best_move.to_fen());
+
+  Ok(())
+}
+
+fn mate(fen: &str, moves: usize, checks_only: bool) -> Result<(), String> {
+  let board = if fen == "startpos" {
+    bitboard::Board::default()
+  } else {
+    bitboard::Board::try_from_fen(fen)?
+  };
+
+  let start = std::time::Instant::now();
+  let mut solver = MateSolver::new(checks_only);
+  let result = solver.solve(&board, moves);
+  let stats = format!("{} nodes, {} ms", solver.nodes, start.elapsed().as_millis());
+
+  match result {
+    MateResult::Mate(line) => {
+      let moves: Vec<String> = line.iter().map(|chess_move| chess_move.to_fen()).collect();
+      println!("- Mate in {}: {} ({stats})", mate::mate_length(&line), moves.join(" "));
+    }
+    MateResult::NoMate if checks_only => println!("- No mate in {moves} with checks only ({stats})"),
+    MateResult::NoMate | MateResult::Unknown => println!("- No mate in {moves} ({stats})"),
+  }
+
+  Ok(())
+}
+
+fn epd_test(args: &Args) -> Result<(), String> {
+  let Some(path) = args.positional.first() else {
+    return Err("an EPD file is needed".to_string());
+  };
+  let contents = std::fs::read_to_string(path).map_err(|error| format!("couldn't read '{path}': {error}"))?;
+  let positions = epd_test::parse(&contents)?;
+
+  let depth = args.option("depth")?;
+  let nodes = args.option("nodes")?;
+  let time = args
+    .option("movetime")?
+    .or((depth.is_none() && nodes.is_none()).then_some(epd_test::DEFAULT_MOVETIME));
+  let limits = SearchLimits {
+    time,
+    depth,
+    nodes,
+    ..Default::default()
+  };
+
+  epd_test::run(&positions, &limits);
+  Ok(())
+}
+
+fn engine_match(args: &Args) -> Result<(), String> {
+  let mut engines = [
+    Engine::new(&args.option::<String>("engine1")?.unwrap_or("self".to_string()))?,
+    Engine::new(&args.option::<String>("engine2")?.unwrap_or("self".to_string()))?,
+  ];
+  let openings = engine_match::load_openings(args.option::<String>("openings")?.as_deref())?;
+
+  let depth = args.option("depth")?;
+  let nodes = args.option("nodes")?;
+  let time = args
+    .option("movetime")?
+    .or((depth.is_none() && nodes.is_none()).then_some(engine_match::DEFAULT_MOVETIME));
+
+  // Any of the SPRT options turns it on, the others keep their defaults
+  let defaults = Sprt::default();
+  let sprt_options = ["elo0", "elo1", "alpha", "beta"];
+  let sprt = (args.flag("sprt") || args.options.iter().any(|(name, _)| sprt_options.contains(&name.as_str()))).then_some(Sprt {
+    elo0: args.option("elo0")?.unwrap_or(defaults.elo0),
+    elo1: args.option("elo1")?.unwrap_or(defaults.elo1),
+    alpha: args.option("alpha")?.unwrap_or(defaults.alpha),
+    beta: args.option("beta")?.unwrap_or(defaults.beta),
+  });
+  if let Some(sprt) = &sprt {
+    if sprt.elo0 >= sprt.elo1 || !(0. ..0.5).contains(&sprt.alpha) || !(0. ..0.5).contains(&sprt.beta) {
+      return Err("the SPRT needs elo0 < elo1 and alpha and beta between 0 and 0.5".to_string());
+    }
+  }
+
+  let settings = MatchSettings {
+    games: args.option("games")?.unwrap_or(engine_match::DEFAULT_GAMES),
+    limits: SearchLimits {
+      time,
+      depth,
+      nodes,
+      ..Default::default()
+    },
+    adjudication: Adjudication {
+      mate_moves: args.option("adjudicate-mate")?.unwrap_or(0),
+      ..Default::default()
+    },
+    sprt,
+    pgn: args.option("pgn")?,
+  };
+
+  println!(
+    "- {} vs {}, {} openings",
+    engines[0].name(),
+    engines[1].name(),
+    openings.len()
+  );
+  let score = engine_match::run(&mut engines, &openings, &settings)?;
+  let (elo, margin) = score.elo();
+  println!(
+    "- {} games: +{} ={} -{}, Elo {elo:.1} ± {margin:.1}",
+    score.games(),
+    score.wins,
+    score.draws,
+    score.losses
+  );
+
+  Ok(())
+}
+
+fn selfplay(args: &Args) -> Result<(), String> {
+  let mut board = match args.positional.first() {
+    Some(fen) => parse_position(fen)?,
+    None => Board::default(),
+  };
+
+  let depth = args.option("depth")?;
+  let time = args
+    .option("movetime")?
+    .or(depth.is_none().then_some(DEFAULT_SELFPLAY_MOVETIME));
+  let limits = SearchLimits {
+    time,
+    depth,
+    ..Default::default()
+  };
+  let max_moves = args.option("max-moves")?.unwrap_or(DEFAULT_SELFPLAY_MAX_MOVES);
+
+  let mut tt = TranspositionTable::new();
+  let mut moves = 0;
+
+  let status = loop {
+    let status = board.game_status();
+    if status != GameStatus::Ongoing || moves >= max_moves * 2 {
+      break status;
+    }
+
+    let (score, best_move) = iterative_deepening(&mut board, &limits, &mut tt, &mut |_| {});
+
+    // From white's point of view, like the rest of the logs
+    let (eval, dots) = if board.side_to_move == Color::White {
+      (score, ".")
+    } else {
+      (-score, "...")
+    };
+    println!(
+      "{}{dots} {} ({})",
+      board.fullmove_counter,
+      best_move.to_fen(),
+      format_score(eval)
+    );
+
+    board.make_move(&best_move);
+    moves += 1;
+  };
+
+  match status {
+    GameStatus::Ongoing => println!("- Stopped after {max_moves} moves"),
+    GameStatus::Checkmate if board.side_to_move == Color::White => println!("- Black wins by checkmate"),
+    GameStatus::Checkmate => println!("- White wins by checkmate"),
+    status => println!("- Draw: {status:?}"),
+  }
+
+  Ok(())
+}
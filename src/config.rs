@@ -0,0 +1,204 @@
+use crate::{log::LogLevel, syzygy::MAX_PIECES};
+use figment::{
+  providers::{Env, Format, Serialized, Toml},
+  value::Value,
+  Figment,
+};
+use serde::{Deserialize, Serialize};
+use std::{path::Path, sync::OnceLock};
+
+const DEFAULT_FILE: &str = "config.toml";
+const ENV_PREFIX: &str = "RUSTY_CHESS_";
+const MAX_HASH: usize = 64 * 1024;
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Config {
+  // Picks the interface port when none is given
+  pub white_side: bool,
+  pub host: String,
+  // Every mode has its own default
+  pub port: Option<u16>,
+  // TT size in MB
+  pub hash: usize,
+  // Search threads, the search is single threaded for now
+  pub threads: usize,
+  pub book: BookConfig,
+  pub time: TimeConfig,
+  pub syzygy: SyzygyConfig,
+  pub http: HttpConfig,
+  pub log_level: LogLevel,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct BookConfig {
+  pub enabled: bool,
+  pub path: String,
+  // Games with a weaker player are left out when building it
+  pub min_elo: i32,
+}
+
+// Tablebases aren't used while the path is empty
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct SyzygyConfig {
+  pub path: String,
+  // Positions with more pieces (kings included) aren't probed
+  pub probe_limit: usize,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct HttpConfig {
+  // In ms, the longest an analysis can take, even one with only a depth
+  pub max_movetime: u64,
+}
+
+// All in ms, see time_for_move
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct TimeConfig {
+  pub move_overhead: u64,
+  pub min_move_time: u64,
+  pub max_move_time: u64,
+}
+
+impl Default for Config {
+  fn default() -> Config {
+    Config {
+      white_side: true,
+      host: "127.0.0.1".to_string(),
+      port: None,
+      hash: 64,
+      threads: 1,
+      book: BookConfig {
+        enabled: true,
+        path: "db.txt".to_string(),
+        min_elo: 2600,
+      },
+      time: TimeConfig {
+        move_overhead: 0,
+        min_move_time: 1000,
+        max_move_time: 12 * 1000,
+      },
+      syzygy: SyzygyConfig {
+        path: String::new(),
+        probe_limit: MAX_PIECES,
+      },
+      http: HttpConfig { max_movetime: 60 * 1000 },
+      log_level: LogLevel::Info,
+    }
+  }
+}
+
+// Every key can also be set with a flag, e.g. --book.path or --log-level
+const KEYS: [&str; 15] = [
+  "white_side",
+  "host",
+  "port",
+  "hash",
+  "threads",
+  "book.enabled",
+  "book.path",
+  "book.min_elo",
+  "time.move_overhead",
+  "time.min_move_time",
+  "time.max_move_time",
+  "syzygy.path",
+  "syzygy.probe_limit",
+  "http.max_movetime",
+  "log_level",
+];
+
+// For the help
+pub fn flags() -> Vec<String> {
+  KEYS.iter().map(|key| format!("--{}", key.replace('_', "-"))).collect()
+}
+
+impl Config {
+  // Merges, from lowest to highest priority: the defaults, the TOML file (config.toml or --config <path>),
+  // RUSTY_CHESS_* environment variables (RUSTY_CHESS_BOOK__PATH for book.path) and the flags.
+  // Returns the args which aren't config flags
+  pub fn load(args: &[String]) -> Result<(Config, Vec<String>), String> {
+    let mut file = DEFAULT_FILE.to_string();
+    let mut flags = vec![];
+    let mut rest = vec![];
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+      let Some(flag) = arg.strip_prefix("--") else {
+        rest.push(arg.clone());
+        continue;
+      };
+
+      let (name, value) = match flag.split_once('=') {
+        Some((name, value)) => (name.to_string(), Some(value.to_string())),
+        None => (flag.to_string(), None),
+      };
+      let key = name.replace('-', "_");
+
+      if key != "config" && !KEYS.contains(&key.as_str()) {
+        rest.push(arg.clone());
+        continue;
+      }
+
+      let Some(value) = value.or_else(|| args.next().cloned()) else {
+        return Err(format!("--{name} needs a value"));
+      };
+
+      if key == "config" {
+        if !Path::new(&value).exists() {
+          return Err(format!("config file '{value}' not found"));
+        }
+        file = value;
+      } else {
+        flags.push((key, value));
+      }
+    }
+
+    let mut figment = Figment::from(Serialized::defaults(Config::default()))
+      .merge(Toml::file(&file))
+      .merge(Env::prefixed(ENV_PREFIX).split("__"));
+
+    for (key, value) in flags {
+      // Parsed like environment variables, so that numbers and booleans aren't strings
+      figment = figment.merge(Serialized::default(&key, value.parse::<Value>().expect("Parsing can't fail")));
+    }
+
+    let config: Config = figment.extract().map_err(|error| error.to_string())?;
+    config.validate()?;
+
+    Ok((config, rest))
+  }
+
+  fn validate(&self) -> Result<(), String> {
+    if !(1..=MAX_HASH).contains(&self.hash) {
+      return Err(format!("hash has to be between 1 and {MAX_HASH} MB"));
+    }
+    if self.threads != 1 {
+      return Err("only 1 search thread is supported".to_string());
+    }
+    if self.time.min_move_time > self.time.max_move_time {
+      return Err("time.min_move_time can't be more than time.max_move_time".to_string());
+    }
+    if self.syzygy.probe_limit > MAX_PIECES {
+      return Err(format!("syzygy.probe_limit can't be more than {MAX_PIECES}"));
+    }
+    if self.http.max_movetime == 0 {
+      return Err("http.max_movetime can't be 0".to_string());
+    }
+    if self.book.enabled && self.book.path.is_empty() {
+      return Err("book.path can't be empty when the book is enabled".to_string());
+    }
+
+    Ok(())
+  }
+}
+
+static CONFIG: OnceLock<Config> = OnceLock::new();
+
+// Can only be set once, before anything reads it
+pub fn init(config: Config) {
+  CONFIG.set(config).expect("Config was already set");
+}
+
+// The defaults if it wasn't set, like in tests
+pub fn get() -> &'static Config {
+  CONFIG.get_or_init(Config::default)
+}
@@ -0,0 +1,314 @@
+use crate::{bitboard, board, perft};
+use hashbrown::HashMap;
+use rand::{rngs::StdRng, seq::SliceRandom, SeedableRng};
+use shakmaty::{
+  fen::Fen,
+  uci::Uci,
+  zobrist::{Zobrist64, ZobristHash},
+  CastlingMode, Chess, EnPassantMode, Position,
+};
+use std::fmt;
+
+pub const DEFAULT_GAMES: usize = 1000;
+pub const DEFAULT_MAX_PLIES: usize = 300;
+
+// The first position where the boards don't agree, and the shortest way found to get there again:
+// a fen and the moves to play from it, often none
+#[derive(Debug)]
+pub struct Divergence {
+  pub fen: String,
+  pub moves: Vec<String>,
+  pub difference: String,
+}
+
+impl fmt::Display for Divergence {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "{}\n  reproduce with: position fen {}", self.difference, self.fen)?;
+    if !self.moves.is_empty() {
+      write!(f, " moves {}", self.moves.join(" "))?;
+    }
+    Ok(())
+  }
+}
+
+// The hashes which were given to the positions of a game, by their shakmaty hash. Both boards use different keys,
+// but they have to give the same hash to the same positions, and only to them
+#[derive(Default)]
+struct SeenHashes {
+  by_reference: HashMap<Zobrist64, (u64, u64)>,
+  board: HashMap<u64, Zobrist64>,
+  bitboard: HashMap<u64, Zobrist64>,
+}
+
+impl SeenHashes {
+  fn check(&mut self, reference: Zobrist64, board: u64, bitboard: u64) -> Result<(), String> {
+    let (seen_board, seen_bitboard) = *self.by_reference.entry(reference).or_insert((board, bitboard));
+    let seen_reference = (
+      *self.board.entry(board).or_insert(reference),
+      *self.bitboard.entry(bitboard).or_insert(reference),
+    );
+
+    for (name, seen, hash, seen_reference) in [
+      ("board", seen_board, board, seen_reference.0),
+      ("bitboard", seen_bitboard, bitboard, seen_reference.1),
+    ] {
+      if seen != hash {
+        return Err(format!(
+          "{name} hash {hash} instead of {seen} for a position which was already seen"
+        ));
+      }
+      if seen_reference != reference {
+        return Err(format!("{name} hash {hash} was already given to another position"));
+      }
+    }
+
+    Ok(())
+  }
+}
+
+// The same position on both boards, and on shakmaty which is the reference
+struct Boards {
+  board: board::Board,
+  bitboard: bitboard::Board,
+  reference: Chess,
+  seen: SeenHashes,
+}
+
+fn sorted(moves: impl Iterator<Item = String>) -> Vec<String> {
+  let mut moves: Vec<String> = moves.collect();
+  moves.sort();
+  moves
+}
+
+impl Boards {
+  fn new(fen: &str) -> Boards {
+    Boards {
+      board: board::Board::from_fen(fen),
+      bitboard: bitboard::Board::from_fen(fen),
+      reference: fen
+        .parse::<Fen>()
+        .ok()
+        .and_then(|fen| fen.into_position(CastlingMode::Standard).ok())
+        .expect("The start positions are legal"),
+      seen: SeenHashes::default(),
+    }
+  }
+
+  fn fen(&self) -> String {
+    Fen::from_position(self.reference.clone(), EnPassantMode::Always).to_string()
+  }
+
+  fn legal_moves(&self) -> Vec<String> {
+    sorted(
+      self
+        .reference
+        .legal_moves()
+        .iter()
+        .map(|chess_move| chess_move.to_uci(CastlingMode::Standard).to_string()),
+    )
+  }
+
+  // The moves come from the reference, so the boards have them after they were compared
+  fn play(&mut self, move_fen: &str) {
+    let board_move = self.board.find_move(move_fen).expect("The legal moves were compared");
+    self.board.make_move(&board_move);
+
+    let bitboard_move = self
+      .bitboard
+      .legal_moves()
+      .into_iter()
+      .find(|chess_move| chess_move.to_fen() == move_fen)
+      .expect("The legal moves were compared");
+    self.bitboard.make_move(&bitboard_move);
+
+    let reference_move = move_fen
+      .parse::<Uci>()
+      .ok()
+      .and_then(|uci| uci.to_move(&self.reference).ok())
+      .expect("The move is a legal one of the reference");
+    self.reference.play_unchecked(&reference_move);
+  }
+
+  // The first difference, in the order: fen, legal moves, check, evaluation, hashes
+  fn compare(&mut self) -> Result<(), String> {
+    let fen = self.fen();
+    for (name, other) in [("board", self.board.to_fen()), ("bitboard", self.bitboard.to_fen())] {
+      if other != fen {
+        return Err(format!("{name} fen '{other}'"));
+      }
+    }
+
+    let expected = self.legal_moves();
+    let board_moves = sorted(self.board.legal_moves().iter().map(|chess_move| chess_move.to_fen()));
+    let bitboard_moves = sorted(self.bitboard.legal_moves().iter().map(|chess_move| chess_move.to_fen()));
+    for (name, moves) in [("board", board_moves), ("bitboard", bitboard_moves)] {
+      if moves != expected {
+        let only_in = |a: &[String], b: &[String]| -> String {
+          a.iter()
+            .filter(|chess_move| !b.contains(chess_move))
+            .cloned()
+            .collect::<Vec<String>>()
+            .join(" ")
+        };
+        return Err(format!(
+          "{name} legal moves are missing [{}] and have extra [{}]",
+          only_in(&expected, &moves),
+          only_in(&moves, &expected)
+        ));
+      }
+    }
+
+    let in_check = self.reference.is_check();
+    let board_in_check = self.board.in_check(&self.board.side_to_move);
+    let bitboard_in_check = self.bitboard.in_check(self.bitboard.side_to_move);
+    for (name, other) in [("board", board_in_check), ("bitboard", bitboard_in_check)] {
+      if other != in_check {
+        return Err(format!("{name} in_check is {other}"));
+      }
+    }
+
+    let (eval, eval_bb) = (self.board.evaluate_relative(), self.bitboard.evaluate_relative());
+    if eval != eval_bb {
+      return Err(format!("board evaluates {eval} and bitboard {eval_bb}"));
+    }
+
+    // The hashes are updated move by move, they have to be the ones of the fen
+    let scratch = (
+      board::Board::from_fen(&fen).meta.hash,
+      bitboard::Board::from_fen(&fen).meta.hash,
+    );
+    for (name, hash, expected) in [
+      ("board", self.board.meta.hash, scratch.0),
+      ("bitboard", self.bitboard.meta.hash, scratch.1),
+    ] {
+      if hash != expected {
+        return Err(format!("{name} hash {hash} instead of {expected}, the hash of the fen"));
+      }
+    }
+
+    // The en passant square is only in the hashes when a capture is legal
+    let reference = self.reference.zobrist_hash(EnPassantMode::Legal);
+    self.seen.check(reference, self.board.meta.hash, self.bitboard.meta.hash)
+  }
+}
+
+// Plays the moves from the fen and compares the boards after every one of them
+fn replay(fen: &str, moves: &[String]) -> Result<(), String> {
+  let mut boards = Boards::new(fen);
+  boards.compare()?;
+
+  for move_fen in moves {
+    boards.play(move_fen);
+    boards.compare()?;
+  }
+
+  Ok(())
+}
+
+// The game from the start fen went wrong after the moves. Looks for the latest position of the game
+// from which replaying the rest of the moves still goes wrong
+fn minimize(start: &str, moves: &[String], difference: String) -> Divergence {
+  let mut boards = Boards::new(start);
+  let mut fens = vec![boards.fen()];
+  for move_fen in moves {
+    boards.play(move_fen);
+    fens.push(boards.fen());
+  }
+
+  for i in (0..=moves.len()).rev() {
+    if let Err(difference) = replay(&fens[i], &moves[i..]) {
+      return Divergence {
+        fen: fens[i].clone(),
+        moves: moves[i..].to_vec(),
+        difference,
+      };
+    }
+  }
+
+  Divergence {
+    fen: start.to_string(),
+    moves: moves.to_vec(),
+    difference,
+  }
+}
+
+// Plays random games, the same ones for the same seed, half of them from the start position and the others from the
+// perft suite positions. Games end without legal moves, at the fifty move rule or after max_plies.
+// Returns the number of compared positions
+pub fn run(games: usize, seed: u64, max_plies: usize) -> Result<usize, Divergence> {
+  let mut rng = StdRng::seed_from_u64(seed);
+  let suite = perft::parse_suite(perft::SUITE).expect("The bundled suite is valid");
+  let mut compared = 0;
+
+  for game in 0..games {
+    let start = if game % 2 == 0 {
+      board::Board::default().to_fen()
+    } else {
+      suite.choose(&mut rng).expect("The suite isn't empty").fen.clone()
+    };
+    let mut boards = Boards::new(&start);
+    let mut moves = vec![];
+
+    for _ in 0..=max_plies {
+      if let Err(difference) = boards.compare() {
+        return Err(minimize(&start, &moves, difference));
+      }
+      compared += 1;
+
+      let legal_moves = boards.legal_moves();
+      let Some(move_fen) = legal_moves.choose(&mut rng) else {
+        break;
+      };
+      if boards.reference.halfmoves() >= 100 || moves.len() == max_plies {
+        break;
+      }
+
+      boards.play(move_fen);
+      moves.push(move_fen.clone());
+    }
+  }
+
+  Ok(compared)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn seen_hashes_test() {
+    let (a, b) = (Zobrist64(1), Zobrist64(2));
+
+    let mut seen = SeenHashes::default();
+    assert!(seen.check(a, 10, 20).is_ok());
+    assert!(seen.check(b, 11, 21).is_ok());
+    assert!(seen.check(a, 10, 20).is_ok());
+    assert_eq!(
+      seen.check(a, 12, 20).unwrap_err(),
+      "board hash 12 instead of 10 for a position which was already seen"
+    );
+
+    let mut seen = SeenHashes::default();
+    assert!(seen.check(a, 10, 20).is_ok());
+    assert_eq!(
+      seen.check(b, 11, 20).unwrap_err(),
+      "bitboard hash 20 was already given to another position"
+    );
+  }
+
+  #[test]
+  fn divergence_test() {
+    let fen = "4k3/8/8/8/8/8/8/4K3 w - - 0 1";
+    assert!(replay(fen, &["e1d1".to_string(), "e8d8".to_string()]).is_ok());
+
+    let divergence = Divergence {
+      fen: fen.to_string(),
+      moves: vec!["e1d1".to_string()],
+      difference: "board in_check is true".to_string(),
+    };
+    assert_eq!(
+      divergence.to_string(),
+      "board in_check is true\n  reproduce with: position fen 4k3/8/8/8/8/8/8/4K3 w - - 0 1 moves e1d1"
+    );
+  }
+}
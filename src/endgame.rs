@@ -0,0 +1,180 @@
+use crate::bitboard::{material_key, Bitboard, Color, File, Piece, PieceType, Rank, Square};
+use crate::kpk;
+use hashbrown::HashMap;
+use lazy_static::lazy_static;
+
+// A won endgame is worth this much more than its material, so the search goes for it
+const KNOWN_WIN: i32 = 10000;
+
+// To get a specific bitboard: `pieces[color][piece type]`, like in bitboard::Board
+pub type Pieces = [[Bitboard; 6]; 2];
+
+// Endgames the general evaluation gets wrong
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Endgame {
+  // King and pawn against king, looked up in the KPK bitbase
+  Kpk,
+  // King and rook or queen against king: the lone king has to be driven to the edge and the kings brought together
+  Kxk,
+  // King, bishop and knight against king: the lone king has to be driven to a corner of the bishop's color
+  Kbnk,
+  // King, bishop and pawn against king: a rook pawn doesn't win when the bishop can't cover the promotion square
+  // and the lone king gets in front of it
+  Kbpk,
+  // No checkmate can be forced, like with two knights
+  Draw,
+}
+
+lazy_static! {
+  // The endgames by their material key, with the stronger side
+  static ref ENDGAMES: HashMap<u64, (Endgame, Color)> = {
+    use PieceType::*;
+
+    let mut endgames = HashMap::new();
+    for strong in Color::ALL {
+      let mut add = |endgame, strong_pieces: &[PieceType]| {
+        let pieces: Vec<Piece> = strong_pieces
+          .iter()
+          .map(|piece_type| Piece::new(strong, *piece_type))
+          .chain([Piece::new(strong, King), Piece::new(!strong, King)])
+          .collect();
+        endgames.insert(material_key(&pieces), (endgame, strong));
+      };
+
+      add(Endgame::Kpk, &[Pawn]);
+      add(Endgame::Kxk, &[Rook]);
+      add(Endgame::Kxk, &[Queen]);
+      add(Endgame::Kbnk, &[Bishop, Knight]);
+      add(Endgame::Kbpk, &[Bishop, Pawn]);
+      add(Endgame::Draw, &[Knight]);
+      add(Endgame::Draw, &[Bishop]);
+      add(Endgame::Draw, &[Knight, Knight]);
+    }
+    endgames
+  };
+}
+
+fn piece(pieces: &Pieces, color: Color, piece_type: PieceType) -> Square {
+  pieces[color.index()][piece_type.index()].lsb()
+}
+
+fn is_light(square: Square) -> bool {
+  (square.file().index() + square.rank().index()) % 2 == 1
+}
+
+// Mirrored so that white is the side looking up the board
+fn relative(square: Square, color: Color) -> Square {
+  match color {
+    Color::White => square,
+    Color::Black => Square::new(
+      square.file(),
+      Rank::new(7 - square.rank().index() as u8).expect("Ranks are below 8"),
+    ),
+  }
+}
+
+// Bigger the closer the square is to an edge, and the most in the corners
+fn push_to_edge(square: Square) -> i32 {
+  let file = square.file().index().min(7 - square.file().index()) as i32;
+  let rank = square.rank().index().min(7 - square.rank().index()) as i32;
+  30 * (3 - file.min(rank)) + 10 * (6 - file - rank)
+}
+
+// Bigger the closer the squares are
+fn push_close(a: Square, b: Square) -> i32 {
+  20 * (7 - a.distance(b) as i32)
+}
+
+fn material(pieces: &Pieces, color: Color) -> i32 {
+  PieceType::ALL
+    .into_iter()
+    .map(|piece_type| piece_type.value() * pieces[color.index()][piece_type.index()].count() as i32)
+    .sum()
+}
+
+impl Endgame {
+  // From the stronger side's perspective, eval is the general evaluation from it too
+  fn evaluate(self, pieces: &Pieces, strong: Color, side_to_move: Color, eval: i32) -> i32 {
+    let weak = !strong;
+    let strong_king = piece(pieces, strong, PieceType::King);
+    let weak_king = piece(pieces, weak, PieceType::King);
+
+    match self {
+      Endgame::Kpk => {
+        // The bitbase has white's pawn on the files a to d
+        let mut pawn = relative(piece(pieces, strong, PieceType::Pawn), strong);
+        let mut kings = (relative(strong_king, strong), relative(weak_king, strong));
+        if pawn.file() > File::D {
+          let mirror = |square: Square| Square::from_index(square.index() ^ 7).expect("Squares are below 64");
+          pawn = mirror(pawn);
+          kings = (mirror(kings.0), mirror(kings.1));
+        }
+        let side_to_move = if side_to_move == strong { Color::White } else { Color::Black };
+
+        if kpk::probe(side_to_move, kings.0, kings.1, pawn) {
+          KNOWN_WIN + PieceType::Pawn.value() + 10 * pawn.rank().index() as i32
+        } else {
+          0
+        }
+      }
+      Endgame::Kxk => KNOWN_WIN + material(pieces, strong) + push_to_edge(weak_king) + push_close(strong_king, weak_king),
+      Endgame::Kbnk => {
+        let corners = if is_light(piece(pieces, strong, PieceType::Bishop)) {
+          [Square::A8, Square::H1]
+        } else {
+          [Square::A1, Square::H8]
+        };
+        let corner_distance = corners.iter().map(|corner| weak_king.distance(*corner)).min().unwrap_or(0) as i32;
+
+        KNOWN_WIN + material(pieces, strong) + 40 * (7 - corner_distance) + push_close(strong_king, weak_king)
+      }
+      Endgame::Kbpk => {
+        let pawn = piece(pieces, strong, PieceType::Pawn);
+        let promotion = relative(Square::new(pawn.file(), Rank::R8), strong);
+        let is_rook_pawn = pawn.file() == File::A || pawn.file() == File::H;
+        let wrong_bishop = is_light(piece(pieces, strong, PieceType::Bishop)) != is_light(promotion);
+
+        if is_rook_pawn && wrong_bishop && weak_king.distance(promotion) <= 1 {
+          0
+        } else {
+          eval
+        }
+      }
+      Endgame::Draw => 0,
+    }
+  }
+}
+
+// Bishops of different colors and only pawns otherwise are very drawish, even a few pawns up
+fn opposite_bishops(pieces: &Pieces) -> bool {
+  let count = |color: Color, piece_type: PieceType| pieces[color.index()][piece_type.index()].count();
+
+  Color::ALL.into_iter().all(|color| {
+    count(color, PieceType::Bishop) == 1
+      && count(color, PieceType::Knight) == 0
+      && count(color, PieceType::Rook) == 0
+      && count(color, PieceType::Queen) == 0
+  }) && is_light(piece(pieces, Color::White, PieceType::Bishop)) != is_light(piece(pieces, Color::Black, PieceType::Bishop))
+}
+
+// Whether the material key belongs to an endgame with its own evaluation
+pub fn is_known(material_key: u64) -> bool {
+  ENDGAMES.contains_key(&material_key)
+}
+
+// Corrects the general evaluation (from white's perspective) of positions it gets wrong.
+// Known endgames are found by the material key, the others can only get scaled down
+pub fn evaluate(material_key: u64, pieces: &Pieces, side_to_move: Color, eval: i32) -> i32 {
+  if let Some((endgame, strong)) = ENDGAMES.get(&material_key) {
+    return match strong {
+      Color::White => endgame.evaluate(pieces, *strong, side_to_move, eval),
+      Color::Black => -endgame.evaluate(pieces, *strong, side_to_move, -eval),
+    };
+  }
+
+  if opposite_bishops(pieces) {
+    eval / 2
+  } else {
+    eval
+  }
+}
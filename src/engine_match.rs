@@ -0,0 +1,699 @@
+use crate::bitboard::Color;
+use crate::board::{Board, GameStatus};
+use crate::mate::{self, MateResult, MateSolver};
+use crate::search::{SearchLimits, Searcher, CHECKMATE};
+use crate::{bench, bitboard, config, epd};
+use pgn_reader::{BufferedReader, RawHeader, SanPlus, Skip, Visitor};
+use std::{
+  fs::File,
+  io::{BufRead, BufReader, BufWriter, Write},
+  process::{Child, ChildStdin, ChildStdout, Command, Stdio},
+  thread,
+  time::Duration,
+};
+
+pub const DEFAULT_GAMES: usize = 100;
+pub const DEFAULT_MOVETIME: u128 = 100;
+// The most nodes of a mate search for the adjudication
+const MATE_NODES: u64 = 100_000;
+const START_FEN: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+
+// The final position of every game, variations are skipped
+struct PgnOpenings {
+  fen: String,
+  sans: Vec<String>,
+}
+
+impl Visitor for PgnOpenings {
+  type Result = Result<String, String>;
+
+  fn begin_game(&mut self) {
+    self.fen = START_FEN.to_string();
+    self.sans.clear();
+  }
+
+  fn header(&mut self, key: &[u8], value: RawHeader<'_>) {
+    if key == b"FEN" {
+      self.fen = value.decode_utf8_lossy().to_string();
+    }
+  }
+
+  fn san(&mut self, san_plus: SanPlus) {
+    self.sans.push(san_plus.to_string());
+  }
+
+  fn begin_variation(&mut self) -> Skip {
+    Skip(true)
+  }
+
+  fn end_game(&mut self) -> Self::Result {
+    let mut board = Board::try_from_fen(&self.fen)?;
+    for san in &self.sans {
+      let chess_move = board
+        .parse_san(san)
+        .ok_or(format!("illegal move '{san}' in '{}'", self.fen))?;
+      board.make_move(&chess_move);
+    }
+
+    Ok(board.to_fen())
+  }
+}
+
+// The positions the games start from: an EPD file, the positions at the end of the games of a PGN file,
+// or the bench positions without a file
+pub fn load_openings(path: Option<&str>) -> Result<Vec<String>, String> {
+  let Some(path) = path else {
+    return Ok(bench::POSITIONS.iter().map(|fen| fen.to_string()).collect());
+  };
+  let file = File::open(path).map_err(|error| format!("couldn't open '{path}': {error}"))?;
+
+  let openings = if path.ends_with(".pgn") {
+    let mut reader = BufferedReader::new(file);
+    let mut visitor = PgnOpenings {
+      fen: START_FEN.to_string(),
+      sans: vec![],
+    };
+
+    let mut openings = vec![];
+    while let Some(opening) = reader.read_game(&mut visitor).map_err(|error| error.to_string())? {
+      openings.push(opening?);
+    }
+    openings
+  } else {
+    let contents = std::io::read_to_string(file).map_err(|error| format!("couldn't read '{path}': {error}"))?;
+    epd::parse_file(&contents)?
+      .into_iter()
+      .map(|epd| Board::normalize_fen(&epd.fen))
+      .collect::<Result<Vec<String>, String>>()?
+  };
+
+  if openings.is_empty() {
+    return Err(format!("no openings in '{path}'"));
+  }
+  Ok(openings)
+}
+
+// A UCI engine in a subprocess, its stderr is dropped
+pub struct UciEngine {
+  name: String,
+  process: Child,
+  input: ChildStdin,
+  output: BufReader<ChildStdout>,
+}
+
+impl UciEngine {
+  // The command is split on spaces, e.g. "./rusty_chess uci"
+  pub fn start(command: &str) -> Result<UciEngine, String> {
+    let mut words = command.split_whitespace();
+    let program = words.next().ok_or("the engine command is empty")?;
+    let mut process = Command::new(program)
+      .args(words)
+      .stdin(Stdio::piped())
+      .stdout(Stdio::piped())
+      .stderr(Stdio::null())
+      .spawn()
+      .map_err(|error| format!("couldn't start '{command}': {error}"))?;
+
+    let mut engine = UciEngine {
+      name: program.to_string(),
+      input: process.stdin.take().expect("stdin is piped"),
+      output: BufReader::new(process.stdout.take().expect("stdout is piped")),
+      process,
+    };
+
+    engine.send("uci")?;
+    let mut name = None;
+    engine.read_until("uciok", |line| {
+      if let Some(id) = line.strip_prefix("id name ") {
+        name = Some(id.to_string());
+      }
+    })?;
+    if let Some(name) = name {
+      engine.name = name;
+    }
+    engine.send("isready")?;
+    engine.read_until("readyok", |_| {})?;
+
+    Ok(engine)
+  }
+
+  fn send(&mut self, command: &str) -> Result<(), String> {
+    writeln!(self.input, "{command}")
+      .and_then(|_| self.input.flush())
+      .map_err(|error| format!("couldn't write to {}: {error}", self.name))
+  }
+
+  // Calls on_line with every line before the one starting with the token, which is returned
+  fn read_until(&mut self, token: &str, mut on_line: impl FnMut(&str)) -> Result<String, String> {
+    let mut buffer = String::new();
+
+    loop {
+      buffer.clear();
+      let read = self
+        .output
+        .read_line(&mut buffer)
+        .map_err(|error| format!("couldn't read from {}: {error}", self.name))?;
+      if read == 0 {
+        return Err(format!("{} quit", self.name));
+      }
+
+      let line = buffer.trim();
+      if line.split_whitespace().next() == Some(token) {
+        return Ok(line.to_string());
+      }
+      on_line(line);
+    }
+  }
+
+  fn new_game(&mut self) -> Result<(), String> {
+    self.send("ucinewgame")?;
+    self.send("isready")?;
+    self.read_until("readyok", |_| {})?;
+    Ok(())
+  }
+
+  fn go(&mut self, fen: &str, moves: &[String], limits: &SearchLimits) -> Result<(String, Option<i32>), String> {
+    let mut position = format!("position fen {fen}");
+    if !moves.is_empty() {
+      position += &format!(" moves {}", moves.join(" "));
+    }
+    self.send(&position)?;
+
+    let mut go = "go".to_string();
+    if let Some(time) = limits.time {
+      go += &format!(" movetime {time}");
+    }
+    if let Some(depth) = limits.depth {
+      go += &format!(" depth {depth}");
+    }
+    if let Some(nodes) = limits.nodes {
+      go += &format!(" nodes {nodes}");
+    }
+    self.send(&go)?;
+
+    let mut score = None;
+    let best_move = self.read_until("bestmove", |line| {
+      if line.starts_with("info") {
+        score = parse_score(line).or(score);
+      }
+    })?;
+
+    match best_move.split_whitespace().nth(1) {
+      Some(chess_move) => Ok((chess_move.to_string(), score)),
+      None => Err(format!("{} sent '{best_move}'", self.name)),
+    }
+  }
+}
+
+// Gives the engine a moment to quit on its own
+impl Drop for UciEngine {
+  fn drop(&mut self) {
+    if self.send("quit").is_ok() {
+      for _ in 0..100 {
+        if let Ok(Some(_)) = self.process.try_wait() {
+          return;
+        }
+        thread::sleep(Duration::from_millis(10));
+      }
+    }
+    self.process.kill().ok();
+    self.process.wait().ok();
+  }
+}
+
+// The score of an info line, with mates like the search scores them
+fn parse_score(info: &str) -> Option<i32> {
+  let words: Vec<&str> = info.split_whitespace().collect();
+  let index = words.iter().position(|word| *word == "score")?;
+  let value: i32 = words.get(index + 2)?.parse().ok()?;
+
+  match *words.get(index + 1)? {
+    "cp" => Some(value),
+    "mate" if value > 0 => Some(CHECKMATE - 2 * value + 1),
+    "mate" => Some(-CHECKMATE - 2 * value),
+    _ => None,
+  }
+}
+
+// A player of the match
+pub enum Engine {
+  // This engine, searching in process
+  Internal(Searcher),
+  Uci(UciEngine),
+}
+
+impl Engine {
+  // "self" for this engine, with the configured hash, otherwise the command of a UCI engine
+  pub fn new(spec: &str) -> Result<Engine, String> {
+    if spec == "self" {
+      Ok(Engine::Internal(Searcher::with_hash_size(config::get().hash)))
+    } else {
+      UciEngine::start(spec).map(Engine::Uci)
+    }
+  }
+
+  pub fn name(&self) -> &str {
+    match self {
+      Engine::Internal(_) => "rusty_chess",
+      Engine::Uci(engine) => &engine.name,
+    }
+  }
+
+  fn new_game(&mut self) -> Result<(), String> {
+    match self {
+      Engine::Internal(searcher) => {
+        searcher.clear();
+        Ok(())
+      }
+      Engine::Uci(engine) => engine.new_game(),
+    }
+  }
+
+  // The move in UCI notation and the score for the side to move, if the engine gave one
+  fn play(&mut self, game: &Game, board: &Board, limits: &SearchLimits) -> Result<(String, Option<i32>), String> {
+    match self {
+      Engine::Internal(searcher) => {
+        let result = searcher.search(board, limits).ok_or("no legal moves to search")?;
+        Ok((result.best_move.to_fen(), Some(result.score)))
+      }
+      Engine::Uci(engine) => engine.go(&game.fen, &game.moves, limits),
+    }
+  }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GameResult {
+  WhiteWins,
+  BlackWins,
+  Draw,
+}
+
+impl GameResult {
+  pub fn to_pgn(self) -> &'static str {
+    match self {
+      GameResult::WhiteWins => "1-0",
+      GameResult::BlackWins => "0-1",
+      GameResult::Draw => "1/2-1/2",
+    }
+  }
+}
+
+pub struct Game {
+  pub white: String,
+  pub black: String,
+  pub fen: String,
+  // In UCI notation, as the engines get them
+  pub moves: Vec<String>,
+  sans: Vec<String>,
+  pub result: GameResult,
+  // Why the game ended, e.g. "checkmate" or "adjudication: black resigns"
+  pub termination: String,
+}
+
+impl Game {
+  pub fn to_pgn(&self, round: usize) -> String {
+    let mut pgn = format!(
+      "[Event \"rusty_chess match\"]\n[Site \"?\"]\n[Date \"????.??.??\"]\n[Round \"{round}\"]\n[White \"{}\"]\n\
+       [Black \"{}\"]\n[Result \"{}\"]\n",
+      self.white,
+      self.black,
+      self.result.to_pgn()
+    );
+    if self.fen != START_FEN {
+      pgn += &format!("[SetUp \"1\"]\n[FEN \"{}\"]\n", self.fen);
+    }
+    pgn += "\n";
+
+    let fields: Vec<&str> = self.fen.split(' ').collect();
+    let mut move_number: u32 = fields[5].parse().unwrap_or(1);
+    let mut white_to_move = fields[1] == "w";
+
+    let mut tokens = vec![];
+    for (i, san) in self.sans.iter().enumerate() {
+      if white_to_move {
+        tokens.push(format!("{move_number}."));
+      } else if i == 0 {
+        tokens.push(format!("{move_number}..."));
+      }
+      tokens.push(san.clone());
+
+      if !white_to_move {
+        move_number += 1;
+      }
+      white_to_move = !white_to_move;
+    }
+    tokens.push(format!("{{{}}}", self.termination));
+    tokens.push(self.result.to_pgn().to_string());
+
+    // Lines of at most 80 characters
+    let mut line = String::new();
+    for token in tokens {
+      if !line.is_empty() && line.len() + token.len() >= 80 {
+        pgn += &line;
+        pgn += "\n";
+        line.clear();
+      }
+      if !line.is_empty() {
+        line += " ";
+      }
+      line += &token;
+    }
+    pgn + &line + "\n\n"
+  }
+}
+
+// Scores are in centipawns, a move is a move of each side
+pub struct Adjudication {
+  // A side resigns when both engines gave it a score below -resign_score for resign_moves moves in a row
+  pub resign_score: i32,
+  pub resign_moves: usize,
+  // A draw from draw_move_number on, when both engines gave a score within draw_score of 0 for draw_moves moves
+  pub draw_score: i32,
+  pub draw_moves: usize,
+  pub draw_move_number: u32,
+  // Games which are still going after this many moves are draws
+  pub max_moves: usize,
+  // The side to move wins when it has a mate in at most this many moves with only checks, 0 turns it off
+  pub mate_moves: usize,
+}
+
+impl Default for Adjudication {
+  fn default() -> Self {
+    Self {
+      resign_score: 1000,
+      resign_moves: 3,
+      draw_score: 10,
+      draw_moves: 8,
+      draw_move_number: 40,
+      max_moves: 200,
+      mate_moves: 0,
+    }
+  }
+}
+
+impl Adjudication {
+  // The scores of the game so far from white's point of view, None where the engine didn't give one
+  fn adjudicate(&self, scores: &[Option<i32>], move_number: u32) -> Option<(GameResult, &'static str)> {
+    let last = |moves: usize| -> Option<Vec<i32>> {
+      if moves == 0 || scores.len() < moves * 2 {
+        return None;
+      }
+      scores[scores.len() - moves * 2..].iter().copied().collect()
+    };
+
+    if let Some(last) = last(self.resign_moves) {
+      if last.iter().all(|score| *score >= self.resign_score) {
+        return Some((GameResult::WhiteWins, "adjudication: black resigns"));
+      }
+      if last.iter().all(|score| *score <= -self.resign_score) {
+        return Some((GameResult::BlackWins, "adjudication: white resigns"));
+      }
+    }
+
+    match last(self.draw_moves) {
+      Some(last) if move_number >= self.draw_move_number && last.iter().all(|score| score.abs() <= self.draw_score) => {
+        Some((GameResult::Draw, "adjudication: draw"))
+      }
+      _ => None,
+    }
+  }
+
+  // Only checking moves are tried with a node limit, so it doesn't take longer than the engines' moves
+  fn forced_mate(&self, board: &Board) -> Option<(GameResult, String)> {
+    if self.mate_moves == 0 {
+      return None;
+    }
+
+    let mut solver = MateSolver::with_node_limit(true, MATE_NODES);
+    let MateResult::Mate(line) = solver.solve(&bitboard::Board::from_fen(&board.to_fen()), self.mate_moves) else {
+      return None;
+    };
+
+    let (result, winner) = if board.side_to_move == Color::White {
+      (GameResult::WhiteWins, "white")
+    } else {
+      (GameResult::BlackWins, "black")
+    };
+    Some((
+      result,
+      format!("adjudication: {winner} mates in {}", mate::mate_length(&line)),
+    ))
+  }
+}
+
+pub fn play_game(
+  white: &mut Engine,
+  black: &mut Engine,
+  fen: &str,
+  limits: &SearchLimits,
+  adjudication: &Adjudication,
+) -> Result<Game, String> {
+  white.new_game()?;
+  black.new_game()?;
+
+  let mut board = Board::try_from_fen(fen)?;
+  let mut game = Game {
+    white: white.name().to_string(),
+    black: black.name().to_string(),
+    fen: fen.to_string(),
+    moves: vec![],
+    sans: vec![],
+    result: GameResult::Draw,
+    termination: String::new(),
+  };
+  let mut scores = vec![];
+
+  let (result, termination) = loop {
+    match board.game_status() {
+      GameStatus::Ongoing => {}
+      GameStatus::Checkmate if board.side_to_move == Color::White => break (GameResult::BlackWins, "checkmate".to_string()),
+      GameStatus::Checkmate => break (GameResult::WhiteWins, "checkmate".to_string()),
+      GameStatus::Stalemate => break (GameResult::Draw, "stalemate".to_string()),
+      GameStatus::Draw(reason) => break (GameResult::Draw, format!("{reason:?}")),
+    }
+    if let Some((result, termination)) = adjudication.adjudicate(&scores, board.fullmove_counter) {
+      break (result, termination.to_string());
+    }
+    if let Some((result, termination)) = adjudication.forced_mate(&board) {
+      break (result, termination);
+    }
+    if game.moves.len() >= adjudication.max_moves * 2 {
+      break (GameResult::Draw, "adjudication: too long".to_string());
+    }
+
+    let white_to_move = board.side_to_move == Color::White;
+    let engine = if white_to_move { &mut *white } else { &mut *black };
+    let (move_fen, score) = engine.play(&game, &board, limits)?;
+
+    let Some(chess_move) = board.find_move(&move_fen) else {
+      let result = if white_to_move {
+        GameResult::BlackWins
+      } else {
+        GameResult::WhiteWins
+      };
+      break (result, format!("illegal move {move_fen}"));
+    };
+
+    scores.push(score.map(|score| if white_to_move { score } else { -score }));
+    game.sans.push(board.to_san(&chess_move));
+    game.moves.push(move_fen);
+    board.make_move(&chess_move);
+  };
+
+  game.result = result;
+  game.termination = termination;
+  Ok(game)
+}
+
+// Games of the first engine
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Score {
+  pub wins: u32,
+  pub draws: u32,
+  pub losses: u32,
+}
+
+fn elo_to_score(elo: f64) -> f64 {
+  1. / (1. + 10f64.powf(-elo / 400.))
+}
+
+fn score_to_elo(score: f64) -> f64 {
+  400. * (score / (1. - score)).log10()
+}
+
+impl Score {
+  pub fn games(&self) -> u32 {
+    self.wins + self.draws + self.losses
+  }
+
+  fn add(&mut self, result: GameResult, first_is_white: bool) {
+    match (result, first_is_white) {
+      (GameResult::Draw, _) => self.draws += 1,
+      (GameResult::WhiteWins, true) | (GameResult::BlackWins, false) => self.wins += 1,
+      _ => self.losses += 1,
+    }
+  }
+
+  // Points per game and their variance
+  fn mean_variance(&self) -> (f64, f64) {
+    let games = self.games().max(1) as f64;
+    let (wins, draws, losses) = (self.wins as f64, self.draws as f64, self.losses as f64);
+    let mean = (wins + draws / 2.) / games;
+    let variance = (wins * (1. - mean).powi(2) + draws * (0.5 - mean).powi(2) + losses * mean.powi(2)) / games;
+
+    (mean, variance)
+  }
+
+  // The Elo difference and the half width of its 95% confidence interval, infinite while a side has all the points
+  pub fn elo(&self) -> (f64, f64) {
+    let (mean, variance) = self.mean_variance();
+    let margin = 1.96 * (variance / self.games().max(1) as f64).sqrt();
+    let bound = |score: f64| score_to_elo(score.clamp(0., 1.));
+
+    (bound(mean), (bound(mean + margin) - bound(mean - margin)) / 2.)
+  }
+}
+
+// Sequential probability ratio test of the first engine being elo1 better rather than elo0, with the false positive
+// and false negative rates alpha and beta
+#[derive(Clone, Copy, Debug)]
+pub struct Sprt {
+  pub elo0: f64,
+  pub elo1: f64,
+  pub alpha: f64,
+  pub beta: f64,
+}
+
+impl Default for Sprt {
+  fn default() -> Self {
+    Self {
+      elo0: 0.,
+      elo1: 5.,
+      alpha: 0.05,
+      beta: 0.05,
+    }
+  }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SprtResult {
+  // elo1 is accepted
+  H1,
+  // elo0 is accepted
+  H0,
+  Continue,
+}
+
+impl Sprt {
+  // Log likelihood ratio, with the normal approximation of the game results which fishtest uses
+  pub fn llr(&self, score: &Score) -> f64 {
+    let (mean, variance) = score.mean_variance();
+    if score.games() == 0 || variance == 0. {
+      return 0.;
+    }
+
+    let (score0, score1) = (elo_to_score(self.elo0), elo_to_score(self.elo1));
+    (score1 - score0) * (2. * mean - score0 - score1) * score.games() as f64 / (2. * variance)
+  }
+
+  // The LLR bounds, below the first H0 is accepted and above the second H1
+  pub fn bounds(&self) -> (f64, f64) {
+    ((self.beta / (1. - self.alpha)).ln(), ((1. - self.beta) / self.alpha).ln())
+  }
+
+  pub fn result(&self, score: &Score) -> SprtResult {
+    let llr = self.llr(score);
+    let (lower, upper) = self.bounds();
+
+    if llr >= upper {
+      SprtResult::H1
+    } else if llr <= lower {
+      SprtResult::H0
+    } else {
+      SprtResult::Continue
+    }
+  }
+}
+
+pub struct MatchSettings {
+  pub games: usize,
+  pub limits: SearchLimits,
+  pub adjudication: Adjudication,
+  // Stops as soon as a hypothesis is accepted
+  pub sprt: Option<Sprt>,
+  // Where the games are written to
+  pub pgn: Option<String>,
+}
+
+// Every opening is played twice with swapped colours, in order, going around as long as games are left.
+// The score is the one of the first engine
+pub fn run(engines: &mut [Engine; 2], openings: &[String], settings: &MatchSettings) -> Result<Score, String> {
+  let mut pgn = match &settings.pgn {
+    Some(path) => Some(BufWriter::new(
+      File::create(path).map_err(|error| format!("couldn't create '{path}': {error}"))?,
+    )),
+    None => None,
+  };
+  let mut score = Score::default();
+  let [first, second] = engines;
+
+  for round in 0..settings.games {
+    let opening = &openings[(round / 2) % openings.len()];
+    let first_is_white = round % 2 == 0;
+    let game = if first_is_white {
+      play_game(first, second, opening, &settings.limits, &settings.adjudication)?
+    } else {
+      play_game(second, first, opening, &settings.limits, &settings.adjudication)?
+    };
+    score.add(game.result, first_is_white);
+
+    if let Some(pgn) = &mut pgn {
+      pgn
+        .write_all(game.to_pgn(round + 1).as_bytes())
+        .and_then(|_| pgn.flush())
+        .map_err(|error| format!("couldn't write the PGN: {error}"))?;
+    }
+
+    let (elo, margin) = score.elo();
+    let mut progress = format!(
+      "- Game {}/{}: {} - {} {} ({}) | +{} ={} -{} | Elo {elo:.1} ± {margin:.1}",
+      round + 1,
+      settings.games,
+      game.white,
+      game.black,
+      game.result.to_pgn(),
+      game.termination,
+      score.wins,
+      score.draws,
+      score.losses
+    );
+
+    if let Some(sprt) = &settings.sprt {
+      let (lower, upper) = sprt.bounds();
+      progress += &format!(" | LLR {:.2} ({lower:.2}, {upper:.2})", sprt.llr(&score));
+      println!("{progress}");
+
+      match sprt.result(&score) {
+        SprtResult::H1 => {
+          println!(
+            "- SPRT: H1 accepted, elo1 = {} is more likely than elo0 = {}",
+            sprt.elo1, sprt.elo0
+          );
+          break;
+        }
+        SprtResult::H0 => {
+          println!(
+            "- SPRT: H0 accepted, elo0 = {} is more likely than elo1 = {}",
+            sprt.elo0, sprt.elo1
+          );
+          break;
+        }
+        SprtResult::Continue => {}
+      }
+    } else {
+      println!("{progress}");
+    }
+  }
+
+  Ok(score)
+}
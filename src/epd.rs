@@ -0,0 +1,118 @@
+// https://www.chessprogramming.org/Extended_Position_Description
+// A position and its operations, e.g. `<fen> bm Nf3; id "test 1";`, or the perft suites' `<fen> ;D1 20 ;D2 400`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Epd {
+  // Always with the 6 fields, the halfmove clock and fullmove number default to 0 1
+  pub fen: String,
+  // Opcode and operand without the quotes, in file order
+  pub operations: Vec<(String, String)>,
+}
+
+impl Epd {
+  pub fn parse(line: &str) -> Result<Epd, String> {
+    let mut rest = line.trim();
+    let mut fields = vec![];
+
+    while fields.len() < 6 {
+      let (field, after) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+      // The clocks are optional, operations come right after the 4 fields then
+      if field.is_empty() || (fields.len() >= 4 && field.parse::<u32>().is_err()) {
+        break;
+      }
+      fields.push(field);
+      rest = after.trim_start();
+    }
+
+    match fields.len() {
+      4 => fields.extend(["0", "1"]),
+      6 => {}
+      5 => return Err("the fullmove number is missing".to_string()),
+      count => return Err(format!("expected at least 4 fields, got {count}")),
+    }
+
+    Ok(Epd {
+      fen: fields.join(" "),
+      operations: split_operations(rest)
+        .into_iter()
+        .map(|operation| {
+          let (opcode, operand) = operation.split_once(char::is_whitespace).unwrap_or((operation, ""));
+          (opcode.to_string(), operand.trim().trim_matches('"').to_string())
+        })
+        .collect(),
+    })
+  }
+
+  // The operand of the first operation with the opcode
+  pub fn operation(&self, opcode: &str) -> Option<&str> {
+    self
+      .operations
+      .iter()
+      .find(|(other, _)| other == opcode)
+      .map(|(_, operand)| operand.as_str())
+  }
+}
+
+// Operations end with ';', which can also be in a quoted operand
+fn split_operations(operations: &str) -> Vec<&str> {
+  let mut split = vec![];
+  let mut start = 0;
+  let mut quoted = false;
+
+  for (i, c) in operations.char_indices() {
+    match c {
+      '"' => quoted = !quoted,
+      ';' if !quoted => {
+        split.push(&operations[start..i]);
+        start = i + 1;
+      }
+      _ => {}
+    }
+  }
+  split.push(&operations[start..]);
+
+  split
+    .into_iter()
+    .map(str::trim)
+    .filter(|operation| !operation.is_empty())
+    .collect()
+}
+
+// Blank lines and lines starting with '#' are skipped, errors have the line number
+pub fn parse_file(contents: &str) -> Result<Vec<Epd>, String> {
+  contents
+    .lines()
+    .enumerate()
+    .filter(|(_, line)| !line.trim().is_empty() && !line.trim_start().starts_with('#'))
+    .map(|(i, line)| Epd::parse(line).map_err(|error| format!("line {}: {error}", i + 1)))
+    .collect()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn parse_test() {
+    let epd = Epd::parse(r#"1k1r4/pp1b1R2/3q2pp/4p3/2B5/4Q3/PPP2B2/2K5 b - - bm Qd1+; id "BK.01"; c0 "a; b";"#).unwrap();
+    assert_eq!(epd.fen, "1k1r4/pp1b1R2/3q2pp/4p3/2B5/4Q3/PPP2B2/2K5 b - - 0 1");
+    assert_eq!(epd.operation("bm"), Some("Qd1+"));
+    assert_eq!(epd.operation("id"), Some("BK.01"));
+    assert_eq!(epd.operation("c0"), Some("a; b"));
+    assert_eq!(epd.operation("am"), None);
+
+    let epd = Epd::parse("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1 ;D1 20 ;D2 400").unwrap();
+    assert_eq!(epd.fen, "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1");
+    assert_eq!(
+      epd.operations,
+      [("D1".to_string(), "20".to_string()), ("D2".to_string(), "400".to_string())]
+    );
+
+    assert!(Epd::parse("8/8/8/8 w").is_err());
+    assert!(Epd::parse("4k3/8/8/8/8/8/8/4K3 w - - 0 ;D1 5").is_err());
+    assert_eq!(parse_file("# comment\n\n4k3/8/8/8/8/8/8/4K3 w - -\n").unwrap().len(), 1);
+    assert_eq!(
+      parse_file("4k3/8/8/8/8/8/8/4K3 w - -\nbad\n").unwrap_err(),
+      "line 2: expected at least 4 fields, got 1"
+    );
+  }
+}
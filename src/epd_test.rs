@@ -0,0 +1,174 @@
+use crate::board::{Board, Move};
+use crate::epd;
+use crate::search::{format_score, iterative_deepening, SearchLimits, TranspositionTable};
+use std::time::Instant;
+
+pub const DEFAULT_MOVETIME: u128 = 1000;
+
+// A position of a suite like WAC or ECM: the search has to play one of the best moves (bm) and none of the moves
+// to avoid (am). STS suites also give points to the good moves in c0, e.g. c0 "f5=10, Be5+=2"
+pub struct TestPosition {
+  pub id: String,
+  pub fen: String,
+  best_moves: Vec<Move>,
+  avoid_moves: Vec<Move>,
+  points: Vec<(Move, u32)>,
+}
+
+impl TestPosition {
+  fn is_solution(&self, chess_move: &Move) -> bool {
+    (self.best_moves.is_empty() || self.best_moves.contains(chess_move)) && !self.avoid_moves.contains(chess_move)
+  }
+
+  fn points(&self, chess_move: &Move) -> u32 {
+    self
+      .points
+      .iter()
+      .find(|(other, _)| other == chess_move)
+      .map_or(0, |(_, points)| *points)
+  }
+
+  fn max_points(&self) -> u32 {
+    self.points.iter().map(|(_, points)| *points).max().unwrap_or(0)
+  }
+}
+
+// SAN moves separated by spaces, None if one of them isn't legal
+fn parse_moves(board: &mut Board, moves: &str) -> Option<Vec<Move>> {
+  moves.split_whitespace().map(|san| board.parse_san(san)).collect()
+}
+
+// Positions without an id are named by their number in the file
+pub fn parse(contents: &str) -> Result<Vec<TestPosition>, String> {
+  epd::parse_file(contents)?
+    .into_iter()
+    .enumerate()
+    .map(|(i, epd)| {
+      let id = epd.operation("id").map_or_else(|| (i + 1).to_string(), str::to_string);
+      let mut board = Board::try_from_fen(&epd.fen).map_err(|error| format!("{id}: {error}"))?;
+
+      let mut moves = |opcode: &str| {
+        parse_moves(&mut board, epd.operation(opcode).unwrap_or(""))
+          .ok_or(format!("{id}: illegal {opcode} '{}'", epd.operation(opcode).unwrap_or("")))
+      };
+      let best_moves = moves("bm")?;
+      let avoid_moves = moves("am")?;
+      if best_moves.is_empty() && avoid_moves.is_empty() {
+        return Err(format!("{id}: no bm or am"));
+      }
+
+      // Any other comment isn't a list of points
+      let points = epd
+        .operation("c0")
+        .and_then(|c0| {
+          c0.split(',')
+            .map(|points| {
+              let (san, points) = points.trim().split_once('=')?;
+              Some((board.parse_san(san)?, points.parse().ok()?))
+            })
+            .collect()
+        })
+        .unwrap_or_default();
+
+      Ok(TestPosition {
+        id,
+        fen: epd.fen,
+        best_moves,
+        avoid_moves,
+        points,
+      })
+    })
+    .collect()
+}
+
+#[derive(Debug, Default)]
+pub struct Summary {
+  pub solved: usize,
+  pub total: usize,
+  pub points: u32,
+  pub max_points: u32,
+  // Of the solved positions, in ms
+  pub time_to_solution: u128,
+}
+
+// Searches every position with the limits and an empty TT, and prints a line for each of them. The time to solution
+// is when the search found the move it ended up playing and didn't change its mind after
+pub fn run(positions: &[TestPosition], limits: &SearchLimits) -> Summary {
+  let mut summary = Summary {
+    total: positions.len(),
+    ..Default::default()
+  };
+  let mut tt = TranspositionTable::new();
+  let start = Instant::now();
+
+  println!(
+    "- {:<16} {:<7} {:<8} {:<16} {:>8} {:>6} {:>8}",
+    "Id", "Result", "Move", "Expected", "Time", "Depth", "Score"
+  );
+
+  for position in positions {
+    let mut board = Board::from_fen(&position.fen);
+    let mut solved_at = None;
+    let mut depth = 0;
+    tt.clear();
+
+    let (score, best_move) = iterative_deepening(&mut board, limits, &mut tt, &mut |info| {
+      if info.multi_pv != 1 {
+        return;
+      }
+      depth = info.depth;
+      solved_at = match info.pv.first() {
+        Some(chess_move) if position.is_solution(chess_move) => solved_at.or(Some(info.time)),
+        _ => None,
+      };
+    });
+
+    let solved = position.is_solution(&best_move);
+    if solved {
+      summary.solved += 1;
+      summary.time_to_solution += solved_at.unwrap_or(0);
+    }
+    summary.points += position.points(&best_move);
+    summary.max_points += position.max_points();
+
+    let mut expected: Vec<String> = vec![];
+    for (opcode, moves) in [("bm", &position.best_moves), ("am", &position.avoid_moves)] {
+      if !moves.is_empty() {
+        let sans: Vec<String> = moves.iter().map(|chess_move| board.to_san(chess_move)).collect();
+        expected.push(format!("{opcode} {}", sans.join(" ")));
+      }
+    }
+
+    println!(
+      "- {:<16} {:<7} {:<8} {:<16} {:>8} {:>6} {:>8}",
+      position.id,
+      if solved { "solved" } else { "failed" },
+      board.to_san(&best_move),
+      expected.join(", "),
+      match solved_at.filter(|_| solved) {
+        Some(time) => format!("{time} ms"),
+        None => "-".to_string(),
+      },
+      depth,
+      format_score(score)
+    );
+  }
+
+  println!(
+    "- Solved {} of {} ({:.1}%) in {} ms",
+    summary.solved,
+    summary.total,
+    summary.solved as f64 * 100. / summary.total.max(1) as f64,
+    start.elapsed().as_millis()
+  );
+  println!(
+    "- Time to solution: {} ms in total, {} ms on average",
+    summary.time_to_solution,
+    summary.time_to_solution / summary.solved.max(1) as u128
+  );
+  if summary.max_points > 0 {
+    println!("- Points: {} of {}", summary.points, summary.max_points);
+  }
+
+  summary
+}
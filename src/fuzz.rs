@@ -0,0 +1,87 @@
+use crate::{bitboard, board, book, tcp, uci};
+use shakmaty::{fen::Fen, uci::Uci, CastlingMode, Chess, Position};
+
+// Fuzz targets, run by cargo fuzz (see fuzz/) and by the property tests (tests.rs) on generated inputs.
+// None of them may panic, whatever the bytes are. Only built for those, with the fuzz feature
+
+// Both boards accept the same fens, and give them back the way they were read
+pub fn fen(data: &[u8]) {
+  let Ok(fen) = std::str::from_utf8(data) else {
+    return;
+  };
+
+  match (board::Board::try_from_fen(fen), bitboard::Board::try_from_fen(fen)) {
+    (Ok(board), Ok(board_bb)) => {
+      let written = board.to_fen();
+      assert_eq!(written, board_bb.to_fen(), "{fen}");
+      assert_eq!(board::Board::from_fen(&written), board, "{fen}");
+      assert_eq!(bitboard::Board::from_fen(&written), board_bb, "{fen}");
+    }
+    (Err(_), Err(_)) => {}
+    (board, board_bb) => panic!("{fen}: board {:?} and bitboard {:?}", board.err(), board_bb.err()),
+  }
+}
+
+// The packets of the interface: "<fen> <white time left> <black time left>"
+pub fn packet(data: &[u8]) {
+  if let Ok(packet) = std::str::from_utf8(data) {
+    if let Ok(fen) = tcp::decode_packet(packet).map(|packet| packet.fen) {
+      assert_eq!(board::Board::from_fen(&fen).to_fen(), fen);
+    }
+  }
+}
+
+// A fen line and a move like "e2e4" or "e7e8q" in the next line: both boards find the same moves as shakmaty,
+// and so does a UCI position command
+pub fn uci_move(data: &[u8]) {
+  let Ok(text) = std::str::from_utf8(data) else {
+    return;
+  };
+  let (fen, move_fen) = text.split_once('\n').unwrap_or((text, ""));
+  let Ok(mut board) = board::Board::try_from_fen(fen) else {
+    return;
+  };
+  let board_bb = bitboard::Board::from_fen(fen);
+  let pos: Chess = fen
+    .parse::<Fen>()
+    .ok()
+    .and_then(|fen| fen.into_position(CastlingMode::Standard).ok())
+    .expect("The fen was validated");
+
+  let expected = move_fen
+    .parse::<Uci>()
+    .ok()
+    .and_then(|uci| uci.to_move(&pos).ok())
+    .map(|chess_move| chess_move.to_uci(CastlingMode::Standard).to_string())
+    // shakmaty also reads castling as the king taking its rook, e.g. "e1h1"
+    .filter(|expected| expected == move_fen);
+  let found = board.find_move(move_fen).map(|chess_move| chess_move.to_fen());
+  let found_bb = board_bb
+    .legal_moves()
+    .into_iter()
+    .map(|chess_move| chess_move.to_fen())
+    .find(|other| other == move_fen);
+  assert_eq!(found, expected, "{fen}: {move_fen}");
+  assert_eq!(found_bb, expected, "{fen}: {move_fen}");
+
+  let command = format!("fen {fen} moves {move_fen}");
+  let tokens: Vec<&str> = command.split_whitespace().collect();
+  if let (Some(after), Some(_)) = (uci::parse_position(&tokens), &expected) {
+    let mut pos = pos;
+    let chess_move = move_fen.parse::<Uci>().ok().and_then(|uci| uci.to_move(&pos).ok());
+    pos.play_unchecked(&chess_move.expect("The move is legal"));
+    assert_eq!(
+      after.to_fen(),
+      Fen::from_position(pos, shakmaty::EnPassantMode::Always).to_string()
+    );
+  }
+}
+
+// The lines of the book file: "<hash>|<move>"
+pub fn book_line(data: &[u8]) {
+  if let Ok(line) = std::str::from_utf8(data) {
+    if let Some((hash, move_fen)) = book::parse_line(line) {
+      assert_eq!(book::parse_line(&format!("{hash}|{move_fen}")), Some((hash, move_fen)));
+    }
+  }
+}
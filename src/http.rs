@@ -0,0 +1,339 @@
+use crate::board::Board;
+use crate::search::{iterative_deepening, mate_in, SearchInfo, SearchLimits, TranspositionTable, MAX_DEPTH};
+use crate::{bitboard, config, perft};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::{collections::BTreeMap, io};
+use tokio::{
+  io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader},
+  net::{TcpListener, TcpStream},
+  sync::Semaphore,
+};
+
+const MAX_HEAD_SIZE: u64 = 8 * 1024;
+const MAX_BODY_SIZE: usize = 64 * 1024;
+const MAX_MULTI_PV: usize = 256;
+const MAX_PERFT_DEPTH: usize = 6;
+
+// Every analysis gets its own small TT, and the others wait until one of them is done
+const MAX_ANALYSES: usize = 4;
+const ANALYSIS_HASH_MB: usize = 16;
+static ANALYSES: Semaphore = Semaphore::const_new(MAX_ANALYSES);
+
+// A small JSON API for scripts, one request per connection:
+//   POST /analyse {"fen", "movetime" (ms) and/or "depth", "multipv"}, never longer than http.max_movetime
+//   POST /perft {"fen", "depth"}
+//   GET /legal-moves?fen=<fen>
+pub async fn run(host: &str, port: u16) -> io::Result<()> {
+  let listener = TcpListener::bind((host, port)).await?;
+  log!(Info, "- Listening for HTTP on {}", listener.local_addr()?);
+  serve(listener).await
+}
+
+pub async fn serve(listener: TcpListener) -> io::Result<()> {
+  loop {
+    let (stream, address) = match listener.accept().await {
+      Ok(client) => client,
+      Err(error) => {
+        log!(Error, "- Couldn't accept a client: {error}");
+        continue;
+      }
+    };
+
+    tokio::spawn(async move {
+      if let Err(error) = handle(stream).await {
+        log!(Error, "- {address}: {error}");
+      }
+    });
+  }
+}
+
+struct Request {
+  method: String,
+  path: String,
+  query: String,
+  body: Vec<u8>,
+}
+
+// Status code and JSON body
+type Response = (u16, Value);
+
+fn error(status: u16, message: impl ToString) -> Response {
+  (status, json!({ "error": message.to_string() }))
+}
+
+async fn handle(stream: TcpStream) -> io::Result<()> {
+  let mut stream = BufReader::new(stream);
+
+  let (status, body) = match read_request(&mut stream).await? {
+    Ok(request) => {
+      log!(Info, "- {} {}", request.method, request.path);
+      route(request).await
+    }
+    Err(message) => error(400, message),
+  };
+
+  let body = body.to_string();
+  let reason = match status {
+    200 => "OK",
+    400 => "Bad Request",
+    404 => "Not Found",
+    405 => "Method Not Allowed",
+    _ => "Internal Server Error",
+  };
+  let response = format!(
+    "HTTP/1.1 {status} {reason}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+    body.len()
+  );
+
+  let stream = stream.get_mut();
+  stream.write_all(response.as_bytes()).await?;
+  stream.shutdown().await
+}
+
+// The outer error is about the connection, the inner one about a malformed request
+async fn read_request(stream: &mut BufReader<TcpStream>) -> io::Result<Result<Request, String>> {
+  let mut head = String::new();
+  let mut limited = (&mut *stream).take(MAX_HEAD_SIZE);
+
+  // The head ends with an empty line
+  loop {
+    let start = head.len();
+    if limited.read_line(&mut head).await? == 0 {
+      return Ok(Err("incomplete request head".to_string()));
+    }
+    if head[start..].trim().is_empty() {
+      break;
+    }
+  }
+
+  let mut lines = head.lines();
+  let mut request_line = lines.next().unwrap_or("").split_whitespace();
+  let (Some(method), Some(target)) = (request_line.next(), request_line.next()) else {
+    return Ok(Err("invalid request line".to_string()));
+  };
+
+  let mut content_length = 0;
+  for header in lines {
+    if let Some((name, value)) = header.split_once(':') {
+      if name.trim().eq_ignore_ascii_case("content-length") {
+        match value.trim().parse::<usize>() {
+          Ok(length) if length <= MAX_BODY_SIZE => content_length = length,
+          _ => return Ok(Err("invalid content length".to_string())),
+        }
+      }
+    }
+  }
+
+  let mut body = vec![0; content_length];
+  stream.read_exact(&mut body).await?;
+
+  let (path, query) = target.split_once('?').unwrap_or((target, ""));
+
+  Ok(Ok(Request {
+    method: method.to_string(),
+    path: path.to_string(),
+    query: query.to_string(),
+    body,
+  }))
+}
+
+async fn route(request: Request) -> Response {
+  // Searches and perft are blocking, so they don't run on the async runtime
+  let blocking = |f: fn(&[u8]) -> Response| async move {
+    tokio::task::spawn_blocking(move || f(&request.body))
+      .await
+      .unwrap_or_else(|error| self::error(500, error))
+  };
+
+  match (request.method.as_str(), request.path.as_str()) {
+    ("POST", "/analyse") => {
+      let _permit = ANALYSES.acquire().await.expect("The semaphore is never closed");
+      blocking(analyse).await
+    }
+    ("POST", "/perft") => blocking(perft).await,
+    ("GET", "/legal-moves") => legal_moves(&request.query),
+    (_, "/analyse" | "/perft" | "/legal-moves") => error(405, "method not allowed"),
+    _ => error(404, "not found"),
+  }
+}
+
+#[derive(Deserialize)]
+struct AnalyseRequest {
+  fen: String,
+  // In ms
+  movetime: Option<u128>,
+  depth: Option<i32>,
+  multipv: Option<usize>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "lowercase")]
+enum Score {
+  Cp(i32),
+  Mate(i32),
+}
+
+impl Score {
+  fn new(score: i32) -> Self {
+    match mate_in(score) {
+      Some(moves) => Self::Mate(moves),
+      None => Self::Cp(score),
+    }
+  }
+}
+
+#[derive(Serialize)]
+struct Line {
+  multipv: usize,
+  depth: i32,
+  score: Score,
+  pv: Vec<String>,
+}
+
+impl Line {
+  fn new(info: &SearchInfo) -> Self {
+    Self {
+      multipv: info.multi_pv,
+      depth: info.depth,
+      score: Score::new(info.score),
+      pv: info.pv.iter().map(|chess_move| chess_move.to_fen()).collect(),
+    }
+  }
+}
+
+// Scores are relative to the side to move
+fn analyse(body: &[u8]) -> Response {
+  let request: AnalyseRequest = match serde_json::from_slice(body) {
+    Ok(request) => request,
+    Err(message) => return error(400, message),
+  };
+  if request.movetime.is_none() && request.depth.is_none() {
+    return error(400, "either movetime or depth is required");
+  }
+  if request.depth.is_some_and(|depth| !(1..=MAX_DEPTH).contains(&depth)) {
+    return error(400, format!("depth has to be between 1 and {MAX_DEPTH}"));
+  }
+
+  let multi_pv = request.multipv.unwrap_or(1);
+  if !(1..=MAX_MULTI_PV).contains(&multi_pv) {
+    return error(400, format!("multipv has to be between 1 and {MAX_MULTI_PV}"));
+  }
+
+  let mut board = match Board::try_from_fen(&request.fen) {
+    Ok(board) => board,
+    Err(message) => return error(400, message),
+  };
+  if board.legal_moves().is_empty() {
+    return error(400, format!("no legal moves: {:?}", board.game_status()));
+  }
+
+  // A search only limited by depth could take forever, so it gets the longest time too
+  let max_movetime = config::get().http.max_movetime as u128;
+  let limits = SearchLimits {
+    time: Some(request.movetime.map_or(max_movetime, |movetime| movetime.min(max_movetime))),
+    depth: request.depth,
+    multi_pv,
+    ..Default::default()
+  };
+
+  // The latest info of every line, and the latest totals
+  let mut lines: Vec<SearchInfo> = vec![];
+  let (mut nodes, mut time) = (0, 0);
+  let mut tt = TranspositionTable::with_size_mb(ANALYSIS_HASH_MB);
+  let (score, best_move) = iterative_deepening(&mut board, &limits, &mut tt, &mut |info| {
+    (nodes, time) = (info.stats.nodes, info.time);
+
+    if info.multi_pv > lines.len() {
+      lines.push(info.clone());
+    } else {
+      lines[info.multi_pv - 1] = info.clone();
+    }
+  });
+
+  let best_line = lines.first();
+  (
+    200,
+    json!({
+      "best_move": best_move.to_fen(),
+      "score": Score::new(score),
+      "depth": best_line.map_or(0, |info| info.depth),
+      "nodes": nodes,
+      "time": time,
+      "pv": best_line.map(|info| Line::new(info).pv).unwrap_or_default(),
+      "lines": lines.iter().map(Line::new).collect::<Vec<Line>>(),
+    }),
+  )
+}
+
+#[derive(Deserialize)]
+struct PerftRequest {
+  fen: String,
+  depth: usize,
+}
+
+fn perft(body: &[u8]) -> Response {
+  let request: PerftRequest = match serde_json::from_slice(body) {
+    Ok(request) => request,
+    Err(message) => return error(400, message),
+  };
+  if !(1..=MAX_PERFT_DEPTH).contains(&request.depth) {
+    return error(400, format!("depth has to be between 1 and {MAX_PERFT_DEPTH}"));
+  }
+
+  let board = match bitboard::Board::try_from_fen(&request.fen) {
+    Ok(board) => board,
+    Err(message) => return error(400, message),
+  };
+
+  // The handlers already run on the blocking threads, one thread per request is enough
+  let moves: BTreeMap<String, u64> = perft::divide(&board, request.depth, None, 1)
+    .into_iter()
+    .map(|(chess_move, nodes)| (chess_move.to_fen(), nodes))
+    .collect();
+
+  (200, json!({ "nodes": moves.values().sum::<u64>(), "moves": moves }))
+}
+
+fn legal_moves(query: &str) -> Response {
+  let fen = query
+    .split('&')
+    .filter_map(|parameter| parameter.split_once('='))
+    .find(|(name, _)| *name == "fen")
+    .map(|(_, value)| decode_query_value(value));
+
+  let mut board = match fen.map(|fen| Board::try_from_fen(&fen)) {
+    Some(Ok(board)) => board,
+    Some(Err(message)) => return error(400, message),
+    None => return error(400, "fen is required"),
+  };
+
+  let moves: Vec<String> = board.legal_moves().iter().map(|chess_move| chess_move.to_fen()).collect();
+  (200, json!({ "status": format!("{:?}", board.game_status()), "moves": moves }))
+}
+
+// '+' is a space and %XX an escaped byte
+fn decode_query_value(value: &str) -> String {
+  let bytes = value.as_bytes();
+  let mut decoded = vec![];
+  let mut i = 0;
+
+  while i < bytes.len() {
+    let escaped = bytes
+      .get(i + 1..i + 3)
+      .and_then(|hex| std::str::from_utf8(hex).ok())
+      .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+
+    match (bytes[i], escaped) {
+      (b'+', _) => decoded.push(b' '),
+      (b'%', Some(byte)) => {
+        decoded.push(byte);
+        i += 2;
+      }
+      (byte, _) => decoded.push(byte),
+    }
+    i += 1;
+  }
+
+  String::from_utf8_lossy(&decoded).into_owned()
+}
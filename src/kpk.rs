@@ -0,0 +1,153 @@
+use crate::bitboard::{king_attacks, Color, Rank, Square};
+use lazy_static::lazy_static;
+
+// King and pawn against king bitbase (https://www.chessprogramming.org/KPK). The pawn is always white's and on
+// the files a to d, other positions are mirrored to those. Every position is indexed by the side to move,
+// both kings and the pawn, which can only be on the ranks 2 to 7
+const SIZE: usize = 2 * 64 * 64 * 24;
+
+// Results of a position while generating, flags so that the results of the moves can be combined
+const INVALID: u8 = 0;
+const UNKNOWN: u8 = 1;
+const DRAW: u8 = 2;
+const WIN: u8 = 4;
+
+lazy_static! {
+  // One bit for every position, set when white wins
+  static ref BITBASE: Vec<u64> = generate();
+}
+
+fn index(side_to_move: Color, white_king: Square, black_king: Square, pawn: Square) -> usize {
+  let pawn = (pawn.rank().index() - 1) * 4 + pawn.file().index();
+  ((side_to_move.index() * 64 + white_king.index()) * 64 + black_king.index()) * 24 + pawn
+}
+
+fn position(index: usize) -> (Color, Square, Square, Square) {
+  let pawn = index % 24;
+  let square = |index: usize| Square::from_index(index).expect("Squares are below 64");
+
+  (
+    if index / (24 * 64 * 64) == 0 {
+      Color::White
+    } else {
+      Color::Black
+    },
+    square(index / (24 * 64) % 64),
+    square(index / 24 % 64),
+    square((pawn / 4 + 1) * 8 + pawn % 4),
+  )
+}
+
+// Whether white wins, with the pawn on the files a to d
+pub fn probe(side_to_move: Color, white_king: Square, black_king: Square, pawn: Square) -> bool {
+  let index = index(side_to_move, white_king, black_king, pawn);
+  BITBASE[index / 64] & (1 << (index % 64)) != 0
+}
+
+// The result of a position which is known without looking at the moves
+fn initial_result(side_to_move: Color, white_king: Square, black_king: Square, pawn: Square) -> u8 {
+  let pawn_attacks = pawn.bitboard().forward_west(Color::White) | pawn.bitboard().forward_east(Color::White);
+
+  if white_king.distance(black_king) <= 1
+    || white_king == pawn
+    || black_king == pawn
+    || (side_to_move == Color::White && pawn_attacks.contains(black_king))
+  {
+    return INVALID;
+  }
+
+  if side_to_move == Color::White && pawn.rank() == Rank::R7 {
+    // Promotes, unless the new queen gets taken right away
+    let promotion = pawn.bitboard().north().lsb();
+    if promotion != white_king && (black_king.distance(promotion) > 1 || white_king.distance(promotion) == 1) {
+      return WIN;
+    }
+  }
+
+  if side_to_move == Color::Black {
+    let moves = king_attacks(black_king) & !(king_attacks(white_king) | pawn_attacks);
+    // Stalemate or the pawn can be taken
+    if moves.is_empty() || moves.contains(pawn) {
+      return DRAW;
+    }
+  }
+
+  UNKNOWN
+}
+
+// The result of a position from the results of its moves: white wins when one move wins,
+// black draws when one move draws, and the result is known once the moves are known
+fn result_from_moves(results: &[u8], side_to_move: Color, white_king: Square, black_king: Square, pawn: Square) -> u8 {
+  let mut result = INVALID;
+
+  if side_to_move == Color::White {
+    for to in king_attacks(white_king) {
+      result |= results[index(Color::Black, to, black_king, pawn)];
+    }
+
+    if pawn.rank() < Rank::R7 {
+      let push = pawn.bitboard().north().lsb();
+      result |= results[index(Color::Black, white_king, black_king, push)];
+
+      if pawn.rank() == Rank::R2 && push != white_king && push != black_king {
+        let double_push = push.bitboard().north().lsb();
+        result |= results[index(Color::Black, white_king, black_king, double_push)];
+      }
+    }
+  } else {
+    for to in king_attacks(black_king) {
+      result |= results[index(Color::White, white_king, to, pawn)];
+    }
+  }
+
+  let (good, bad) = if side_to_move == Color::White {
+    (WIN, DRAW)
+  } else {
+    (DRAW, WIN)
+  };
+  if result & good != 0 {
+    good
+  } else if result & UNKNOWN != 0 {
+    UNKNOWN
+  } else {
+    bad
+  }
+}
+
+// Retrograde analysis: positions get their result from the ones after their moves until nothing changes anymore.
+// Whatever is still unknown then is a draw
+fn generate() -> Vec<u64> {
+  let mut results: Vec<u8> = (0..SIZE)
+    .map(|index| {
+      let (side_to_move, white_king, black_king, pawn) = position(index);
+      initial_result(side_to_move, white_king, black_king, pawn)
+    })
+    .collect();
+
+  let mut changed = true;
+  while changed {
+    changed = false;
+
+    for index in 0..SIZE {
+      if results[index] != UNKNOWN {
+        continue;
+      }
+
+      let (side_to_move, white_king, black_king, pawn) = position(index);
+      let result = result_from_moves(&results, side_to_move, white_king, black_king, pawn);
+      if result != UNKNOWN {
+        results[index] = result;
+        changed = true;
+      }
+    }
+  }
+
+  let mut bitbase = vec![0; SIZE / 64];
+  for (index, result) in results.into_iter().enumerate() {
+    if result == WIN {
+      bitbase[index / 64] |= 1 << (index % 64);
+    }
+  }
+
+  bitbase
+}
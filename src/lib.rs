@@ -0,0 +1,50 @@
+#![feature(const_mut_refs)]
+#![feature(const_eval_limit)]
+#![const_eval_limit = "10000000"]
+#![feature(test)]
+
+// The engine as a library, main.rs is only the command line on top of it.
+// The types most users need are at the root, e.g. `rusty_chess::{Board, Searcher}`
+#[macro_use]
+mod log;
+
+mod bench;
+#[cfg(test)]
+mod benches;
+pub mod bitboard;
+mod board;
+mod book;
+mod cli;
+mod config;
+mod differential;
+mod endgame;
+pub mod engine_match;
+mod epd;
+mod epd_test;
+#[cfg(any(test, feature = "fuzz"))]
+pub mod fuzz;
+mod http;
+mod kpk;
+mod mate;
+mod perft;
+mod search;
+mod search_thread;
+mod server;
+mod syzygy;
+#[cfg(test)]
+mod syzygy_fixtures;
+mod tcp;
+#[cfg(test)]
+mod tests;
+mod uci;
+
+// Both boards take and give the squares, pieces and colors of the bitboard engine
+pub use bitboard::{Color, File, Piece, PieceType, Rank, Square};
+pub use board::{Board, CastlingSide, DrawReason, GameStatus, Move as ChessMove};
+pub use search::{mate_in, RootMoves, SearchInfo, SearchLimits, SearchResult, SearchSignals, SearchStats, Searcher};
+pub use syzygy::{Outcome, Tablebases};
+
+// For main.rs: the configuration (see config.rs) and the commands (see cli.rs)
+pub use cli::run;
+pub use config::{init as init_config, Config};
+pub use log::LogLevel;
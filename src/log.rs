@@ -0,0 +1,23 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogLevel {
+  Off,
+  Error,
+  Info,
+  Debug,
+}
+
+pub fn enabled(level: LogLevel) -> bool {
+  level <= crate::config::get().log_level
+}
+
+// Engine logs, only printed up to the configured level. Protocol output (UCI, bench, ...) is always printed
+macro_rules! log {
+  ($level:ident, $($arg:tt)*) => {
+    if $crate::log::enabled($crate::log::LogLevel::$level) {
+      println!($($arg)*);
+    }
+  };
+}
@@ -0,0 +1,123 @@
+use crate::bitboard::{Board, ChessMove};
+use hashbrown::HashSet;
+
+// Proves or refutes a mate in N moves for the side to move. The attacker needs one move after which every defence
+// gets mated in one move less, so it's an alpha-beta search with only won or not won as the values.
+// With checks_only the attacker only plays checks: much faster, but then not finding a mate doesn't refute it
+pub struct MateSolver {
+  checks_only: bool,
+  // The search gives up after this many nodes
+  max_nodes: u64,
+  pub nodes: u64,
+  // Positions (by hash) and numbers of moves in which there is no mate
+  refuted: HashSet<(u64, usize)>,
+}
+
+// The result of a solve: the mating line, or why there is none
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum MateResult {
+  // The attacker's and the defender's moves, the defender resisting the longest
+  Mate(Vec<ChessMove>),
+  NoMate,
+  // Gave up at the node limit
+  Unknown,
+}
+
+impl MateSolver {
+  pub fn new(checks_only: bool) -> Self {
+    Self {
+      checks_only,
+      max_nodes: u64::MAX,
+      nodes: 0,
+      refuted: HashSet::new(),
+    }
+  }
+
+  pub fn with_node_limit(checks_only: bool, max_nodes: u64) -> Self {
+    Self {
+      max_nodes,
+      ..Self::new(checks_only)
+    }
+  }
+
+  // The shortest mate in at most `moves` moves
+  pub fn solve(&mut self, board: &Board, moves: usize) -> MateResult {
+    for moves in 1..=moves {
+      if let Some(line) = self.attack(board, moves) {
+        return MateResult::Mate(line);
+      }
+      if self.nodes >= self.max_nodes {
+        return MateResult::Unknown;
+      }
+    }
+
+    MateResult::NoMate
+  }
+
+  // A mate in at most `moves` moves for the side to move
+  fn attack(&mut self, board: &Board, moves: usize) -> Option<Vec<ChessMove>> {
+    if self.nodes >= self.max_nodes || self.refuted.contains(&(board.meta.hash, moves)) {
+      return None;
+    }
+    self.nodes += 1;
+
+    let attacker = board.side_to_move;
+    let mut children: Vec<(ChessMove, Board, bool)> = board
+      .legal_moves()
+      .into_iter()
+      .map(|chess_move| {
+        let mut child = board.clone();
+        child.make_move(&chess_move);
+        let check = child.in_check(!attacker);
+        (chess_move, child, check)
+      })
+      .filter(|(_, _, check)| *check || !self.checks_only)
+      .collect();
+
+    // Checks leave the defender the fewest moves, so they are tried first
+    children.sort_by_key(|(_, _, check)| !check);
+
+    for (chess_move, child, _) in children {
+      if let Some(line) = self.defend(&child, moves - 1) {
+        return Some([chess_move].into_iter().chain(line).collect());
+      }
+    }
+
+    // A search cut by the node limit didn't refute anything
+    if self.nodes < self.max_nodes {
+      self.refuted.insert((board.meta.hash, moves));
+    }
+    None
+  }
+
+  // Whether every move of the side to move gets mated in at most `moves` more moves of the attacker
+  fn defend(&mut self, board: &Board, moves: usize) -> Option<Vec<ChessMove>> {
+    let replies = board.legal_moves();
+    if replies.is_empty() {
+      // Checkmate, a stalemate is no mate
+      return board.in_check(board.side_to_move).then(Vec::new);
+    }
+    if moves == 0 {
+      return None;
+    }
+
+    let mut longest: Option<Vec<ChessMove>> = None;
+    for reply in replies {
+      let mut child = board.clone();
+      child.make_move(&reply);
+
+      // The shortest mate after this reply
+      let line = (1..=moves).find_map(|moves| self.attack(&child, moves))?;
+      if longest.as_ref().map_or(true, |longest| line.len() + 1 > longest.len()) {
+        longest = Some([reply].into_iter().chain(line).collect());
+      }
+    }
+
+    longest
+  }
+}
+
+// Number of moves of the attacker in a mating line
+pub fn mate_length(line: &[ChessMove]) -> usize {
+  (line.len() + 1) / 2
+}
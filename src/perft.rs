@@ -0,0 +1,390 @@
+use crate::bitboard::{Board, ChessMove};
+use crate::{board, epd};
+use shakmaty::{fen::Fen, uci::Uci, CastlingMode, Chess, Position};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::thread;
+use std::time::Instant;
+
+// The bundled suite, see its header
+pub const SUITE: &str = include_str!("../epd/perft.epd");
+
+// Entries are 2 u64s
+const ENTRY_SIZE: usize = 16;
+
+// Shared by all the threads without locks: the key is stored xored with the node count, so an entry which
+// was half written by another thread doesn't match anymore
+struct Entry {
+  check: AtomicU64,
+  nodes: AtomicU64,
+}
+
+// Node counts of already counted subtrees, positions which are reached again by transposition are counted once
+pub struct PerftTable {
+  entries: Vec<Entry>,
+}
+
+impl PerftTable {
+  pub fn new(size_mb: usize) -> PerftTable {
+    let length = (size_mb * 1024 * 1024 / ENTRY_SIZE).max(1);
+
+    PerftTable {
+      entries: (0..length)
+        .map(|_| Entry {
+          check: AtomicU64::new(0),
+          nodes: AtomicU64::new(0),
+        })
+        .collect(),
+    }
+  }
+
+  // The same position has a different count at every depth
+  fn key(hash: u64, depth: usize) -> u64 {
+    hash ^ (depth as u64).wrapping_mul(0x9e37_79b9_7f4a_7c15)
+  }
+
+  fn entry(&self, key: u64) -> &Entry {
+    &self.entries[(key % self.entries.len() as u64) as usize]
+  }
+
+  fn probe(&self, hash: u64, depth: usize) -> Option<u64> {
+    let key = Self::key(hash, depth);
+    let entry = self.entry(key);
+    let nodes = entry.nodes.load(Ordering::Relaxed);
+
+    (entry.check.load(Ordering::Relaxed) ^ nodes == key).then_some(nodes)
+  }
+
+  fn store(&self, hash: u64, depth: usize, nodes: u64) {
+    let key = Self::key(hash, depth);
+    let entry = self.entry(key);
+
+    entry.check.store(key ^ nodes, Ordering::Relaxed);
+    entry.nodes.store(nodes, Ordering::Relaxed);
+  }
+}
+
+// https://www.chessprogramming.org/Perft
+// The leaves aren't made, the legal moves are only counted (bulk counting)
+pub fn perft(board: &Board, depth: usize, table: Option<&PerftTable>) -> u64 {
+  match depth {
+    0 => return 1,
+    1 => return board.legal_moves().len() as u64,
+    _ => {}
+  }
+
+  if let Some(nodes) = table.and_then(|table| table.probe(board.meta.hash, depth)) {
+    return nodes;
+  }
+
+  let nodes = board
+    .legal_moves()
+    .iter()
+    .map(|chess_move| {
+      let mut new_board = board.clone();
+      new_board.make_move(chess_move);
+      perft(&new_board, depth - 1, table)
+    })
+    .sum();
+
+  if let Some(table) = table {
+    table.store(board.meta.hash, depth, nodes);
+  }
+
+  nodes
+}
+
+// Number of leaf nodes after every legal move, in move generation order. The root moves are split across the threads,
+// which take the next one when they are done
+pub fn divide(board: &Board, depth: usize, table: Option<&PerftTable>, threads: usize) -> Vec<(ChessMove, u64)> {
+  let moves = board.legal_moves();
+  let next = AtomicUsize::new(0);
+  let counts: Vec<AtomicU64> = moves.iter().map(|_| AtomicU64::new(0)).collect();
+
+  thread::scope(|scope| {
+    for _ in 0..threads.clamp(1, moves.len().max(1)) {
+      scope.spawn(|| {
+        let mut index = next.fetch_add(1, Ordering::Relaxed);
+        while let Some(chess_move) = moves.get(index) {
+          let mut new_board = board.clone();
+          new_board.make_move(chess_move);
+          counts[index].store(perft(&new_board, depth.saturating_sub(1), table), Ordering::Relaxed);
+          index = next.fetch_add(1, Ordering::Relaxed);
+        }
+      });
+    }
+  });
+
+  moves.into_iter().zip(counts.into_iter().map(AtomicU64::into_inner)).collect()
+}
+
+// The mailbox board, to compare both move generators
+pub fn mailbox_perft(board: &mut board::Board, depth: usize) -> u64 {
+  if depth == 0 {
+    return 1;
+  }
+
+  let mut nodes = 0;
+  let side_to_move = board.side_to_move;
+
+  for chess_move in board.pseudo_legal_moves() {
+    board.make_move(&chess_move);
+    if !board.in_check(&side_to_move) {
+      nodes += mailbox_perft(board, depth - 1);
+    }
+    board.undo_move(&chess_move);
+  }
+
+  nodes
+}
+
+// A table of table_mb MB is used if given
+pub fn run(board: &Board, depth: usize, print_divide: bool, table_mb: Option<usize>, threads: usize) {
+  let current_time = Instant::now();
+  let table = table_mb.map(PerftTable::new);
+  let mut nodes = 0;
+
+  for (chess_move, to_add) in divide(board, depth, table.as_ref(), threads) {
+    if print_divide {
+      println!("{}: {}", chess_move.to_fen(), to_add);
+    }
+    nodes += to_add;
+  }
+
+  println!("Depth = {depth}, total nodes = {nodes}");
+
+  let time_elapsed = current_time.elapsed();
+  println!("Time taken: {:?}", time_elapsed);
+
+  let seconds = time_elapsed.as_secs_f64();
+
+  println!("Speed: {} Mn/s", ((nodes as f64 / 1_000_000.) / seconds).round())
+}
+
+pub struct SuitePosition {
+  pub fen: String,
+  // Leaf nodes at depth 1, 2, ...
+  pub nodes: Vec<u64>,
+}
+
+// EPD with the counts as D1, D2, ... operations, the other operations are ignored
+pub fn parse_suite(contents: &str) -> Result<Vec<SuitePosition>, String> {
+  epd::parse_file(contents)?
+    .into_iter()
+    .map(|epd| {
+      let fen = board::Board::normalize_fen(&epd.fen).map_err(|error| format!("{}: {error}", epd.fen))?;
+
+      let mut nodes = vec![];
+      for (opcode, operand) in &epd.operations {
+        let Some(depth) = opcode.strip_prefix('D').and_then(|depth| depth.parse::<usize>().ok()) else {
+          continue;
+        };
+        if depth != nodes.len() + 1 {
+          return Err(format!("{}: D{depth} is out of order", epd.fen));
+        }
+        nodes.push(
+          operand
+            .parse()
+            .map_err(|_| format!("{}: invalid node count '{operand}'", epd.fen))?,
+        );
+      }
+
+      Ok(SuitePosition { fen, nodes })
+    })
+    .collect()
+}
+
+// Leaf nodes after every legal move of the position reached by playing the moves from the fen, with the moves in UCI.
+// The moves are played on the board itself, so that wrong incremental updates are found too
+type Divide = fn(&str, &[String], usize) -> Vec<(String, u64)>;
+
+fn mailbox_divide(fen: &str, moves: &[String], depth: usize) -> Vec<(String, u64)> {
+  let mut board = board::Board::from_fen(fen);
+  for move_fen in moves {
+    let chess_move = board.find_move(move_fen).expect("Only moves of both generators are played");
+    board.make_move(&chess_move);
+  }
+
+  let mut divided = vec![];
+  for chess_move in board.legal_moves() {
+    board.make_move(&chess_move);
+    divided.push((chess_move.to_fen(), mailbox_perft(&mut board, depth - 1)));
+    board.undo_move(&chess_move);
+  }
+
+  divided
+}
+
+fn bitboard_divide(fen: &str, moves: &[String], depth: usize) -> Vec<(String, u64)> {
+  let mut board = Board::from_fen(fen);
+  for move_fen in moves {
+    let chess_move = board
+      .legal_moves()
+      .into_iter()
+      .find(|chess_move| chess_move.to_fen() == *move_fen)
+      .expect("Only moves of both generators are played");
+    board.make_move(&chess_move);
+  }
+
+  divide(&board, depth, None, 1)
+    .into_iter()
+    .map(|(chess_move, nodes)| (chess_move.to_fen(), nodes))
+    .collect()
+}
+
+// The reference
+fn shakmaty_divide(fen: &str, moves: &[String], depth: usize) -> Vec<(String, u64)> {
+  let mut position: Chess = fen
+    .parse::<Fen>()
+    .ok()
+    .and_then(|fen| fen.into_position(CastlingMode::Standard).ok())
+    .expect("The suite fens are validated");
+  for move_fen in moves {
+    let chess_move = move_fen
+      .parse::<Uci>()
+      .ok()
+      .and_then(|uci| uci.to_move(&position).ok())
+      .expect("Only moves of both generators are played");
+    position.play_unchecked(&chess_move);
+  }
+
+  position
+    .legal_moves()
+    .iter()
+    .map(|chess_move| {
+      let mut new_position = position.clone();
+      new_position.play_unchecked(chess_move);
+      (
+        chess_move.to_uci(CastlingMode::Standard).to_string(),
+        shakmaty::perft(&new_position, depth as u32 - 1),
+      )
+    })
+    .collect()
+}
+
+// Follows the first move with a different count than shakmaty, down to the position where the legal moves differ
+fn bisect(fen: &str, depth: usize, divide: Divide) -> String {
+  let mut moves: Vec<String> = vec![];
+
+  for depth in (1..=depth).rev() {
+    let mut divided = divide(fen, &moves, depth);
+    let mut expected = shakmaty_divide(fen, &moves, depth);
+    divided.sort();
+    expected.sort();
+
+    let only_in = |a: &[(String, u64)], b: &[(String, u64)]| -> Vec<String> {
+      a.iter()
+        .filter(|(chess_move, _)| !b.iter().any(|(other, _)| other == chess_move))
+        .map(|(chess_move, _)| chess_move.clone())
+        .collect()
+    };
+    let missing = only_in(&expected, &divided);
+    let extra = only_in(&divided, &expected);
+    if !missing.is_empty() || !extra.is_empty() {
+      return format!(
+        "after [{}] missing [{}] and extra [{}]",
+        moves.join(" "),
+        missing.join(" "),
+        extra.join(" ")
+      );
+    }
+
+    match divided.iter().zip(&expected).find(|(a, b)| a.1 != b.1) {
+      Some(((chess_move, _), _)) => moves.push(chess_move.clone()),
+      None => break,
+    }
+  }
+
+  format!("same counts as shakmaty after [{}]", moves.join(" "))
+}
+
+// The counts of both boards which don't match the suite, up to max_depth, with where they start to differ
+pub fn check(position: &SuitePosition, max_depth: usize) -> Vec<String> {
+  let generators: [(&str, Divide); 2] = [("board", mailbox_divide), ("bitboard", bitboard_divide)];
+  let mut reports = vec![];
+
+  for (name, divide) in generators {
+    for (i, expected) in position.nodes.iter().enumerate().take(max_depth) {
+      let depth = i + 1;
+      let nodes: u64 = divide(&position.fen, &[], depth).iter().map(|(_, nodes)| nodes).sum();
+
+      if nodes != *expected {
+        reports.push(format!(
+          "{name} at depth {depth}: {nodes} nodes instead of {expected}, {}",
+          bisect(&position.fen, depth, divide)
+        ));
+        break;
+      }
+    }
+  }
+
+  reports
+}
+
+// Checks every position of the suite up to max_depth, true if they all match
+pub fn run_suite(contents: &str, max_depth: usize) -> Result<bool, String> {
+  let positions = parse_suite(contents)?;
+  let current_time = Instant::now();
+  let mut failed = 0;
+
+  for (i, position) in positions.iter().enumerate() {
+    let reports = check(position, max_depth);
+    if !reports.is_empty() {
+      failed += 1;
+      println!("- Position {}: {}", i + 1, position.fen);
+      for report in reports {
+        println!("  - {report}");
+      }
+    }
+  }
+
+  println!(
+    "- {} of {} positions passed in {:?}",
+    positions.len() - failed,
+    positions.len(),
+    current_time.elapsed()
+  );
+
+  Ok(failed == 0)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  const START: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+
+  #[test]
+  fn bisect_test() {
+    // Loses d7d5 after e2e4
+    fn buggy_divide(fen: &str, moves: &[String], depth: usize) -> Vec<(String, u64)> {
+      let mut divided = mailbox_divide(fen, moves, depth);
+      match moves {
+        [] => divided
+          .iter_mut()
+          .filter(|(chess_move, _)| chess_move == "e2e4")
+          .for_each(|(_, nodes)| *nodes -= 1),
+        [chess_move] if chess_move == "e2e4" => divided.retain(|(chess_move, _)| chess_move != "d7d5"),
+        _ => {}
+      }
+      divided
+    }
+
+    assert_eq!(bisect(START, 2, buggy_divide), "after [e2e4] missing [d7d5] and extra []");
+    assert_eq!(bisect(START, 2, bitboard_divide), "same counts as shakmaty after []");
+  }
+
+  #[test]
+  fn check_test() {
+    let positions = parse_suite(&format!("{START} ;D1 20 ;D2 400\n{START} ;D1 20 ;D2 401 ;D3 8902")).unwrap();
+    assert!(check(&positions[0], usize::MAX).is_empty());
+
+    let reports = check(&positions[1], usize::MAX);
+    assert_eq!(reports.len(), 2);
+    assert!(reports[0].starts_with("board at depth 2: 400 nodes instead of 401"));
+    assert!(reports[1].starts_with("bitboard at depth 2: 400 nodes instead of 401"));
+    assert!(check(&positions[1], 1).is_empty());
+
+    assert!(parse_suite(&format!("{START} ;D2 400")).is_err());
+    assert!(parse_suite("8/8/8/8/8/8/8/8 w - - ;D1 0").is_err());
+  }
+}
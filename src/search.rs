@@ -0,0 +1,1141 @@
+use crate::bitboard::Color;
+use crate::board::{Board, Move};
+use crate::syzygy::{self, Outcome, Tablebases};
+use std::{
+  sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+  },
+  time::Instant,
+};
+
+// Getting checkmated at ply N is scored as -CHECKMATE + N, so shorter mates are preferred
+pub const CHECKMATE: i32 = 100000;
+// Any score above this (or below the negative of it) is a checkmate
+pub const MATE_BOUND: i32 = CHECKMATE - 1000;
+// A won tablebase position at ply N is scored as TB_WIN - N, below any mate but above any evaluation
+pub const TB_WIN: i32 = MATE_BOUND - 1000;
+// Any score above this (or below the negative of it) is a mate or a tablebase win
+const TB_BOUND: i32 = TB_WIN - MAX_PLY as i32;
+const INF: i32 = 10000000;
+
+pub const MAX_DEPTH: i32 = 100;
+const MAX_PLY: usize = 128;
+
+const EXACT_VALUE: u8 = 0;
+const LOWERBOUND: u8 = 1;
+const UPPERBOUND: u8 = 2;
+
+#[derive(Debug)]
+pub struct TTEntry {
+  typ: u8,
+  value: i32,
+  depth: i32,
+  best_move: Option<Move>,
+}
+
+// A fixed number of slots, the size of the configured hash. A position goes to the slot of its hash and always
+// replaces what was there, so the table keeps taking the newest positions once it's full
+pub struct TranspositionTable {
+  entries: Vec<Option<(u64, TTEntry)>>,
+  used: usize,
+}
+
+impl TranspositionTable {
+  pub fn new() -> Self {
+    Self::with_size_mb(crate::config::get().hash)
+  }
+
+  pub fn with_size_mb(size_mb: usize) -> Self {
+    let length = (size_mb * 1024 * 1024 / std::mem::size_of::<Option<(u64, TTEntry)>>()).max(1);
+    let mut entries = Vec::with_capacity(length);
+    entries.resize_with(length, || None);
+
+    Self { entries, used: 0 }
+  }
+
+  fn index(&self, hash: u64) -> usize {
+    (hash % self.entries.len() as u64) as usize
+  }
+
+  pub fn get(&self, hash: &u64) -> Option<&TTEntry> {
+    match &self.entries[self.index(*hash)] {
+      Some((key, tte)) if key == hash => Some(tte),
+      _ => None,
+    }
+  }
+
+  fn insert(&mut self, hash: u64, tte: TTEntry) {
+    let index = self.index(hash);
+    if self.entries[index].is_none() {
+      self.used += 1;
+    }
+    self.entries[index] = Some((hash, tte));
+  }
+
+  pub fn len(&self) -> usize {
+    self.used
+  }
+
+  pub fn clear(&mut self) {
+    self.entries.fill_with(|| None);
+    self.used = 0;
+  }
+
+  // Per mille
+  pub fn hashfull(&self) -> usize {
+    self.used * 1000 / self.entries.len()
+  }
+}
+
+impl Default for TranspositionTable {
+  fn default() -> Self {
+    Self::new()
+  }
+}
+
+// Search stops at whichever limit is reached first. No limits means searching up to MAX_DEPTH
+#[derive(Clone, Debug)]
+pub struct SearchLimits {
+  // In ms
+  pub time: Option<u128>,
+  pub depth: Option<i32>,
+  // Stops once this many nodes were searched, like the time limit
+  pub nodes: Option<u64>,
+  // Number of best lines to search and report at every depth
+  pub multi_pv: usize,
+  pub root_moves: RootMoves,
+  pub signals: SearchSignals,
+  // Probed in the search and used to pick the root moves in positions with few pieces
+  pub tablebases: Option<Arc<Tablebases>>,
+}
+
+impl Default for SearchLimits {
+  fn default() -> Self {
+    Self {
+      time: None,
+      depth: None,
+      nodes: None,
+      multi_pv: 1,
+      root_moves: RootMoves::default(),
+      signals: SearchSignals::default(),
+      tablebases: syzygy::configured(),
+    }
+  }
+}
+
+impl SearchLimits {
+  pub fn time(time: u128) -> Self {
+    Self {
+      time: Some(time),
+      ..Default::default()
+    }
+  }
+
+  pub fn depth(depth: i32) -> Self {
+    Self {
+      depth: Some(depth),
+      ..Default::default()
+    }
+  }
+}
+
+// Shared with another thread to control a running search
+#[derive(Clone, Debug, Default)]
+pub struct SearchSignals {
+  stop: Arc<AtomicBool>,
+  // While pondering the time limit isn't counting, it starts on ponderhit
+  ponder: Arc<AtomicBool>,
+}
+
+impl SearchSignals {
+  pub fn pondering() -> Self {
+    let signals = Self::default();
+    signals.ponder.store(true, Ordering::Relaxed);
+    signals
+  }
+
+  pub fn stop(&self) {
+    self.stop.store(true, Ordering::Relaxed);
+  }
+
+  pub fn ponderhit(&self) {
+    self.ponder.store(false, Ordering::Relaxed);
+  }
+
+  pub fn is_stopped(&self) -> bool {
+    self.stop.load(Ordering::Relaxed)
+  }
+
+  pub fn is_pondering(&self) -> bool {
+    self.ponder.load(Ordering::Relaxed)
+  }
+}
+
+// Which moves are searched at the root and how much they are preferred
+#[derive(Clone, Debug, Default)]
+pub struct RootMoves {
+  // If not empty, only these moves are searched (UCI searchmoves)
+  pub only: Vec<Move>,
+  pub excluded: Vec<Move>,
+  // Added to the scores of the moves, e.g. to prefer book moves
+  pub bonuses: Vec<(Move, i32)>,
+}
+
+impl RootMoves {
+  pub fn only(moves: Vec<Move>) -> Self {
+    Self {
+      only: moves,
+      ..Default::default()
+    }
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.only.is_empty() && self.excluded.is_empty() && self.bonuses.is_empty()
+  }
+
+  pub fn allows(&self, chess_move: &Move) -> bool {
+    (self.only.is_empty() || self.only.contains(chess_move)) && !self.excluded.contains(chess_move)
+  }
+
+  pub fn bonus(&self, chess_move: &Move) -> i32 {
+    self
+      .bonuses
+      .iter()
+      .find(|(m, _)| m == chess_move)
+      .map_or(0, |(_, bonus)| *bonus)
+  }
+
+  // The legal moves of the position which will be searched
+  pub fn legal_moves(&self, board: &mut Board) -> Vec<Move> {
+    board.legal_moves().into_iter().filter(|m| self.allows(m)).collect()
+  }
+}
+
+// Counters collected during the search, to see what it is doing and how well the moves are ordered.
+// There are no null-move or LMR counts because the search prunes with neither of them
+#[derive(Clone, Debug, Default)]
+pub struct SearchStats {
+  // All nodes, including the quiescence ones
+  pub nodes: u64,
+  pub qnodes: u64,
+  pub tt_hits: u64,
+  // TT hits which were enough to return from the node without searching it
+  pub tt_cutoffs: u64,
+  // Beta cutoffs in the main search and how many of them happened on the first searched move
+  pub beta_cutoffs: u64,
+  pub first_move_cutoffs: u64,
+  // Positions whose value was taken from the tablebases
+  pub tb_hits: u64,
+}
+
+impl SearchStats {
+  pub fn add(&mut self, other: &SearchStats) {
+    self.nodes += other.nodes;
+    self.qnodes += other.qnodes;
+    self.tt_hits += other.tt_hits;
+    self.tt_cutoffs += other.tt_cutoffs;
+    self.beta_cutoffs += other.beta_cutoffs;
+    self.first_move_cutoffs += other.first_move_cutoffs;
+    self.tb_hits += other.tb_hits;
+  }
+
+  // In percent, a well ordered search cuts off on the first move most of the time
+  pub fn first_move_cutoff_rate(&self) -> f64 {
+    if self.beta_cutoffs == 0 {
+      0.
+    } else {
+      self.first_move_cutoffs as f64 * 100. / self.beta_cutoffs as f64
+    }
+  }
+
+  pub fn to_log(&self) -> String {
+    format!(
+      "- Stats: {} nodes ({} qnodes) | TT {} hits, {} cutoffs | {} beta cutoffs, {:.1}% on the first move | {} TB hits",
+      self.nodes,
+      self.qnodes,
+      self.tt_hits,
+      self.tt_cutoffs,
+      self.beta_cutoffs,
+      self.first_move_cutoff_rate(),
+      self.tb_hits
+    )
+  }
+}
+
+// Information about a fully searched depth
+#[derive(Clone, Debug)]
+pub struct SearchInfo {
+  pub depth: i32,
+  // Rank of the line, starting from 1
+  pub multi_pv: usize,
+  pub seldepth: i32,
+  // Relative to the side to move
+  pub score: i32,
+  pub stats: SearchStats,
+  // In ms
+  pub time: u128,
+  // Per mille of the TT size
+  pub hashfull: usize,
+  pub pv: Vec<Move>,
+}
+
+impl SearchInfo {
+  pub fn nps(&self) -> u64 {
+    (self.stats.nodes as u128 * 1000 / self.time.max(1)) as u64
+  }
+
+  fn pv_string(&self) -> String {
+    self.pv.iter().map(|m| m.to_fen()).collect::<Vec<String>>().join(" ")
+  }
+
+  pub fn to_uci(&self) -> String {
+    let score = match mate_in(self.score) {
+      Some(moves) => format!("mate {moves}"),
+      None => format!("cp {}", self.score),
+    };
+
+    format!(
+      "info depth {} seldepth {} multipv {} score {score} nodes {} nps {} hashfull {} tbhits {} time {} pv {}",
+      self.depth,
+      self.seldepth,
+      self.multi_pv,
+      self.stats.nodes,
+      self.nps(),
+      self.hashfull,
+      self.stats.tb_hits,
+      self.time,
+      self.pv_string()
+    )
+  }
+
+  // The score is shown from white's perspective, like the rest of the log
+  pub fn to_log(&self, side_to_move: &Color) -> String {
+    let score = if *side_to_move == Color::White {
+      self.score
+    } else {
+      -self.score
+    };
+
+    let line = if self.multi_pv > 1 {
+      format!(" | line {}", self.multi_pv)
+    } else {
+      String::new()
+    };
+
+    format!(
+      "- Depth {} ({}){line} | eval {} | {} nodes, {} kn/s | hash {}‰ | {} ms | {}",
+      self.depth,
+      self.seldepth,
+      format_score(score),
+      self.stats.nodes,
+      self.nps() / 1000,
+      self.hashfull,
+      self.time,
+      self.pv_string()
+    )
+  }
+}
+
+// State shared by the whole search
+struct SearchState {
+  start: Instant,
+  // When the time limit started counting, later than start after a ponderhit
+  limit_start: Instant,
+  // In ms
+  limit: u128,
+  node_limit: u64,
+  signals: SearchSignals,
+  pondering: bool,
+  stopped: bool,
+  stats: SearchStats,
+  seldepth: i32,
+  // Triangular PV table: pv[ply] is the best line found from that ply
+  pv: Vec<Vec<Move>>,
+  // The first moves of better lines are added to its excluded moves, see iterative_deepening
+  root_moves: RootMoves,
+  tablebases: Option<Arc<Tablebases>>,
+}
+
+impl SearchState {
+  fn new(limits: &SearchLimits) -> Self {
+    Self {
+      start: Instant::now(),
+      limit_start: Instant::now(),
+      limit: limits.time.unwrap_or(u128::MAX),
+      node_limit: limits.nodes.unwrap_or(u64::MAX),
+      signals: limits.signals.clone(),
+      pondering: limits.signals.is_pondering(),
+      stopped: false,
+      stats: SearchStats::default(),
+      seldepth: 0,
+      pv: vec![vec![]; MAX_PLY + 1],
+      root_moves: limits.root_moves.clone(),
+      tablebases: limits.tablebases.clone(),
+    }
+  }
+
+  fn time_is_up(&mut self) -> bool {
+    if self.pondering && !self.signals.is_pondering() {
+      self.pondering = false;
+      self.limit_start = Instant::now();
+    }
+
+    if self.signals.is_stopped()
+      || self.stats.nodes >= self.node_limit
+      || (!self.pondering && self.limit_start.elapsed().as_millis() > self.limit)
+    {
+      self.stopped = true;
+    }
+
+    self.stopped
+  }
+
+  fn update_pv(&mut self, ply: usize, chess_move: &Move) {
+    let (current, rest) = self.pv.split_at_mut(ply + 1);
+
+    current[ply].clear();
+    current[ply].push(chess_move.clone());
+    current[ply].extend_from_slice(&rest[0]);
+  }
+}
+
+// The time to spend on a move, given the time left on our clock (both in ms).
+// The overhead is kept for the communication, the result is clamped to the configured bounds
+// and never more than a tenth of what is left, so a nearly empty clock doesn't run out
+pub fn time_for_move(time_left: isize) -> u128 {
+  let config = &crate::config::get().time;
+  let time_left = time_left - config.move_overhead as isize;
+
+  let time = if time_left < 10 * 1000 {
+    1000
+  } else if time_left < 30 * 1000 {
+    2 * 1000
+  } else if time_left < 60 * 1000 {
+    4 * 1000
+  } else if time_left < 2 * 60 * 1000 {
+    6 * 1000
+  } else if time_left < 3 * 60 * 1000 {
+    8 * 1000
+  } else if time_left < 4 * 60 * 1000 {
+    10 * 1000
+  } else {
+    12 * 1000
+  };
+
+  time
+    .clamp(config.min_move_time as u128, config.max_move_time as u128)
+    .min(time_left.max(0) as u128 / 10)
+}
+
+// Mate and tablebase scores are stored in the TT relative to the node instead of the root,
+// because the same position can be reached at a different ply
+fn value_to_tt(value: i32, ply: i32) -> i32 {
+  if value >= TB_BOUND {
+    value + ply
+  } else if value <= -TB_BOUND {
+    value - ply
+  } else {
+    value
+  }
+}
+
+fn value_from_tt(value: i32, ply: i32) -> i32 {
+  if value >= TB_BOUND {
+    value - ply
+  } else if value <= -TB_BOUND {
+    value + ply
+  } else {
+    value
+  }
+}
+
+// Number of moves until checkmate, negative if the side to move is the one getting checkmated
+pub fn mate_in(score: i32) -> Option<i32> {
+  if score >= MATE_BOUND {
+    Some((CHECKMATE - score + 1) / 2)
+  } else if score <= -MATE_BOUND {
+    Some(-(CHECKMATE + score) / 2)
+  } else {
+    None
+  }
+}
+
+pub fn format_score(score: i32) -> String {
+  match mate_in(score) {
+    Some(moves) => format!("#{moves}"),
+    None => score.to_string(),
+  }
+}
+
+// fn mtdf(board: &mut Board, first: i32, depth: i32, tt: &mut TranspositionTable) -> (i32, Move) {
+//   let mut g = first;
+//   let mut beta: i32;
+//   let mut upperbound = INF;
+//   let mut lowerbound = -INF;
+//   let mut best_move: Move;
+
+//   loop {
+//     if g == lowerbound {
+//       beta = g + 1;
+//     } else {
+//       beta = g;
+//     }
+
+//     g = alpha_beta_tt(board, tt, beta - 1, beta, depth);
+//     let tte = tt.get(&board.meta.hash).expect("Root node not in TT");
+//     best_move = tte.best_move.clone().expect("Root node does not have best move");
+
+//     if g < beta {
+//       upperbound = g;
+//     } else {
+//       lowerbound = g;
+//     }
+
+//     if lowerbound >= upperbound {
+//       break;
+//     }
+//   }
+
+//   (g, best_move)
+// }
+
+// fn sss(board: &mut Board, depth: i32, tt: &mut TranspositionTable) -> (i32, Move) {
+//   let mut g: i32 = INF;
+//   let mut w: i32;
+//   let mut best_move: Move;
+
+//   loop {
+//     w = g;
+//     g = alpha_beta_tt(board, tt, w - 1, 1, depth);
+//     let tte = tt.get(&board.meta.hash).expect("Root node not in TT");
+//     best_move = tte.best_move.clone().expect("Root node does not have best move");
+
+//     if g == w {
+//       break;
+//     }
+//   }
+
+//   (g, best_move)
+// }
+
+// Searches with the given time and logs every iteration
+fn aspiration(
+  board: &mut Board,
+  tt: &mut TranspositionTable,
+  state: &mut SearchState,
+  depth: i32,
+  prev_value: i32,
+  window: i32,
+) -> i32 {
+  let alpha = prev_value - window;
+  let beta = prev_value + window;
+
+  let mut value = alpha_beta_tt_i(board, tt, state, alpha, beta, depth, 0);
+
+  if value >= beta {
+    value = alpha_beta_tt_i(board, tt, state, value, INF, depth, 0);
+  } else if value <= alpha {
+    value = alpha_beta_tt_i(board, tt, state, -INF, value, depth, 0);
+  }
+
+  value
+}
+
+// Not used by the engine, kept to compare aspiration windows against full windows
+#[allow(dead_code)]
+pub fn iterative_deepening_asp(
+  board: &mut Board,
+  limits: &SearchLimits,
+  tt: &mut TranspositionTable,
+  report: &mut dyn FnMut(&SearchInfo),
+) -> (i32, Move) {
+  let mut best = 0;
+  let mut best_move: Option<Move> = None;
+  let mut state = SearchState::new(limits);
+
+  for depth in 1..=limits.depth.unwrap_or(MAX_DEPTH).min(MAX_DEPTH) {
+    let eval = aspiration(board, tt, &mut state, depth, best, 100);
+
+    if state.stopped {
+      break;
+    }
+
+    best = eval;
+    best_move = state.pv[0].first().cloned();
+
+    report(&iteration_info(board, &state, tt, depth, 1, eval));
+
+    if best.abs() >= MATE_BOUND || state.time_is_up() {
+      break;
+    }
+  }
+
+  (best, best_move.unwrap())
+}
+
+pub fn iterative_deepening(
+  board: &mut Board,
+  limits: &SearchLimits,
+  tt: &mut TranspositionTable,
+  report: &mut dyn FnMut(&SearchInfo),
+) -> (i32, Move) {
+  let mut best = 0;
+  let mut best_move: Option<Move> = None;
+  let mut state = SearchState::new(limits);
+
+  // Restrictions which leave nothing to search are ignored, like UCI engines usually do
+  let mut root_moves_count = state.root_moves.legal_moves(board).len();
+  if root_moves_count == 0 {
+    state.root_moves = RootMoves::default();
+    root_moves_count = board.legal_moves().len();
+  }
+
+  // In a tablebase position only the moves keeping the best outcome are searched
+  let tablebase_moves = state.tablebases.clone().and_then(|tablebases| tablebases.root_moves(board));
+  if let Some((_, moves)) = tablebase_moves {
+    let moves: Vec<Move> = moves.into_iter().filter(|m| state.root_moves.allows(m)).collect();
+    if !moves.is_empty() {
+      root_moves_count = moves.len();
+      state.root_moves.only = moves;
+    }
+  }
+  let excluded_count = state.root_moves.excluded.len();
+
+  // There can't be more lines than moves
+  let lines = limits.multi_pv.clamp(1, root_moves_count.max(1));
+
+  for depth in 1..=limits.depth.unwrap_or(MAX_DEPTH).min(MAX_DEPTH) {
+    // Every next line is searched without the first moves of the previous ones,
+    // so the lines come out ranked from best to worst
+    state.root_moves.excluded.truncate(excluded_count);
+
+    for multi_pv in 1..=lines {
+      let eval = alpha_beta_tt_i(board, tt, &mut state, -INF, INF, depth, 0);
+
+      if state.stopped {
+        break;
+      }
+
+      if multi_pv == 1 {
+        best = eval;
+        best_move = state.pv[0].first().cloned();
+      }
+
+      report(&iteration_info(board, &state, tt, depth, multi_pv, eval));
+
+      state.root_moves.excluded.extend(state.pv[0].first().cloned());
+    }
+
+    if state.stopped {
+      break;
+    }
+
+    if best.abs() >= MATE_BOUND || state.time_is_up() {
+      break;
+    }
+  }
+
+  // Stopped before the first depth was searched
+  let best_move = best_move.or_else(|| state.root_moves.legal_moves(board).first().cloned());
+
+  (best, best_move.expect("No legal moves"))
+}
+
+// The outcome of a whole search
+#[derive(Clone, Debug)]
+pub struct SearchResult {
+  // Relative to the side to move
+  pub score: i32,
+  pub best_move: Move,
+  pub pv: Vec<Move>,
+  // The last fully searched depth
+  pub depth: i32,
+  pub stats: SearchStats,
+}
+
+// Keeps the TT between searches, so a game can be searched move after move
+pub struct Searcher {
+  tt: TranspositionTable,
+}
+
+impl Searcher {
+  pub fn new() -> Self {
+    Self {
+      tt: TranspositionTable::new(),
+    }
+  }
+
+  pub fn with_hash_size(size_mb: usize) -> Self {
+    Self {
+      tt: TranspositionTable::with_size_mb(size_mb),
+    }
+  }
+
+  // None when there is no legal move
+  pub fn search(&mut self, board: &Board, limits: &SearchLimits) -> Option<SearchResult> {
+    self.search_with_info(board, limits, |_| {})
+  }
+
+  // Calls on_info after every searched depth (and line with multi pv)
+  pub fn search_with_info(
+    &mut self,
+    board: &Board,
+    limits: &SearchLimits,
+    mut on_info: impl FnMut(&SearchInfo),
+  ) -> Option<SearchResult> {
+    let mut board = board.clone();
+    if board.legal_moves().is_empty() {
+      return None;
+    }
+
+    let mut best_line: Option<SearchInfo> = None;
+    let (score, best_move) = iterative_deepening(&mut board, limits, &mut self.tt, &mut |info| {
+      if info.multi_pv == 1 {
+        best_line = Some(info.clone());
+      }
+      on_info(info);
+    });
+
+    Some(SearchResult {
+      score,
+      pv: best_line
+        .as_ref()
+        .map_or_else(|| vec![best_move.clone()], |info| info.pv.clone()),
+      best_move,
+      depth: best_line.as_ref().map_or(0, |info| info.depth),
+      stats: best_line.map(|info| info.stats).unwrap_or_default(),
+    })
+  }
+
+  // Forgets everything learned, e.g. for a new game
+  pub fn clear(&mut self) {
+    self.tt.clear();
+  }
+}
+
+impl Default for Searcher {
+  fn default() -> Self {
+    Self::new()
+  }
+}
+
+fn iteration_info(
+  board: &mut Board,
+  state: &SearchState,
+  tt: &TranspositionTable,
+  depth: i32,
+  multi_pv: usize,
+  score: i32,
+) -> SearchInfo {
+  SearchInfo {
+    depth,
+    multi_pv,
+    seldepth: state.seldepth,
+    score,
+    stats: state.stats.clone(),
+    time: state.start.elapsed().as_millis(),
+    hashfull: tt.hashfull(),
+    pv: extend_pv(board, tt, &state.pv[0], depth as usize),
+  }
+}
+
+// The PV table stops where a TT entry cut the search, so the rest of the line is taken from the TT.
+// Repeated positions end the walk, otherwise it could go around in circles
+fn extend_pv(board: &mut Board, tt: &TranspositionTable, pv: &[Move], depth: usize) -> Vec<Move> {
+  let mut line = pv.to_vec();
+  let mut seen = vec![board.meta.hash];
+
+  for chess_move in pv {
+    board.make_move(chess_move);
+    seen.push(board.meta.hash);
+  }
+
+  while line.len() < depth {
+    let Some(chess_move) = tt.get(&board.meta.hash).and_then(|tte| tte.best_move.clone()) else {
+      break;
+    };
+    // A hash collision could give a move from another position
+    if !board.legal_moves().contains(&chess_move) {
+      break;
+    }
+
+    board.make_move(&chess_move);
+    line.push(chess_move);
+
+    if seen.contains(&board.meta.hash) {
+      break;
+    }
+    seen.push(board.meta.hash);
+  }
+
+  for chess_move in line.iter().rev() {
+    board.undo_move(chess_move);
+  }
+
+  line
+}
+
+// fn iterative_deepening_mtd(board: &mut Board, time_given: u128) -> (i32, Move) {
+//   let mut best = 0;
+//   let mut best_move: Option<Move> = None;
+//   let mut tt: TranspositionTable = HashMap::new();
+//   let started_time = Instant::now();
+
+//   for depth in 1..=100 {
+//     let (some_guess, some_move) = mtdf(board, best, depth, &mut tt);
+//     best = some_guess;
+//     best_move = Some(some_move);
+
+//     let dt = started_time.elapsed();
+
+//     if (CHECKMATE..CHECKMATE + 100).contains(&best) {
+//       println!("- Found checkmate");
+//       println!("- Searched to depth {}", depth);
+//       break;
+//     }
+
+//     if dt.as_millis() >= time_given {
+//       println!("- Time limit exceeded");
+//       println!("- Searched to depth {}", depth);
+//       break;
+//     }
+//   }
+
+//   println!("- TT size: {}", tt.len());
+
+//   (best, best_move.unwrap())
+// }
+
+#[allow(clippy::too_many_arguments)]
+fn save_tte(
+  board: &Board,
+  tt: &mut TranspositionTable,
+  value: i32,
+  depth: i32,
+  ply: i32,
+  alpha: i32,
+  beta: i32,
+  best_move: Option<Move>,
+) {
+  let typ = if value <= alpha {
+    UPPERBOUND
+  } else if value >= beta {
+    LOWERBOUND
+  } else {
+    EXACT_VALUE
+  };
+
+  let tte = TTEntry {
+    typ,
+    value: value_to_tt(value, ply),
+    depth,
+    best_move,
+  };
+
+  tt.insert(board.meta.hash, tte);
+}
+
+fn alpha_beta_tt_i(
+  board: &mut Board,
+  tt: &mut TranspositionTable,
+  state: &mut SearchState,
+  mut alpha: i32,
+  mut beta: i32,
+  depth: i32,
+  ply: i32,
+) -> i32 {
+  if state.time_is_up() {
+    return 0;
+  }
+
+  state.stats.nodes += 1;
+  state.seldepth = state.seldepth.max(ply);
+  state.pv[ply as usize].clear();
+
+  // The root position has to be searched even if it is drawn, otherwise there would be no move to play
+  if ply > 0 {
+    if board.is_fifty_move_draw() || board.is_repetition(2) || board.is_insufficient_material() {
+      return 0;
+    }
+
+    // Mate distance pruning: no line from here can be better than mating right away
+    // or worse than getting mated right away
+    alpha = alpha.max(-CHECKMATE + ply);
+    beta = beta.min(CHECKMATE - ply - 1);
+    if alpha >= beta {
+      return alpha;
+    }
+  }
+
+  // Right after a capture or a pawn move, a position with few pieces has its value in the tablebases
+  if ply > 0 {
+    if let Some(outcome) = state.tablebases.as_ref().and_then(|tablebases| tablebases.probe_wdl(board)) {
+      state.stats.tb_hits += 1;
+      return match outcome {
+        Outcome::Win => TB_WIN - ply,
+        Outcome::Draw => 0,
+        Outcome::Loss => -TB_WIN + ply,
+      };
+    }
+  }
+
+  // Alpha gets raised while searching, but whether the result is an upper bound depends on the starting one
+  let original_alpha = alpha;
+
+  let mut value: i32;
+  let option_tte = tt.get(&board.meta.hash);
+  if let Some(tte) = option_tte {
+    state.stats.tt_hits += 1;
+
+    // The root always gets searched, so that it has a PV
+    if tte.depth >= depth && ply > 0 {
+      let tte_value = value_from_tt(tte.value, ply);
+
+      if tte.typ == EXACT_VALUE {
+        state.stats.tt_cutoffs += 1;
+        return tte_value;
+      }
+
+      if tte.typ == LOWERBOUND && tte_value > alpha {
+        alpha = tte_value;
+      } else if tte.typ == UPPERBOUND && tte_value < beta {
+        beta = tte_value;
+      }
+
+      if alpha >= beta {
+        state.stats.tt_cutoffs += 1;
+        return tte_value;
+      }
+    }
+  }
+
+  if depth == 0 {
+    value = quiesce_i(board, state, alpha, beta, ply);
+
+    save_tte(board, tt, value, depth, ply, original_alpha, beta, None);
+
+    return value;
+  }
+
+  let side_to_move = board.side_to_move;
+
+  let pseudo_legal_moves = board.pseudo_legal_moves();
+
+  let mut cant_move = true;
+  let mut best: i32 = -INF;
+  let mut best_move: Option<Move> = None;
+  // The bonus of the best move, taken off the value again when it's stored or returned
+  let mut best_bonus = 0;
+  let mut searched_moves = 0;
+
+  // Only the moves allowed by RootMoves are searched at the root
+  let is_excluded = |chess_move: &Move, state: &SearchState| ply == 0 && !state.root_moves.allows(chess_move);
+  // The search value of a root move is shifted by its bonus, so the window of the child is shifted too
+  let bonus = |chess_move: &Move, state: &SearchState| if ply == 0 { state.root_moves.bonus(chess_move) } else { 0 };
+
+  if let Some(tte) = option_tte {
+    if let Some(good_move) = tte.best_move.as_ref().filter(|m| !is_excluded(m, state)) {
+      let some_move = good_move.clone();
+      let bonus = bonus(&some_move, state);
+
+      board.make_move(&some_move);
+      value = -alpha_beta_tt_i(board, tt, state, -beta + bonus, -alpha + bonus, depth - 1, ply + 1) + bonus;
+      board.undo_move(&some_move);
+      if state.stopped {
+        return 0;
+      }
+      searched_moves += 1;
+
+      if value > best {
+        best = value;
+        best_move = Some(some_move);
+        best_bonus = bonus;
+      }
+      if best > alpha {
+        alpha = best;
+        state.update_pv(ply as usize, best_move.as_ref().unwrap());
+      }
+      if best >= beta {
+        state.stats.beta_cutoffs += 1;
+        state.stats.first_move_cutoffs += 1;
+        return save_root_or_node(
+          board,
+          tt,
+          state,
+          best - best_bonus,
+          depth,
+          ply,
+          original_alpha,
+          beta,
+          best_move,
+        );
+      }
+    }
+  }
+
+  for pseudo_legal_move in pseudo_legal_moves {
+    if is_excluded(&pseudo_legal_move, state) {
+      continue;
+    }
+
+    board.make_move(&pseudo_legal_move);
+
+    if !board.in_check(&side_to_move) {
+      // It is a legal move
+      cant_move = false;
+      let bonus = bonus(&pseudo_legal_move, state);
+
+      value = -alpha_beta_tt_i(board, tt, state, -beta + bonus, -alpha + bonus, depth - 1, ply + 1) + bonus;
+      board.undo_move(&pseudo_legal_move);
+      if state.stopped {
+        return 0;
+      }
+      searched_moves += 1;
+
+      if value > best {
+        best = value;
+        best_move = Some(pseudo_legal_move.clone());
+        best_bonus = bonus;
+      }
+      if best > alpha {
+        alpha = best;
+        state.update_pv(ply as usize, &pseudo_legal_move);
+      }
+      if best >= beta {
+        state.stats.beta_cutoffs += 1;
+        if searched_moves == 1 {
+          state.stats.first_move_cutoffs += 1;
+        }
+        break;
+      }
+    } else {
+      board.undo_move(&pseudo_legal_move);
+    }
+  }
+
+  if cant_move {
+    if board.in_check(&side_to_move) {
+      // In check and can't move => Checkmate
+      value = -CHECKMATE + ply;
+    } else {
+      // Not in check and can't move => Stalemate
+      value = 0;
+    }
+
+    save_tte(board, tt, value, depth, ply, original_alpha, beta, None);
+
+    return value;
+  }
+
+  save_root_or_node(
+    board,
+    tt,
+    state,
+    best - best_bonus,
+    depth,
+    ply,
+    original_alpha,
+    beta,
+    best_move,
+  )
+}
+
+// Saves the value of a node and returns it. At the root the value is the one of the chosen move without its bonus
+#[allow(clippy::too_many_arguments)]
+fn save_root_or_node(
+  board: &Board,
+  tt: &mut TranspositionTable,
+  state: &SearchState,
+  value: i32,
+  depth: i32,
+  ply: i32,
+  alpha: i32,
+  beta: i32,
+  best_move: Option<Move>,
+) -> i32 {
+  let root_moves = &state.root_moves;
+
+  if ply > 0 {
+    save_tte(board, tt, value, depth, ply, alpha, beta, best_move);
+  } else if root_moves.only.is_empty() && root_moves.excluded.is_empty() {
+    // With bonuses the chosen move doesn't have to be the best one, so its value is only a lower bound.
+    // A root searched without some of its moves doesn't have its real value at all
+    let beta = if root_moves.bonuses.is_empty() {
+      beta
+    } else {
+      beta.min(value)
+    };
+    save_tte(board, tt, value, depth, ply, alpha, beta, best_move);
+  }
+
+  value
+}
+
+fn quiesce_i(board: &mut Board, state: &mut SearchState, mut alpha: i32, beta: i32, ply: i32) -> i32 {
+  if state.time_is_up() {
+    return 0;
+  }
+
+  state.stats.nodes += 1;
+  state.stats.qnodes += 1;
+  state.seldepth = state.seldepth.max(ply);
+
+  let standing_eval = board.evaluate_relative();
+
+  if standing_eval >= beta {
+    return beta;
+  }
+
+  if alpha < standing_eval {
+    alpha = standing_eval;
+  }
+
+  let side_to_move = board.side_to_move;
+
+  let pseudo_legal_moves = board.pseudo_legal_moves();
+
+  let mut cant_move = true;
+
+  for pseudo_legal_move in pseudo_legal_moves {
+    board.make_move(&pseudo_legal_move);
+
+    if !board.in_check(&side_to_move) {
+      // It is a legal move
+      cant_move = false;
+
+      let should_branch = pseudo_legal_move.is_capture() || board.in_check(&board.side_to_move);
+
+      if should_branch {
+        let score = -quiesce_i(board, state, -beta, -alpha, ply + 1);
+
+        if state.stopped {
+          board.undo_move(&pseudo_legal_move);
+          return 0;
+        }
+
+        if score >= beta {
+          board.undo_move(&pseudo_legal_move);
+          return beta;
+        }
+        if score > alpha {
+          alpha = score;
+        }
+      }
+    }
+
+    board.undo_move(&pseudo_legal_move);
+  }
+
+  if cant_move {
+    if board.in_check(&side_to_move) {
+      // In check and can't move => Checkmate
+      return -CHECKMATE + ply;
+    } else {
+      // Not in check and can't move => Stalemate
+      return 0;
+    }
+  }
+
+  alpha
+}
@@ -0,0 +1,82 @@
+use crate::board::{Board, Move};
+use crate::search::{iterative_deepening, SearchInfo, SearchLimits, SearchSignals, TranspositionTable};
+use std::thread::{self, JoinHandle};
+use tokio::sync::mpsc::{self, UnboundedReceiver};
+
+#[derive(Debug)]
+pub enum SearchEvent {
+  // After every searched depth (and line with MultiPV)
+  Info(SearchInfo),
+  // Always the last event, also when the search was stopped
+  Done {
+    score: i32,
+    best_move: Move,
+    expected_reply: Option<Move>,
+  },
+}
+
+// A search running on a dedicated thread. It is controlled through its signals and reports through a channel,
+// so async code can wait for it together with other events instead of being blocked by it
+pub struct SearchThread {
+  signals: SearchSignals,
+  events: UnboundedReceiver<SearchEvent>,
+  handle: JoinHandle<TranspositionTable>,
+}
+
+impl SearchThread {
+  // The TT is moved to the thread and given back by finish
+  pub fn spawn(mut board: Board, limits: SearchLimits, mut tt: TranspositionTable) -> Self {
+    let (sender, events) = mpsc::unbounded_channel();
+    let signals = limits.signals.clone();
+
+    let handle = thread::spawn(move || {
+      let mut pv = vec![];
+
+      // Sending fails only if the receiver is gone, then nobody is interested in the search anymore
+      let (score, best_move) = iterative_deepening(&mut board, &limits, &mut tt, &mut |info| {
+        if info.multi_pv == 1 {
+          pv = info.pv.clone();
+        }
+        sender.send(SearchEvent::Info(info.clone())).ok();
+      });
+
+      let expected_reply = if pv.first() == Some(&best_move) {
+        pv.get(1).cloned()
+      } else {
+        None
+      };
+
+      sender
+        .send(SearchEvent::Done {
+          score,
+          best_move,
+          expected_reply,
+        })
+        .ok();
+
+      tt
+    });
+
+    Self { signals, events, handle }
+  }
+
+  pub fn signals(&self) -> &SearchSignals {
+    &self.signals
+  }
+
+  // None after Done was received
+  pub async fn next_event(&mut self) -> Option<SearchEvent> {
+    self.events.recv().await
+  }
+
+  // Can't be used inside the async runtime, only on other threads
+  pub fn blocking_next_event(&mut self) -> Option<SearchEvent> {
+    self.events.blocking_recv()
+  }
+
+  // Stops the search if it is still running
+  pub fn finish(self) -> TranspositionTable {
+    self.signals.stop();
+    self.handle.join().expect("Search thread panicked")
+  }
+}
@@ -0,0 +1,50 @@
+use crate::{tcp, uci};
+use std::io;
+use tokio::net::{TcpListener, TcpStream};
+
+// Serves every client on its own task with its own game, so one long running engine can play many games at once.
+// Clients either speak UCI or send the interface packets
+pub async fn run(host: &str, port: u16) -> io::Result<()> {
+  let listener = TcpListener::bind((host, port)).await?;
+  log!(Info, "- Listening on {}", listener.local_addr()?);
+  serve(listener).await
+}
+
+pub async fn serve(listener: TcpListener) -> io::Result<()> {
+  loop {
+    // Failing to accept one client (e.g. out of file descriptors) shouldn't stop serving the others
+    let (stream, address) = match listener.accept().await {
+      Ok(client) => client,
+      Err(error) => {
+        log!(Error, "- Couldn't accept a client: {error}");
+        continue;
+      }
+    };
+
+    log!(Info, "- {address} connected");
+    tokio::spawn(async move {
+      if let Err(error) = handle(stream).await {
+        log!(Error, "- {address}: {error}");
+      }
+      log!(Info, "- {address} disconnected");
+    });
+  }
+}
+
+// A UCI session always starts with "uci", while a fen never starts with 'u'
+async fn handle(stream: TcpStream) -> io::Result<()> {
+  let mut first = [0_u8; 1];
+  if stream.peek(&mut first).await? == 0 {
+    return Ok(());
+  }
+
+  if first[0] == b'u' {
+    // The UCI loop is blocking, so it gets a blocking socket and thread
+    let stream = stream.into_std()?;
+    stream.set_nonblocking(false)?;
+    tokio::task::spawn_blocking(move || uci::run_tcp(stream)).await?
+  } else {
+    tcp::play(stream).await;
+    Ok(())
+  }
+}
@@ -0,0 +1,195 @@
+use crate::board::{Board, Move};
+use crate::config;
+use hashbrown::HashSet;
+use shakmaty::{fen::Fen, uci::Uci, CastlingMode, Chess, Position};
+use shakmaty_syzygy::{Dtz, Material, SyzygyError, Tablebase, Wdl};
+use std::{
+  fmt,
+  path::Path,
+  sync::{Arc, OnceLock, RwLock},
+};
+
+// The biggest tables there are
+pub const MAX_PIECES: usize = 7;
+
+// Syzygy endgame tablebases (https://www.chessprogramming.org/Syzygy_Bases) loaded from local files.
+// WDL tables tell whether a position is won, drawn or lost, DTZ tables how far the next capture or pawn move is
+pub struct Tablebases {
+  tables: Tablebase<Chess>,
+  path: String,
+  // Positions with more pieces aren't probed, even if there are tables for them
+  probe_limit: usize,
+  // Tables which couldn't be read (corrupt or truncated files), logged once and not probed again
+  failed: RwLock<HashSet<String>>,
+}
+
+impl fmt::Debug for Tablebases {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(f, "Tablebases({}, up to {} pieces)", self.path, self.max_pieces())
+  }
+}
+
+// What a position is worth with perfect play, counting the 50-move rule
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Outcome {
+  Loss,
+  Draw,
+  Win,
+}
+
+impl Outcome {
+  fn from_wdl(wdl: Wdl) -> Outcome {
+    match wdl {
+      Wdl::Win => Outcome::Win,
+      Wdl::Loss => Outcome::Loss,
+      // Wins and losses which take too long are draws because of the 50-move rule
+      Wdl::CursedWin | Wdl::Draw | Wdl::BlessedLoss => Outcome::Draw,
+    }
+  }
+}
+
+impl Tablebases {
+  // Loads the tables of every directory in the path, separated by ':' (';' on Windows) like in other engines
+  pub fn open(path: &str, probe_limit: usize) -> Result<Tablebases, String> {
+    let separator = if cfg!(windows) { ';' } else { ':' };
+    let mut tables = Tablebase::new();
+
+    for directory in path.split(separator).filter(|directory| !directory.is_empty()) {
+      if !Path::new(directory).is_dir() {
+        return Err(format!("syzygy directory '{directory}' not found"));
+      }
+      tables
+        .add_directory(directory)
+        .map_err(|error| format!("can't read syzygy directory '{directory}': {error}"))?;
+    }
+
+    if tables.max_pieces() == 0 {
+      return Err(format!("no syzygy tables in '{path}'"));
+    }
+
+    Ok(Tablebases {
+      tables,
+      path: path.to_string(),
+      probe_limit: probe_limit.min(MAX_PIECES),
+      failed: RwLock::new(HashSet::new()),
+    })
+  }
+
+  // The most pieces (kings included) of a position which gets probed
+  pub fn max_pieces(&self) -> usize {
+    self.tables.max_pieces().min(self.probe_limit)
+  }
+
+  // Counting the pieces is cheap, so it's done before converting the position
+  fn position(&self, board: &Board) -> Option<Chess> {
+    if board.piece_count() > self.max_pieces() {
+      return None;
+    }
+
+    // The tables don't have positions where castling is still possible
+    board
+      .to_fen()
+      .parse::<Fen>()
+      .ok()?
+      .into_position(CastlingMode::Standard)
+      .ok()
+      .filter(|pos: &Chess| pos.castles().is_empty() && !self.has_failed(pos))
+  }
+
+  // The table of the position (with either side as white) couldn't be read before
+  fn has_failed(&self, pos: &Chess) -> bool {
+    let failed = self.failed.read().expect("Probes don't panic");
+    if failed.is_empty() {
+      return false;
+    }
+
+    let material = Material::from_board(pos.board()).to_string();
+    let (white, black) = material.split_once('v').expect("Materials have two sides");
+    failed.contains(&material) || failed.contains(&format!("{black}v{white}"))
+  }
+
+  // A missing table only means the position isn't probed, a table which can't be read gets logged the first time
+  fn checked<T>(&self, result: Result<T, SyzygyError>) -> Option<T> {
+    match result {
+      Ok(value) => Some(value),
+      Err(SyzygyError::ProbeFailed { material, error, .. }) => {
+        if self.failed.write().expect("Probes don't panic").insert(material.to_string()) {
+          log!(Error, "- Syzygy: can't read the {material} table: {error}");
+        }
+        None
+      }
+      Err(_) => None,
+    }
+  }
+
+  // The outcome for the side to move. Only right after a capture or a pawn move (or when nothing was played yet
+  // since one), because WDL tables don't know how long ago that was
+  pub fn probe_wdl(&self, board: &Board) -> Option<Outcome> {
+    if board.halfmove_clock() != 0 {
+      return None;
+    }
+
+    let pos = self.position(board)?;
+    self.checked(self.tables.probe_wdl_after_zeroing(&pos)).map(Outcome::from_wdl)
+  }
+
+  // The root moves which keep the best outcome, found with the DTZ tables. Wins have to be completed
+  // before the 50-move rule draws the game, and when losing only the moves resisting the longest are kept.
+  // None if the position can't be probed
+  pub fn root_moves(&self, board: &mut Board) -> Option<(Outcome, Vec<Move>)> {
+    let pos = self.position(board)?;
+    let halfmove_clock = board.halfmove_clock() as i32;
+    let mut ranked = vec![];
+
+    for chess_move in board.legal_moves() {
+      let reference_move = chess_move.to_fen().parse::<Uci>().ok()?.to_move(&pos).ok()?;
+      let zeroing = reference_move.is_zeroing();
+      let child = pos.clone().play(&reference_move).ok()?;
+
+      // The DTZ of the position after the move is from the opponent's side, negative when they lose
+      let rank = if child.is_checkmate() {
+        (Outcome::Win, 0)
+      } else {
+        let Dtz(dtz) = self.checked(self.tables.probe_dtz(&child))?.ignore_rounding();
+        let clock = if zeroing { 0 } else { halfmove_clock + 1 };
+
+        if dtz == 0 || clock + dtz.abs() > 100 {
+          (Outcome::Draw, 0)
+        } else if dtz < 0 {
+          (Outcome::Win, 0)
+        } else {
+          (Outcome::Loss, dtz)
+        }
+      };
+
+      ranked.push((rank, chess_move));
+    }
+
+    let best = ranked.iter().map(|(rank, _)| *rank).max()?;
+    let moves = ranked.into_iter().filter(|(rank, _)| *rank == best).map(|(_, m)| m).collect();
+
+    Some((best.0, moves))
+  }
+}
+
+static CONFIGURED: OnceLock<Option<Arc<Tablebases>>> = OnceLock::new();
+
+// The tables at syzygy.path, loaded the first time they are needed. None if the path is empty or has no tables
+pub fn configured() -> Option<Arc<Tablebases>> {
+  CONFIGURED
+    .get_or_init(|| {
+      let syzygy = &config::get().syzygy;
+      if syzygy.path.is_empty() {
+        return None;
+      }
+
+      match Tablebases::open(&syzygy.path, syzygy.probe_limit) {
+        Ok(tablebases) => Some(Arc::new(tablebases)),
+        Err(error) => {
+          log!(Error, "- Syzygy: {error}");
+          None
+        }
+      }
+    })
+    .clone()
+}
@@ -0,0 +1,649 @@
+// Writes the small Syzygy tables in tests/syzygy. Every position is solved by retrograde analysis, and the values
+// are compressed like in the real tables: common pairs of symbols become new symbols, which get Huffman codes
+use hashbrown::HashMap;
+use shakmaty::{CastlingMode, Chess, Color, FromSetup, Piece, Position, Role, Setup, Square};
+use shakmaty_syzygy::{Material, Wdl};
+use std::{cmp::Reverse, collections::BinaryHeap, fs, io, path::Path};
+
+// The tables, each one after those its pawn can promote to
+pub const NAMES: [&str; 5] = ["KQvK", "KRvK", "KBvK", "KNvK", "KPvK"];
+
+// Bytes in a block of values, and how many values are between the entries of the sparse index, as powers of two
+const BLOCK_SIZE: u8 = 5;
+const SPAN: u8 = 6;
+
+// The first bytes of the table files
+const WDL_MAGIC: [u8; 4] = [0x71, 0xe8, 0x23, 0x5d];
+const DTZ_MAGIC: [u8; 4] = [0xd7, 0x66, 0x0c, 0xa5];
+
+// Flags of a table (the sides have different pieces, there are pawns) and of its parts (DTZ values of wins
+// and losses in plies, every position has the same value)
+const SPLIT: u8 = 1;
+const HAS_PAWNS: u8 = 2;
+const WIN_PLIES: u8 = 4;
+const LOSS_PLIES: u8 = 8;
+const SINGLE_VALUE: u8 = 128;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Kind {
+  Wdl,
+  Dtz,
+}
+
+// Every legal position of a table, with white as the stronger side
+pub struct Solved {
+  pub name: &'static str,
+  // The pieces of the table, every square of them and the side to move make the key of a position
+  pieces: Vec<Piece>,
+  keys: Vec<usize>,
+  pub wdl: Vec<Wdl>,
+  pub dtz: Vec<i32>,
+}
+
+// A move to a position of the same table, or the result of a position with other pieces
+#[derive(Clone, Copy)]
+enum Child {
+  Same { slot: usize, zeroing: bool },
+  Other(Wdl),
+}
+
+// The pieces of a table name, white first
+fn pieces(name: &str) -> Vec<Piece> {
+  let (white, black) = name.split_once('v').expect("Table names have two sides");
+  let side = |side: &str, color: Color| -> Vec<Piece> {
+    side
+      .chars()
+      .map(|piece| Piece {
+        color,
+        role: Role::from_char(piece).expect("Table names only have pieces"),
+      })
+      .collect()
+  };
+
+  [side(white, Color::White), side(black, Color::Black)].concat()
+}
+
+impl Solved {
+  fn key(&self, pos: &Chess) -> usize {
+    self.pieces.iter().fold(pos.turn().is_black() as usize, |key, piece| {
+      let square = pos.board().by_piece(*piece).first().expect("Every piece is on the board");
+      key * 64 + usize::from(square)
+    })
+  }
+
+  pub fn position(&self, key: usize) -> Option<Chess> {
+    let mut setup = Setup::empty();
+    let mut rest = key;
+    for piece in self.pieces.iter().rev() {
+      let square = Square::new((rest % 64) as u32);
+      if setup.board.piece_at(square).is_some() {
+        return None;
+      }
+      setup.board.set_piece_at(square, *piece);
+      rest /= 64;
+    }
+    setup.turn = if rest == 1 { Color::Black } else { Color::White };
+
+    Chess::from_setup(setup, CastlingMode::Standard).ok()
+  }
+
+  // The positions with their results
+  pub fn positions(&self) -> impl Iterator<Item = (Chess, Wdl, i32)> + '_ {
+    (0..self.keys.len()).map(|slot| {
+      let pos = self.position(self.keys[slot]).expect("Solved positions are legal");
+      (pos, self.wdl[slot], self.dtz[slot])
+    })
+  }
+}
+
+// Solves the tables in order
+pub fn solve() -> Vec<Solved> {
+  let mut solved: Vec<Solved> = vec![];
+  for name in NAMES {
+    let table = solve_table(name, &solved);
+    solved.push(table);
+  }
+  solved
+}
+
+fn solve_table(name: &'static str, solved: &[Solved]) -> Solved {
+  let mut table = Solved {
+    name,
+    pieces: pieces(name),
+    keys: vec![],
+    wdl: vec![],
+    dtz: vec![],
+  };
+  assert!(table.pieces.len() == 3, "Only tables with different pieces can be solved");
+
+  let positions: Vec<(usize, Chess)> = (0..2 * 64 * 64 * 64)
+    .filter_map(|key| table.position(key).map(|pos| (key, pos)))
+    .collect();
+  let slots: HashMap<usize, usize> = positions.iter().enumerate().map(|(slot, (key, _))| (*key, slot)).collect();
+  table.keys = positions.iter().map(|(key, _)| *key).collect();
+
+  // The results of captures and promotions come from the tables solved before
+  let other = |pos: &Chess| -> Wdl {
+    if pos.is_insufficient_material() {
+      return Wdl::Draw;
+    }
+    let material = Material::from_board(pos.board()).to_string();
+    let other = solved
+      .iter()
+      .find(|other| other.name == material)
+      .expect("Tables are solved in order");
+    other.wdl[other.keys.binary_search(&other.key(pos)).expect("Legal positions are solved")]
+  };
+
+  let mut children = vec![];
+  let mut mated = vec![];
+  for (_, pos) in &positions {
+    let moves: Vec<Child> = pos
+      .legal_moves()
+      .iter()
+      .map(|chess_move| {
+        let mut child = pos.clone();
+        child.play_unchecked(chess_move);
+        if Material::from_board(child.board()).to_string() == name {
+          Child::Same {
+            slot: slots[&table.key(&child)],
+            zeroing: chess_move.is_zeroing(),
+          }
+        } else {
+          Child::Other(other(&child))
+        }
+      })
+      .collect();
+    mated.push(pos.is_checkmate());
+    children.push(moves);
+  }
+
+  // Wins have a move to a lost position, losses only moves to won ones, and the rest are draws
+  let mut wdl: Vec<Option<Wdl>> = mated
+    .iter()
+    .zip(&children)
+    .map(|(mated, moves)| match (mated, moves.is_empty()) {
+      (true, _) => Some(Wdl::Loss),
+      (false, true) => Some(Wdl::Draw),
+      _ => None,
+    })
+    .collect();
+  let mut changed = true;
+  while changed {
+    changed = false;
+    for slot in 0..positions.len() {
+      if wdl[slot].is_some() {
+        continue;
+      }
+
+      let results: Vec<Option<Wdl>> = children[slot]
+        .iter()
+        .map(|child| match child {
+          Child::Same { slot, .. } => wdl[*slot],
+          Child::Other(wdl) => Some(*wdl),
+        })
+        .collect();
+      if results.contains(&Some(Wdl::Loss)) {
+        wdl[slot] = Some(Wdl::Win);
+        changed = true;
+      } else if results.iter().all(|result| *result == Some(Wdl::Win)) {
+        wdl[slot] = Some(Wdl::Loss);
+        changed = true;
+      }
+    }
+  }
+  table.wdl = wdl.into_iter().map(|wdl| wdl.unwrap_or(Wdl::Draw)).collect();
+
+  // The DTZ of wins and losses, one ply more every time: a win takes the fastest move to a loss, and a loss
+  // the slowest move. Captures, pawn moves and mates count as one ply
+  table.dtz = mated.iter().map(|mated| if *mated { -1 } else { 0 }).collect();
+  for plies in 1.. {
+    let mut found = vec![];
+    for (slot, moves) in children.iter().enumerate() {
+      if table.dtz[slot] != 0 || table.wdl[slot] == Wdl::Draw {
+        continue;
+      }
+
+      let distance = |child: &Child| -> Option<i32> {
+        match *child {
+          Child::Same { slot, zeroing } if zeroing || mated[slot] => Some(1),
+          Child::Same { slot, .. } if table.dtz[slot] != 0 => Some(table.dtz[slot].abs() + 1),
+          Child::Same { .. } => None,
+          Child::Other(_) => Some(1),
+        }
+      };
+      let lost = |child: &Child| match *child {
+        Child::Same { slot, .. } => table.wdl[slot] == Wdl::Loss,
+        Child::Other(wdl) => wdl == Wdl::Loss,
+      };
+
+      let dtz = if table.wdl[slot] == Wdl::Win {
+        moves.iter().filter(|child| lost(child)).filter_map(distance).min()
+      } else {
+        moves
+          .iter()
+          .map(distance)
+          .collect::<Option<Vec<i32>>>()
+          .and_then(|distances| distances.into_iter().max())
+      };
+      if dtz == Some(plies) {
+        found.push((slot, if table.wdl[slot] == Wdl::Win { plies } else { -plies }));
+      }
+    }
+
+    for (slot, dtz) in found {
+      table.dtz[slot] = dtz;
+    }
+    if (0..positions.len()).all(|slot| table.dtz[slot] != 0 || table.wdl[slot] == Wdl::Draw) {
+      break;
+    }
+    assert!(plies < 100, "The 50-move rule doesn't matter in the solved tables");
+  }
+
+  table
+}
+
+// The compressed values of a part of a table, and what's written before them
+struct Part {
+  sizes: Vec<u8>,
+  sparse_index: Vec<u8>,
+  block_lengths: Vec<u8>,
+  blocks: Vec<u8>,
+}
+
+// A value, or two other symbols standing for their values one after the other
+#[derive(Clone, Copy)]
+enum Symbol {
+  Value(u16),
+  Pair(usize, usize),
+}
+
+// The most common pair of neighbouring symbols becomes a new symbol, over and over. Symbols are numbered with
+// 12 bits (0xfff is taken) and can stand for at most 256 values
+fn pair(values: &[u16]) -> (Vec<Symbol>, Vec<usize>) {
+  let mut symbols = vec![];
+  let mut lengths = vec![];
+  let mut leaves: HashMap<u16, usize> = HashMap::new();
+  let mut sequence = vec![];
+  for value in values {
+    let symbol = *leaves.entry(*value).or_insert_with(|| {
+      symbols.push(Symbol::Value(*value));
+      lengths.push(1);
+      symbols.len() - 1
+    });
+    sequence.push(symbol);
+  }
+
+  while symbols.len() < 0xfff {
+    let mut counts: HashMap<(usize, usize), usize> = HashMap::new();
+    let mut i = 0;
+    while i + 1 < sequence.len() {
+      let pair = (sequence[i], sequence[i + 1]);
+      *counts.entry(pair).or_default() += 1;
+      // A run of the same symbol has half as many pairs which don't overlap
+      i += if pair.0 == pair.1 && sequence.get(i + 2) == Some(&pair.0) {
+        2
+      } else {
+        1
+      };
+    }
+
+    let Some(((first, second), count)) = counts
+      .into_iter()
+      .filter(|((first, second), _)| lengths[*first] + lengths[*second] <= 256)
+      .max_by_key(|(pair, count)| (*count, Reverse(*pair)))
+    else {
+      break;
+    };
+    if count < 4 {
+      break;
+    }
+
+    let symbol = symbols.len();
+    symbols.push(Symbol::Pair(first, second));
+    lengths.push(lengths[first] + lengths[second]);
+
+    let mut paired = Vec::with_capacity(sequence.len());
+    let mut i = 0;
+    while i < sequence.len() {
+      if sequence.get(i + 1) == Some(&second) && sequence[i] == first {
+        paired.push(symbol);
+        i += 2;
+      } else {
+        paired.push(sequence[i]);
+        i += 1;
+      }
+    }
+    sequence = paired;
+  }
+
+  (symbols, sequence)
+}
+
+// Huffman code lengths of the symbols
+fn code_lengths(counts: &[usize]) -> Vec<u32> {
+  let mut lengths = vec![0; counts.len()];
+  let mut heap: BinaryHeap<Reverse<(usize, Vec<usize>)>> = counts
+    .iter()
+    .enumerate()
+    .map(|(i, count)| Reverse((*count, vec![i])))
+    .collect();
+
+  while heap.len() > 1 {
+    let Reverse((first_count, first)) = heap.pop().expect("The heap has two nodes");
+    let Reverse((second_count, second)) = heap.pop().expect("The heap has two nodes");
+    for &i in first.iter().chain(&second) {
+      lengths[i] += 1;
+    }
+    heap.push(Reverse((first_count + second_count, [first, second].concat())));
+  }
+
+  lengths
+}
+
+fn compress(values: &[u16], flags: u8) -> Part {
+  if values.iter().all(|value| *value == values[0]) {
+    return Part {
+      sizes: vec![flags | SINGLE_VALUE, values[0] as u8],
+      sparse_index: vec![],
+      block_lengths: vec![],
+      blocks: vec![],
+    };
+  }
+
+  // Every symbol gets a code, also those only used in pairs, which are counted once so that the codes stay short
+  let (symbols, sequence) = pair(values);
+  let mut counts = vec![1; symbols.len()];
+  for symbol in &sequence {
+    counts[*symbol] += 1;
+  }
+  let values_of = |symbol: usize| -> usize {
+    let mut stack = vec![symbol];
+    let mut count = 0;
+    while let Some(symbol) = stack.pop() {
+      match symbols[symbol] {
+        Symbol::Value(_) => count += 1,
+        Symbol::Pair(first, second) => stack.extend([first, second]),
+      }
+    }
+    count
+  };
+
+  // Longer codes have lower numbers, and lower codes
+  let lengths = code_lengths(&counts);
+  let mut order: Vec<usize> = (0..symbols.len()).collect();
+  order.sort_by_key(|symbol| (Reverse(lengths[*symbol]), *symbol));
+  let mut numbers = vec![0; symbols.len()];
+  for (number, symbol) in order.iter().enumerate() {
+    numbers[*symbol] = number;
+  }
+  let min_length = lengths[order[order.len() - 1]];
+  let max_length = lengths[order[0]];
+  assert!(max_length <= 32, "Codes fit in 32 bits");
+
+  let count = |length: u32| order.iter().filter(|symbol| lengths[**symbol] == length).count() as u64;
+  let code_count = (max_length - min_length + 1) as usize;
+  let lowest: Vec<u64> = (0..code_count)
+    .map(|i| {
+      order
+        .iter()
+        .filter(|symbol| lengths[**symbol] > min_length + i as u32)
+        .count() as u64
+    })
+    .collect();
+  let mut bases = vec![0; code_count];
+  for i in (0..code_count - 1).rev() {
+    let codes = bases[i + 1] + count(min_length + i as u32 + 1);
+    assert!(codes.is_multiple_of(2), "Huffman codes are complete");
+    bases[i] = codes / 2;
+  }
+  let code = |symbol: usize| -> (u32, u64) {
+    let i = (lengths[symbol] - min_length) as usize;
+    (lengths[symbol], bases[i] + numbers[symbol] as u64 - lowest[i])
+  };
+
+  // Blocks of whole codes, the first bit highest, with at most 65536 values
+  let block_bits = 8 << BLOCK_SIZE;
+  let mut blocks: Vec<Vec<u8>> = vec![];
+  let mut block_values: Vec<u64> = vec![];
+  let mut bits = block_bits;
+  for symbol in &sequence {
+    let (length, code) = code(*symbol);
+    let symbol_values = values_of(*symbol) as u64;
+    if bits + length as usize > block_bits || block_values.last().is_some_and(|count| count + symbol_values > 65536) {
+      blocks.push(vec![0; 1 << BLOCK_SIZE]);
+      block_values.push(0);
+      bits = 0;
+    }
+
+    let block = blocks.last_mut().expect("There is a block");
+    for bit in (0..length).rev() {
+      if code >> bit & 1 != 0 {
+        block[bits / 8] |= 0x80 >> (bits % 8);
+      }
+      bits += 1;
+    }
+    *block_values.last_mut().expect("There is a block") += symbol_values;
+  }
+
+  // The block and the offset in it of every span-th value, starting at span / 2
+  let span = 1 << SPAN;
+  let mut sparse_index = vec![];
+  for k in 0..(values.len() as u64).div_ceil(span) {
+    let mut target = k * span + span / 2;
+    let mut block = 0;
+    while block < block_values.len() && target >= block_values[block] {
+      target -= block_values[block];
+      block += 1;
+    }
+    sparse_index.extend((block as u32).to_le_bytes());
+    sparse_index.extend((target as u16).to_le_bytes());
+  }
+
+  let mut sizes = vec![flags, BLOCK_SIZE, SPAN, 0];
+  sizes.extend((blocks.len() as u32).to_le_bytes());
+  sizes.extend([max_length as u8, min_length as u8]);
+  for symbol in &lowest {
+    sizes.extend((*symbol as u16).to_le_bytes());
+  }
+  sizes.extend((symbols.len() as u16).to_le_bytes());
+  for symbol in &order {
+    // 12 bits for each half, a value on the left and 0xfff on the right for leaves
+    let (left, right) = match symbols[*symbol] {
+      Symbol::Value(value) => (value as usize, 0xfff),
+      Symbol::Pair(first, second) => (numbers[first], numbers[second]),
+    };
+    sizes.extend([left as u8, (left >> 8) as u8 | (right << 4) as u8, (right >> 4) as u8]);
+  }
+  if symbols.len() % 2 == 1 {
+    sizes.push(0);
+  }
+
+  Part {
+    sizes,
+    sparse_index,
+    block_lengths: block_values
+      .iter()
+      .flat_map(|values| ((values - 1) as u16).to_le_bytes())
+      .collect(),
+    blocks: blocks.concat(),
+  }
+}
+
+// Pieces in table files: 1 to 6 from pawn to king, and 8 more for black
+fn piece_code(piece: Piece) -> u8 {
+  piece.role as u8 | if piece.color == Color::Black { 8 } else { 0 }
+}
+
+// How far a square is above the a1-h8 diagonal
+fn off_diagonal(square: usize) -> i64 {
+  (square >> 3) as i64 - (square & 7) as i64
+}
+
+// The squares below the diagonal to 0..28
+fn below_diagonal(square: usize) -> usize {
+  (0..square).filter(|other| off_diagonal(*other) < 0).count()
+}
+
+// The a1-d1-d4 triangle to 0..10: b1, c1, d1, c2, d2 and d3, then the diagonal
+fn triangle(square: usize) -> usize {
+  if off_diagonal(square) == 0 {
+    6 + (square >> 3)
+  } else {
+    [1, 2, 3, 10, 11, 19]
+      .iter()
+      .position(|other| *other == square)
+      .expect("The square is in the triangle")
+  }
+}
+
+// The positions of a part of the table: the pawn on one file (its rank and the squares left for the kings),
+// or without pawns the 31332 ways to place three different pieces
+fn part_size(has_pawns: bool) -> usize {
+  if has_pawns {
+    6 * 63 * 62
+  } else {
+    31332
+  }
+}
+
+// Where a position is stored: the side to move, the file of the pawn and the index in that part. The same
+// encoding as in every Syzygy prober, for three different pieces in the order of the table
+fn index(pieces: &[Piece], pos: &Chess) -> (usize, usize, usize) {
+  let side = pos.turn().is_black() as usize;
+  let mut squares: Vec<usize> = pieces
+    .iter()
+    .map(|piece| usize::from(pos.board().by_piece(*piece).first().expect("Every piece is on the board")))
+    .collect();
+
+  // The first piece goes to the files a to d
+  if squares[0] & 7 > 3 {
+    squares.iter_mut().for_each(|square| *square ^= 7);
+  }
+
+  // Squares are counted without those of the pieces before
+  let free = |squares: &[usize], i: usize| squares[i] - squares[..i].iter().filter(|other| **other < squares[i]).count();
+  if pieces[0].role == Role::Pawn {
+    let index = (squares[0] >> 3) - 1 + 6 * free(&squares, 1) + 6 * 63 * free(&squares, 2);
+    return (side, squares[0] & 7, index);
+  }
+
+  // Without pawns, the first piece also goes to the ranks 1 to 4 and below the diagonal
+  if squares[0] >> 3 > 3 {
+    squares.iter_mut().for_each(|square| *square ^= 56);
+  }
+  if let Some(i) = (0..3).find(|i| off_diagonal(squares[*i]) != 0) {
+    if off_diagonal(squares[i]) > 0 {
+      squares[i..]
+        .iter_mut()
+        .for_each(|square| *square = ((*square >> 3) | (*square << 3)) & 63);
+    }
+  }
+
+  let [first, second, third] = [0, 1, 2].map(|i| squares[i]);
+  let rank = |square: usize| square >> 3;
+  let adjust1 = (second > first) as usize;
+  let adjust2 = (third > first) as usize + (third > second) as usize;
+  let index = if off_diagonal(first) != 0 {
+    (triangle(first) * 63 + second - adjust1) * 62 + third - adjust2
+  } else if off_diagonal(second) != 0 {
+    (6 * 63 + rank(first) * 28 + below_diagonal(second)) * 62 + third - adjust2
+  } else if off_diagonal(third) != 0 {
+    6 * 63 * 62 + 4 * 28 * 62 + rank(first) * 7 * 28 + (rank(second) - adjust1) * 28 + below_diagonal(third)
+  } else {
+    6 * 63 * 62 + 4 * 28 * 62 + 4 * 7 * 28 + rank(first) * 7 * 6 + (rank(second) - adjust1) * 6 + rank(third) - adjust2
+  };
+
+  (side, 0, index)
+}
+
+// Writes the WDL and DTZ tables. DTZ tables only have white to move, in plies
+pub fn write(directory: &Path, solved: &Solved) -> io::Result<()> {
+  let name = solved.name;
+  let has_pawns = name.contains('P');
+
+  // The pawn comes first
+  let mut pieces = solved.pieces.clone();
+  pieces.sort_by_key(|piece| piece.role != Role::Pawn);
+
+  for kind in [Kind::Wdl, Kind::Dtz] {
+    let flags = if kind == Kind::Dtz { WIN_PLIES | LOSS_PLIES } else { 0 };
+    let (sides, files) = (if kind == Kind::Wdl { 2 } else { 1 }, if has_pawns { 4 } else { 1 });
+
+    let mut values: Vec<Vec<Vec<Option<u16>>>> = vec![vec![vec![None; part_size(has_pawns)]; files]; sides];
+    for (pos, wdl, dtz) in solved.positions() {
+      let (side, file, index) = index(&pieces, &pos);
+      if side >= sides {
+        continue;
+      }
+      let value = match kind {
+        Kind::Wdl => (wdl as i32 + 2) as u16,
+        // Draws aren't probed
+        Kind::Dtz if wdl == Wdl::Draw => continue,
+        Kind::Dtz => (dtz.abs() - 1) as u16,
+      };
+
+      let stored = &mut values[side][file][index];
+      assert!(
+        stored.is_none_or(|stored| stored == value),
+        "Positions with the same index have the same value"
+      );
+      *stored = Some(value);
+    }
+
+    let mut parts = vec![];
+    for side in values {
+      for values in side {
+        // Positions which can't be probed get the most common value
+        let mut counts: HashMap<u16, usize> = HashMap::new();
+        values
+          .iter()
+          .flatten()
+          .for_each(|value| *counts.entry(*value).or_default() += 1);
+        let common = counts
+          .into_iter()
+          .max_by_key(|(value, count)| (*count, Reverse(*value)))
+          .map_or(0, |(value, _)| value);
+
+        let values: Vec<u16> = values.iter().map(|value| value.unwrap_or(common)).collect();
+        parts.push(compress(&values, flags));
+      }
+    }
+    // In the file, the parts are by file and then by side
+    let order: Vec<usize> = (0..files)
+      .flat_map(|file| (0..sides).map(move |side| side * files + file))
+      .collect();
+
+    let mut bytes = if kind == Kind::Wdl {
+      WDL_MAGIC.to_vec()
+    } else {
+      DTZ_MAGIC.to_vec()
+    };
+    bytes.push(SPLIT | if has_pawns { HAS_PAWNS } else { 0 });
+    for _ in 0..files {
+      // The leading group is encoded first on both sides
+      bytes.push(0);
+      bytes.extend(pieces.iter().map(|piece| piece_code(*piece) | piece_code(*piece) << 4));
+    }
+    let align = |bytes: &mut Vec<u8>, alignment: usize| bytes.resize(bytes.len().div_ceil(alignment) * alignment, 0);
+
+    align(&mut bytes, 2);
+    order.iter().for_each(|i| bytes.extend(&parts[*i].sizes));
+    align(&mut bytes, 2);
+    order.iter().for_each(|i| bytes.extend(&parts[*i].sparse_index));
+    order.iter().for_each(|i| bytes.extend(&parts[*i].block_lengths));
+    for i in &order {
+      align(&mut bytes, 64);
+      bytes.extend(&parts[*i].blocks);
+    }
+
+    // Probers only check that the 16 byte checksum at the end is there
+    align(&mut bytes, 64);
+    bytes.extend([0; 16]);
+
+    let extension = if kind == Kind::Wdl { "rtbw" } else { "rtbz" };
+    fs::write(directory.join(format!("{name}.{extension}")), bytes)?;
+  }
+
+  Ok(())
+}
@@ -0,0 +1,317 @@
+use crate::bitboard::Color;
+use crate::board::{Board, GameStatus, Move};
+use crate::book::try_find_opening;
+use crate::search::{format_score, time_for_move, SearchLimits, SearchSignals, SearchStats, TranspositionTable};
+use crate::search_thread::{SearchEvent, SearchThread};
+use std::{
+  io,
+  time::{Duration, Instant},
+};
+use tokio::{
+  io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+  net::{tcp::OwnedWriteHalf, TcpStream},
+};
+
+const FIRST_RETRY_DELAY: Duration = Duration::from_millis(100);
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(5);
+
+// Tries to connect to the interface, waiting twice as long after every failed attempt
+pub async fn connect(host: &str, port: u16, attempts: u32) -> Option<TcpStream> {
+  let mut delay = FIRST_RETRY_DELAY;
+
+  for attempt in 1..=attempts {
+    match TcpStream::connect((host, port)).await {
+      Ok(stream) => return Some(stream),
+      Err(error) => log!(Info, "- Couldn't connect to {host}:{port} ({attempt}/{attempts}): {error}"),
+    }
+
+    if attempt < attempts {
+      tokio::time::sleep(delay).await;
+      delay = (delay * 2).min(MAX_RETRY_DELAY);
+    }
+  }
+
+  None
+}
+
+pub struct Packet {
+  pub fen: String,
+  // time is in ms
+  pub white_time_left: isize,
+  pub black_time_left: isize,
+}
+
+// "<fen> <white time left> <black time left>"
+pub fn decode_packet(packet: &str) -> Result<Packet, String> {
+  let strings: Vec<&str> = packet.split_whitespace().collect();
+  if strings.len() != 8 {
+    return Err(format!("expected 8 fields, got {}", strings.len()));
+  }
+
+  let fen = Board::normalize_fen(&strings[..6].join(" "))?;
+
+  let time_left = |time: &str| time.parse::<isize>().map_err(|_| format!("invalid time left '{time}'"));
+
+  Ok(Packet {
+    fen,
+    white_time_left: time_left(strings[6])?,
+    black_time_left: time_left(strings[7])?,
+  })
+}
+
+// A search started by the game, either for the position we have to move in,
+// or pondering on the position after the expected reply
+struct TcpSearch {
+  thread: SearchThread,
+  board: Board,
+  // When the position arrived and how much time we had left then
+  start_time: Instant,
+  time_left: isize,
+  pondering: bool,
+  // A ponder search can be done before its position arrives
+  result: Option<(i32, Move, Option<Move>)>,
+  stats: SearchStats,
+}
+
+impl TcpSearch {
+  fn start(board: Board, limits: SearchLimits, tt: &mut TranspositionTable, start_time: Instant, time_left: isize) -> Self {
+    Self {
+      pondering: limits.signals.is_pondering(),
+      thread: SearchThread::spawn(board.clone(), limits, std::mem::take(tt)),
+      board,
+      start_time,
+      time_left,
+      result: None,
+      stats: SearchStats::default(),
+    }
+  }
+}
+
+struct Game {
+  previous_hashes: Vec<u64>,
+  tt: TranspositionTable,
+  search: Option<TcpSearch>,
+}
+
+// Plays a game against the interface: it sends the position and both times left, we answer with our move.
+// Every packet and answer is a line, so packets can arrive in pieces or several at once.
+// Searches run on their own thread, so packets are handled right away, even in the middle of a search.
+// A "stop" packet makes us play the best move found so far, an invalid one is answered with "error <reason>"
+pub async fn play(stream: TcpStream) {
+  let (reader, mut writer) = stream.into_split();
+  let mut lines = BufReader::new(reader).lines();
+  let mut game = Game {
+    previous_hashes: vec![],
+    tt: TranspositionTable::new(),
+    search: None,
+  };
+
+  log!(Info, "- Waiting for fen...");
+  loop {
+    // next_line is cancel safe, so a partly received packet isn't lost when a search event comes first
+    let result = tokio::select! {
+      line = lines.next_line() => match line {
+        Ok(Some(line)) if line.trim().is_empty() => Ok(()),
+        Ok(Some(line)) => game.on_packet(&mut writer, line.trim()).await,
+        Ok(None) => break,
+        Err(error) => Err(error),
+      },
+      Some(event) = game.next_event() => game.on_search_event(&mut writer, event).await,
+    };
+
+    if let Err(error) = result {
+      log!(Error, "- Connection error: {error}");
+      break;
+    }
+  }
+
+  // Nobody is waiting for its result anymore
+  if let Some(search) = game.search.take() {
+    search.thread.finish();
+  }
+
+  log!(Info, "- Disconnecting");
+  // The interface might be gone already
+  writer.shutdown().await.ok();
+}
+
+impl Game {
+  // Never ready while there is no search
+  async fn next_event(&mut self) -> Option<SearchEvent> {
+    match &mut self.search {
+      Some(search) => search.thread.next_event().await,
+      None => std::future::pending().await,
+    }
+  }
+
+  async fn on_packet(&mut self, writer: &mut OwnedWriteHalf, packet: &str) -> io::Result<()> {
+    let start_time = Instant::now();
+
+    log!(Info, "- Received packet: '{packet}'");
+
+    if packet == "stop" {
+      // The search sends its move once it's done
+      if let Some(search) = self.search.as_ref().filter(|search| !search.pondering) {
+        log!(Info, "- Stopping the search");
+        search.thread.signals().stop();
+      }
+      return Ok(());
+    }
+
+    let packet = match decode_packet(packet) {
+      Ok(packet) => packet,
+      Err(error) => {
+        log!(Error, "- Invalid packet: {error}");
+        return writer.write_all(format!("error {error}\n").as_bytes()).await;
+      }
+    };
+
+    let mut board = Board::from_fen_saved(&packet.fen, self.previous_hashes.clone());
+    // Packets only come when it's our turn, so we play the side to move
+    let time_left = if board.side_to_move == Color::White {
+      packet.white_time_left
+    } else {
+      packet.black_time_left
+    };
+
+    self.previous_hashes.push(board.meta.hash);
+
+    if let Some(mut search) = self.search.take() {
+      // On a ponder hit the search is already for this position, it only has to start counting time
+      if search.pondering && search.board.meta.hash == board.meta.hash {
+        log!(Info, "- Ponder hit");
+        search.thread.signals().ponderhit();
+        search.pondering = false;
+        search.start_time = start_time;
+        search.time_left = time_left;
+
+        match search.result.take() {
+          Some(result) => return self.finish_search(writer, search, result).await,
+          None => self.search = Some(search),
+        }
+        return Ok(());
+      }
+
+      if search.pondering {
+        log!(Info, "- Ponder miss");
+      } else {
+        log!(Info, "- Got a new position, stopping the search");
+      }
+      self.tt = search.thread.finish();
+    }
+
+    let status = board.game_status();
+    if status != GameStatus::Ongoing {
+      log!(Info, "- Game status: {status:?}");
+    }
+    if status == GameStatus::Checkmate || status == GameStatus::Stalemate {
+      log!(Info, "- No legal moves, waiting for the next fen...");
+      return Ok(());
+    }
+
+    if let Some(saved_move) = try_find_opening(&packet.fen).and_then(|saved_move| board.find_move(&saved_move)) {
+      return self.play_move(writer, board, &saved_move, None, start_time, time_left).await;
+    }
+
+    let limits = SearchLimits::time(time_for_move(time_left));
+    self.search = Some(TcpSearch::start(board, limits, &mut self.tt, start_time, time_left));
+    Ok(())
+  }
+
+  async fn on_search_event(&mut self, writer: &mut OwnedWriteHalf, event: SearchEvent) -> io::Result<()> {
+    let Some(search) = &mut self.search else {
+      return Ok(());
+    };
+
+    match event {
+      SearchEvent::Info(info) => {
+        log!(Debug, "{}", info.to_log(&search.board.side_to_move));
+        search.stats = info.stats;
+      }
+      SearchEvent::Done {
+        score,
+        best_move,
+        expected_reply,
+      } => {
+        let result = (score, best_move, expected_reply);
+
+        if search.pondering {
+          search.result = Some(result);
+        } else if let Some(search) = self.search.take() {
+          return self.finish_search(writer, search, result).await;
+        }
+      }
+    }
+
+    Ok(())
+  }
+
+  async fn finish_search(
+    &mut self,
+    writer: &mut OwnedWriteHalf,
+    search: TcpSearch,
+    result: (i32, Move, Option<Move>),
+  ) -> io::Result<()> {
+    let (score, best_move, expected_reply) = result;
+    self.tt = search.thread.finish();
+
+    let eval = if search.board.side_to_move == Color::White {
+      score
+    } else {
+      -score
+    };
+
+    log!(Info, "- Eval: {}", format_score(eval));
+    log!(Info, "- TT size: {}", self.tt.len());
+    log!(Info, "{}", search.stats.to_log());
+
+    let (board, start_time, time_left) = (search.board, search.start_time, search.time_left);
+    self
+      .play_move(writer, board, &best_move, expected_reply, start_time, time_left)
+      .await
+  }
+
+  // Sends our move and starts pondering on the expected reply
+  async fn play_move(
+    &mut self,
+    writer: &mut OwnedWriteHalf,
+    mut board: Board,
+    chess_move: &Move,
+    expected_reply: Option<Move>,
+    start_time: Instant,
+    time_left: isize,
+  ) -> io::Result<()> {
+    // The move is also made on the board, so that the position after it is remembered for repetitions
+    board.make_move(chess_move);
+    self.previous_hashes.push(board.meta.hash);
+
+    let chess_move_fen = chess_move.to_fen();
+
+    log!(Info, "- Sending move: '{chess_move_fen}'...");
+    writer.write_all(format!("{chess_move_fen}\n").as_bytes()).await?;
+    log!(Info, "- Sent move successfully");
+
+    let time_taken = start_time.elapsed();
+    log!(Info, "- Time taken: {:?}", time_taken);
+
+    // The TT is kept for the whole game (the connection), so pondering goes on from what this search found
+    if let Some(reply) = expected_reply {
+      board.make_move(&reply);
+
+      if !board.legal_moves().is_empty() {
+        log!(Info, "- Pondering on '{}'...", reply.to_fen());
+
+        let time_left = time_left - time_taken.as_millis() as isize;
+        let limits = SearchLimits {
+          time: Some(time_for_move(time_left)),
+          signals: SearchSignals::pondering(),
+          ..Default::default()
+        };
+        self.search = Some(TcpSearch::start(board, limits, &mut self.tt, start_time, time_left));
+      }
+    }
+
+    log!(Info, "- Receiving fen...");
+    Ok(())
+  }
+}
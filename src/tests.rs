@@ -1,4 +1,18 @@
-use crate::{bitboard, board};
+use crate::{
+  bitboard, board, cli, differential, epd_test, fuzz, http,
+  mate::{self, MateResult, MateSolver},
+  perft::{self, PerftTable},
+  search::{
+    iterative_deepening, mate_in, time_for_move, RootMoves, SearchLimits, SearchSignals, SearchStats, TranspositionTable,
+    CHECKMATE,
+  },
+  search_thread::{SearchEvent, SearchThread},
+  server,
+  syzygy::{Outcome, Tablebases},
+  syzygy_fixtures, tcp, uci,
+};
+use proptest::{prelude::*, sample::select};
+use std::sync::Arc;
 
 #[test]
 fn is_same() {
@@ -13,8 +27,8 @@ fn is_same() {
   let moves = board.pseudo_legal_moves();
   let moves_bb = board_bb.pseudo_legal_moves();
 
-  let c = board.side_to_move.clone();
-  let c_bb = if board_bb.white_to_move { 0 } else { 1 };
+  let c = board.side_to_move;
+  let c_bb = board_bb.side_to_move;
 
   for m in &moves {
     board.make_move(m);
@@ -51,3 +65,877 @@ fn is_same() {
 
   assert_eq!(count, count_bb);
 }
+
+// https://www.chessprogramming.org/Perft_Results
+const PERFT_POSITIONS: [(&str, &[u64]); 7] = [
+  // Position 1
+  (
+    "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+    &[1, 20, 400, 8_902, 197_281, 4_865_609, 119_060_324],
+  ),
+  // Position 2
+  (
+    "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+    &[1, 48, 2_039, 97_862, 4_085_603, 193_690_690],
+  ),
+  // Position 3
+  (
+    "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+    &[1, 14, 191, 2_812, 43_238, 674_624, 11_030_083],
+  ),
+  // Position 4
+  (
+    "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+    &[1, 6, 264, 9_467, 422_333, 15_833_292],
+  ),
+  // Position 4 mirrored
+  (
+    "r2q1rk1/pP1p2pp/Q4n2/bbp1p3/Np6/1B3NBn/pPPP1PPP/R3K2R b KQ - 0 1",
+    &[1, 6, 264, 9_467, 422_333, 15_833_292],
+  ),
+  // Position 5
+  (
+    "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8",
+    &[1, 44, 1_486, 62_379, 2_103_487, 89_941_194],
+  ),
+  // Position 6
+  (
+    "r4rk1/1pp1qppp/p1np1n2/2b1p1B1/2B1P1b1/P1NP1N2/1PP1QPPP/R4RK1 w - - 0 10",
+    &[1, 46, 2_079, 89_890, 3_894_594, 164_075_551],
+  ),
+];
+
+#[test]
+fn perft_test() {
+  for (fen, depth_nodes) in PERFT_POSITIONS {
+    let mut board = board::Board::from_fen(fen);
+    let board_bb = bitboard::Board::from_fen(fen);
+
+    for (depth, nodes) in depth_nodes.iter().enumerate() {
+      assert_eq!(perft::mailbox_perft(&mut board, depth), *nodes, "{fen} at depth {depth}");
+      assert_eq!(perft::perft(&board_bb, depth, None), *nodes, "{fen} at depth {depth}");
+    }
+  }
+}
+
+#[test]
+fn perft_suite() {
+  // The deeper counts take a while, `rusty_chess perft-epd` checks all of them
+  for position in perft::parse_suite(perft::SUITE).unwrap() {
+    let depth = position.nodes.iter().take_while(|nodes| **nodes <= 100_000).count();
+    assert_eq!(perft::check(&position, depth), Vec::<String>::new(), "{}", position.fen);
+  }
+}
+
+#[test]
+fn perft_table_and_threads() {
+  for (fen, depth_nodes) in &PERFT_POSITIONS[1..4] {
+    let board = bitboard::Board::from_fen(fen);
+    let depth = depth_nodes.len() - 2;
+
+    // A tiny table to also go through replaced entries
+    for table in [PerftTable::new(16), PerftTable::new(0)] {
+      for threads in [1, 3] {
+        let divided = perft::divide(&board, depth, Some(&table), threads);
+        assert_eq!(divided.len() as u64, depth_nodes[1]);
+        assert_eq!(
+          divided.iter().map(|(_, nodes)| nodes).sum::<u64>(),
+          depth_nodes[depth],
+          "{fen}"
+        );
+      }
+    }
+  }
+}
+
+#[test]
+fn mate_in_n() {
+  // Positive is mate for the side to move, negative is getting mated
+  let puzzles = [
+    ("6k1/5ppp/8/8/8/8/5PPP/3R2K1 w - - 0 1", 1),
+    ("3r2k1/5ppp/8/8/8/8/5PPP/6K1 b - - 0 1", 1),
+    ("r1bqkb1r/pppp1ppp/2n2n2/4p2Q/2B1P3/8/PPPP1PPP/RNB1K1NR w KQkq - 4 4", 1),
+    ("k7/8/2K5/8/8/8/8/7R w - - 0 1", 2),
+    ("2k5/8/1K6/8/8/8/8/7R w - - 0 1", 2),
+    ("r2qkb1r/pp2nppp/3p4/2pNN1B1/2BnP3/3P4/PPP2PPP/R2bK2R w KQkq - 1 10", 2),
+    ("6k1/pp4p1/2p5/2bp4/8/P5Pb/1P3rrP/2BRRN1K b - - 0 1", 2),
+    ("r1b2k1r/ppppq3/5N1p/4P2Q/4PP2/1B6/PP5P/n2K2R1 w - - 1 1", 2),
+    ("r5rk/5p1p/5R2/4B3/8/8/7P/7K w - - 0 1", 3),
+    ("r1b1kb1r/pppp1ppp/5q2/4n3/3KP3/2N3PN/PPP4P/R1BQ1B1R b kq - 0 1", 3),
+    ("1r4k1/8/8/8/8/8/r7/7K w - - 0 1", -1),
+    ("1r5k/8/8/8/8/8/r7/6K1 w - - 0 1", -1),
+  ];
+
+  for (fen, moves) in puzzles {
+    let mut board = board::Board::from_fen(fen);
+    let mut tt = TranspositionTable::new();
+
+    let (score, _) = iterative_deepening(&mut board, &SearchLimits::time(10_000), &mut tt, &mut |_| {});
+
+    assert_eq!(mate_in(score), Some(moves), "{fen}");
+  }
+}
+
+#[test]
+fn mate_solver() {
+  let solve =
+    |fen: &str, moves: usize, checks_only: bool| MateSolver::new(checks_only).solve(&bitboard::Board::from_fen(fen), moves);
+
+  let MateResult::Mate(line) = solve("6k1/5ppp/8/8/8/8/5PPP/R5K1 w - - 0 1", 3, true) else {
+    panic!("no mate found");
+  };
+  assert_eq!(line.iter().map(|m| m.to_fen()).collect::<Vec<String>>(), ["a1a8"]);
+
+  // Two rooks need two moves, the line ends in checkmate whatever the defender plays
+  let ladder = "7k/8/8/8/8/8/R7/1R4K1 w - - 0 1";
+  assert_eq!(solve(ladder, 1, false), MateResult::NoMate);
+  let MateResult::Mate(line) = solve(ladder, 3, false) else {
+    panic!("no mate found");
+  };
+  assert_eq!(mate::mate_length(&line), 2);
+  let mut board = bitboard::Board::from_fen(ladder);
+  for chess_move in &line {
+    board.make_move(chess_move);
+  }
+  assert!(board.legal_moves().is_empty() && board.in_check(board.side_to_move));
+
+  // A stalemate isn't a mate, and a search cut by the node limit doesn't prove or refute anything
+  assert_eq!(solve("7k/5Q2/6K1/8/8/8/8/8 b - - 0 1", 2, false), MateResult::NoMate);
+  assert_eq!(
+    MateSolver::with_node_limit(false, 1).solve(&bitboard::Board::from_fen(ladder), 3),
+    MateResult::Unknown
+  );
+}
+
+#[test]
+fn differential() {
+  // Random games from the start position and the perft suite, both boards have to agree with shakmaty all along
+  let compared = differential::run(200, 1, 200).unwrap_or_else(|divergence| panic!("{divergence}"));
+  assert!(compared > 10_000, "{compared}");
+  assert_eq!(differential::run(10, 7, 50).ok(), differential::run(10, 7, 50).ok());
+}
+
+#[test]
+fn hashes() {
+  // The en passant square only changes the hash when the capture is legal
+  for (fen, move_fen, capturable) in [
+    ("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1", "e2e4", false),
+    ("4k3/8/8/8/3p4/8/4P3/4K3 w - - 0 1", "e2e4", true),
+    ("8/8/8/8/k2p3R/8/4P3/4K3 w - - 0 1", "e2e4", false),
+  ] {
+    let mut board = board::Board::from_fen(fen);
+    let chess_move = board.find_move(move_fen).unwrap();
+    board.make_move(&chess_move);
+
+    let fen = board.to_fen();
+    let without_en_passant = {
+      let mut fields: Vec<&str> = fen.split(' ').collect();
+      fields[3] = "-";
+      fields.join(" ")
+    };
+    let bitboard = bitboard::Board::from_fen(&fen);
+
+    assert_eq!(board.meta.hash, board.compute_hash_from_scratch(), "{fen}");
+    assert_eq!(
+      board.meta.hash != board::Board::from_fen(&without_en_passant).meta.hash,
+      capturable,
+      "{fen}"
+    );
+    assert_eq!(
+      bitboard.meta.hash != bitboard::Board::from_fen(&without_en_passant).meta.hash,
+      capturable,
+      "{fen}"
+    );
+  }
+
+  // The pawn hash only depends on the pawns, the material hash only on how many pieces there are
+  let board = board::Board::from_fen("4k3/pp6/8/8/8/8/PP3N2/4K3 w - - 0 1");
+  let knight_moved = board::Board::from_fen("4k3/pp6/8/8/3N4/8/PP6/4K3 w - - 0 1");
+  let pawn_moved = board::Board::from_fen("4k3/pp6/8/8/8/P7/1P3N2/4K3 w - - 0 1");
+  assert_eq!(board.meta.pawn_hash, knight_moved.meta.pawn_hash);
+  assert_ne!(board.meta.pawn_hash, pawn_moved.meta.pawn_hash);
+  assert_eq!(board.meta.material_hash, knight_moved.meta.material_hash);
+  assert_eq!(board.meta.material_hash, pawn_moved.meta.material_hash);
+
+  let bitboard = bitboard::Board::from_fen("4k3/pp6/8/8/8/8/PP3N2/4K3 w - - 0 1");
+  let knight_moved = bitboard::Board::from_fen("4k3/pp6/8/8/3N4/8/PP6/4K3 w - - 0 1");
+  let captured = bitboard::Board::from_fen("4k3/p7/8/8/8/8/PP3N2/4K3 w - - 0 1");
+
+  assert_eq!(bitboard.meta.pawn_hash, knight_moved.meta.pawn_hash);
+  assert_eq!(bitboard.meta.material_hash, knight_moved.meta.material_hash);
+  assert_ne!(bitboard.meta.material_hash, captured.meta.material_hash);
+}
+
+#[test]
+fn endgames() {
+  // From white's perspective, the same on both boards
+  let evaluate = |fen: &str| {
+    let eval = board::Board::from_fen(fen).evaluate();
+    assert_eq!(eval, bitboard::Board::from_fen(fen).evaluate(), "{fen}");
+    eval
+  };
+
+  // KPK: a win with the king in front of the pawn, a draw with the lone king in front of it or with a rook pawn
+  assert!(evaluate("3k4/8/3K4/3P4/8/8/8/8 b - - 0 1") > 5000);
+  assert!(evaluate("8/8/8/8/4p3/4k3/8/4K3 b - - 0 1") < -5000);
+  assert_eq!(evaluate("3k4/8/8/8/8/8/3P4/3K4 b - - 0 1"), 0);
+  assert_eq!(evaluate("k7/8/K7/P7/8/8/8/8 w - - 0 1"), 0);
+
+  // Mating with the rook drives the lone king to the edge
+  assert!(evaluate("8/8/8/3k4/8/8/8/R3K3 w - - 0 1") > 5000);
+  assert!(evaluate("3k4/8/3K4/8/8/8/8/R7 w - - 0 1") > evaluate("8/8/8/3k4/8/8/8/R3K3 w - - 0 1"));
+  assert!(evaluate("8/8/8/3K4/8/8/8/q3k3 w - - 0 1") < -5000);
+
+  // KBNK: to the corner of the bishop's color
+  assert!(evaluate("k7/8/2K5/8/8/8/8/1B1N4 w - - 0 1") > evaluate("7k/8/5K2/8/8/8/8/1B1N4 w - - 0 1"));
+
+  // Drawish material
+  assert_eq!(evaluate("8/8/3k4/8/8/2NN4/8/4K3 w - - 0 1"), 0);
+  assert_eq!(evaluate("k7/8/8/8/8/8/P7/2B1K3 w - - 0 1"), 0);
+  assert!(evaluate("k7/8/8/8/8/8/P7/1B2K3 w - - 0 1") > 0);
+  let opposite_bishops = evaluate("4k3/5b2/8/8/8/8/PPP5/2B1K3 w - - 0 1");
+  let same_bishops = evaluate("4k3/4b3/8/8/8/8/PPP5/2B1K3 w - - 0 1");
+  assert!(opposite_bishops > 0 && opposite_bishops < same_bishops);
+}
+
+#[test]
+fn search_stats() {
+  let search = || {
+    let mut board = board::Board::from_fen("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1");
+    let mut tt = TranspositionTable::new();
+    let mut stats = SearchStats::default();
+
+    iterative_deepening(&mut board, &SearchLimits::depth(4), &mut tt, &mut |info| {
+      stats = info.stats.clone()
+    });
+
+    stats
+  };
+
+  let stats = search();
+
+  assert!(stats.qnodes > 0 && stats.qnodes < stats.nodes);
+  assert!(stats.tt_cutoffs <= stats.tt_hits);
+  assert!(stats.beta_cutoffs > 0 && stats.first_move_cutoffs <= stats.beta_cutoffs);
+
+  // A fixed depth search doesn't depend on time, which is what makes bench reproducible
+  assert_eq!(search().nodes, stats.nodes);
+}
+
+#[test]
+fn multi_pv() {
+  let fen = "r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 2 3";
+  let mut board = board::Board::from_fen(fen);
+  let mut tt = TranspositionTable::new();
+  let mut lines = vec![];
+
+  let limits = SearchLimits {
+    multi_pv: 3,
+    ..SearchLimits::depth(4)
+  };
+  iterative_deepening(&mut board, &limits, &mut tt, &mut |info| {
+    if info.depth == 4 {
+      lines.push((info.multi_pv, info.score, info.pv[0].to_fen()));
+    }
+  });
+
+  assert_eq!(lines.iter().map(|line| line.0).collect::<Vec<usize>>(), [1, 2, 3]);
+  assert!(lines[0].1 >= lines[1].1 && lines[1].1 >= lines[2].1);
+  assert!(lines[0].2 != lines[1].2 && lines[0].2 != lines[2].2 && lines[1].2 != lines[2].2);
+
+  // The first line is the same as a single line search, which doesn't see the worse ones
+  let (score, _) = iterative_deepening(
+    &mut board,
+    &SearchLimits::depth(4),
+    &mut TranspositionTable::new(),
+    &mut |_| {},
+  );
+  assert_eq!(score, lines[0].1);
+
+  // There can't be more lines than legal moves
+  let mut board = board::Board::from_fen("k7/8/1K6/8/8/8/8/8 b - - 0 1");
+  let mut count = 0;
+  let limits = SearchLimits {
+    multi_pv: 5,
+    ..SearchLimits::depth(1)
+  };
+  iterative_deepening(&mut board, &limits, &mut tt, &mut |_| count += 1);
+  assert_eq!(count, 1);
+}
+
+#[test]
+fn node_limit() {
+  let mut board = board::Board::default();
+  let limits = SearchLimits {
+    nodes: Some(5_000),
+    ..Default::default()
+  };
+  let mut depths = vec![];
+  iterative_deepening(&mut board, &limits, &mut TranspositionTable::new(), &mut |info| {
+    assert!(info.stats.nodes <= 5_000);
+    depths.push(info.depth);
+  });
+
+  // Stopped by the nodes, not by the depth
+  assert!(!depths.is_empty() && depths.len() < 10);
+}
+
+#[test]
+fn tt_size() {
+  // The TT doesn't grow past its size, and a full one still takes new positions
+  let mut tt = TranspositionTable::with_size_mb(1);
+  iterative_deepening(&mut board::Board::default(), &SearchLimits::depth(6), &mut tt, &mut |_| {});
+  assert!(tt.hashfull() <= 1000);
+  let mut board = board::Board::from_fen("6k1/5ppp/8/8/8/8/5PPP/3R2K1 w - - 0 1");
+  iterative_deepening(&mut board, &SearchLimits::depth(3), &mut tt, &mut |_| {});
+  assert!(tt.get(&board.meta.hash).is_some());
+}
+
+#[test]
+fn go_parameters() {
+  let mut board = board::Board::default();
+  let mut go = |line: &str| uci::parse_go(&line.split_whitespace().collect::<Vec<&str>>(), &mut board, 1, None);
+
+  let limits = go("go depth 5 nodes 1000 movetime 200").unwrap();
+  assert_eq!((limits.depth, limits.nodes, limits.time), (Some(5), Some(1000), Some(200)));
+
+  // Negative limits are errors instead of huge ones, a clock below zero has no time left
+  assert_eq!(go("go movetime -1").err(), Some("invalid movetime value '-1'".to_string()));
+  assert_eq!(go("go nodes -5").err(), Some("invalid nodes value '-5'".to_string()));
+  assert_eq!(go("go depth").err(), Some("invalid depth value ''".to_string()));
+
+  // Searchmoves have to be legal, and a mate in n moves takes 2n - 1 plies
+  assert_eq!(go("go searchmoves e2e4 d2d4 depth 2").unwrap().depth, Some(2));
+  assert_eq!(
+    go("go searchmoves e2e4 e2e5").err(),
+    Some("invalid searchmoves value 'e2e5'".to_string())
+  );
+  assert_eq!(go("go mate 2").unwrap().depth, Some(3));
+  assert_eq!(go("go mate 3 depth 2").unwrap().depth, Some(2));
+  assert!(go("go mate 0").is_err());
+  assert_eq!(go("go wtime -100 btime 1000").unwrap().time, Some(0));
+
+  // Never more than a fraction of what is left on the clock
+  assert!(go("go wtime 300 btime 300").unwrap().time.unwrap() <= 30);
+  assert!(time_for_move(10 * 60 * 1000) >= 1000);
+}
+
+#[test]
+fn epd_suite() {
+  let positions = epd_test::parse(
+    r#"
+    6k1/5ppp/8/8/8/8/5PPP/R5K1 w - - bm Ra8#; id "back rank";
+    4k3/8/8/3q4/8/8/8/3QK3 w - - bm Qxd5; c0 "Qxd5=10, Ke2=1";
+    3rk3/8/8/3p4/8/8/8/3QK3 w - - am Qxd5; id "defended pawn";
+    "#,
+  )
+  .unwrap();
+  assert_eq!(positions[0].id, "back rank");
+  assert_eq!(positions[1].id, "2");
+
+  let summary = epd_test::run(&positions, &SearchLimits::depth(4));
+  assert_eq!((summary.solved, summary.total), (3, 3));
+  assert_eq!((summary.points, summary.max_points), (10, 10));
+
+  assert_eq!(
+    epd_test::parse("4k3/8/8/8/8/8/8/4K3 w - - bm Qh5; id \"no queen\";").err(),
+    Some("no queen: illegal bm 'Qh5'".to_string())
+  );
+  assert_eq!(
+    epd_test::parse("4k3/8/8/8/8/8/8/4K3 w - - id \"nothing\";").err(),
+    Some("nothing: no bm or am".to_string())
+  );
+}
+
+#[test]
+fn root_moves() {
+  // Rd8 is mate, every other move is much worse
+  let mut board = board::Board::from_fen("6k1/5ppp/8/8/8/8/5PPP/3R2K1 w - - 0 1");
+  let mate = board.find_move("d1d8").unwrap();
+  let push = board.find_move("h2h3").unwrap();
+  let illegal = board::Board::default().find_move("e2e4").unwrap();
+
+  let mut search = |root_moves: RootMoves| {
+    let limits = SearchLimits {
+      root_moves,
+      ..SearchLimits::depth(3)
+    };
+    let (score, best_move) = iterative_deepening(&mut board, &limits, &mut TranspositionTable::new(), &mut |_| {});
+
+    (mate_in(score), best_move.to_fen())
+  };
+
+  assert_eq!(search(RootMoves::default()), (Some(1), "d1d8".to_string()));
+  assert_eq!(
+    search(RootMoves::only(vec![push.clone(), mate.clone()])),
+    (Some(1), "d1d8".to_string())
+  );
+  assert_eq!(search(RootMoves::only(vec![push.clone()])).1, "h2h3");
+
+  let (mate_score, best_move) = search(RootMoves {
+    excluded: vec![mate],
+    ..Default::default()
+  });
+  assert_eq!(mate_score, None);
+  assert_ne!(best_move, "d1d8");
+
+  // A big enough bonus makes a worse move the best one, but the score is still the move's own
+  let bonuses = RootMoves {
+    bonuses: vec![(push, CHECKMATE)],
+    ..Default::default()
+  };
+  assert_eq!(search(bonuses), (None, "h2h3".to_string()));
+
+  // Restrictions which don't allow any legal move are ignored
+  assert_eq!(search(RootMoves::only(vec![illegal])).1, "d1d8");
+}
+
+#[test]
+fn search_signals() {
+  // Without limits the search would go on until stopped
+  let signals = SearchSignals::default();
+  signals.stop();
+  let limits = SearchLimits {
+    signals,
+    ..Default::default()
+  };
+  let mut board = board::Board::default();
+  let (_, best_move) = iterative_deepening(&mut board, &limits, &mut TranspositionTable::new(), &mut |_| {});
+  assert!(board.legal_moves().contains(&best_move));
+
+  // The time limit only starts counting on ponderhit
+  let signals = SearchSignals::pondering();
+  let limits = SearchLimits {
+    signals: signals.clone(),
+    ..SearchLimits::time(0)
+  };
+  let search = std::thread::spawn(move || {
+    let start = std::time::Instant::now();
+    iterative_deepening(
+      &mut board::Board::default(),
+      &limits,
+      &mut TranspositionTable::new(),
+      &mut |_| {},
+    );
+    start.elapsed()
+  });
+
+  std::thread::sleep(std::time::Duration::from_millis(200));
+  signals.ponderhit();
+
+  assert!(search.join().unwrap() >= std::time::Duration::from_millis(200));
+}
+
+#[test]
+fn search_thread() {
+  // Without limits it only ends when stopped
+  let mut search = SearchThread::spawn(board::Board::default(), SearchLimits::default(), TranspositionTable::new());
+
+  assert!(matches!(search.blocking_next_event(), Some(SearchEvent::Info(info)) if info.depth == 1));
+  search.signals().stop();
+
+  let mut done = None;
+  while let Some(event) = search.blocking_next_event() {
+    if let SearchEvent::Done { best_move, .. } = event {
+      done = Some(best_move);
+    }
+  }
+
+  assert!(board::Board::default().legal_moves().contains(&done.unwrap()));
+  assert!(search.finish().get(&board::Board::default().meta.hash).is_some());
+}
+
+#[tokio::test]
+async fn tcp_interface() {
+  use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+  use tokio::net::TcpListener;
+
+  // Nobody is listening on a port that was just freed
+  let port = TcpListener::bind("127.0.0.1:0").await.unwrap().local_addr().unwrap().port();
+  assert!(tcp::connect("127.0.0.1", port, 2).await.is_none());
+
+  // A mock of the interface
+  let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+  let port = listener.local_addr().unwrap().port();
+  let engine = tokio::spawn(async move {
+    let stream = tcp::connect("127.0.0.1", port, 3).await.unwrap();
+    tcp::play(stream).await;
+  });
+
+  let (stream, _) = listener.accept().await.unwrap();
+  let (reader, mut writer) = stream.into_split();
+  let mut replies = BufReader::new(reader).lines();
+
+  // Two invalid packets at once: not a packet at all, and one with a time which isn't a number
+  writer
+    .write_all(b"hello\n6k1/5ppp/8/8/8/8/5PPP/3R2K1 w - - 0 1 x 1000\n")
+    .await
+    .unwrap();
+  assert!(replies.next_line().await.unwrap().unwrap().starts_with("error"));
+  assert!(replies.next_line().await.unwrap().unwrap().starts_with("error"));
+
+  // A packet in pieces
+  writer.write_all(b"6k1/5ppp/8/8/8/8/5PPP/3R2K1 w - ").await.unwrap();
+  tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+  writer.write_all(b"- 0 1 10000 10000\n").await.unwrap();
+  assert_eq!(replies.next_line().await.unwrap().unwrap(), "d1d8");
+
+  // The game ends when the interface disconnects
+  drop(writer);
+  engine.await.unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn server() {
+  use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+  use tokio::net::{TcpListener, TcpStream};
+
+  let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+  let port = listener.local_addr().unwrap().port();
+  tokio::spawn(server::serve(listener));
+
+  // Both clients are connected at the same time, each one with its own game
+  let (uci_reader, mut uci_writer) = TcpStream::connect(("127.0.0.1", port)).await.unwrap().into_split();
+  let (reader, mut writer) = TcpStream::connect(("127.0.0.1", port)).await.unwrap().into_split();
+  let mut uci_replies = BufReader::new(uci_reader).lines();
+  let mut replies = BufReader::new(reader).lines();
+
+  uci_writer
+    .write_all(b"uci\nisready\nposition fen 3r2k1/5ppp/8/8/8/8/5PPP/6K1 b - - 0 1\ngo depth 3\n")
+    .await
+    .unwrap();
+  writer
+    .write_all(b"6k1/5ppp/8/8/8/8/5PPP/3R2K1 w - - 0 1 10000 10000\n")
+    .await
+    .unwrap();
+
+  assert_eq!(replies.next_line().await.unwrap().unwrap(), "d1d8");
+
+  let mut uci_lines = vec![];
+  while let Some(line) = uci_replies.next_line().await.unwrap() {
+    if line.starts_with("bestmove") {
+      assert_eq!(line, "bestmove d8d1");
+      break;
+    }
+    uci_lines.push(line);
+  }
+  assert!(uci_lines.contains(&"uciok".to_string()) && uci_lines.contains(&"readyok".to_string()));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn http_api() {
+  use tokio::io::{AsyncReadExt, AsyncWriteExt};
+  use tokio::net::{TcpListener, TcpStream};
+
+  let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+  let port = listener.local_addr().unwrap().port();
+  tokio::spawn(http::serve(listener));
+
+  let request = |method: &str, target: &str, body: &str| {
+    let request = format!(
+      "{method} {target} HTTP/1.1\r\nHost: localhost\r\nContent-Length: {}\r\n\r\n{body}",
+      body.len()
+    );
+
+    async move {
+      let mut stream = TcpStream::connect(("127.0.0.1", port)).await.unwrap();
+      stream.write_all(request.as_bytes()).await.unwrap();
+
+      let mut response = String::new();
+      stream.read_to_string(&mut response).await.unwrap();
+
+      let (head, body) = response.split_once("\r\n\r\n").unwrap();
+      let status: u16 = head.split(' ').nth(1).unwrap().parse().unwrap();
+      (status, serde_json::from_str::<serde_json::Value>(body).unwrap())
+    }
+  };
+
+  let (status, analysis) = request(
+    "POST",
+    "/analyse",
+    r#"{"fen": "6k1/5ppp/8/8/8/8/5PPP/3R2K1 w - - 0 1", "depth": 3, "multipv": 2}"#,
+  )
+  .await;
+  assert_eq!(status, 200);
+  assert_eq!(analysis["best_move"], "d1d8");
+  assert_eq!(analysis["score"]["mate"], 1);
+  assert_eq!(analysis["pv"][0], "d1d8");
+  assert_eq!(analysis["lines"].as_array().unwrap().len(), 2);
+  assert!(analysis["nodes"].as_u64().unwrap() > 0);
+
+  let startpos = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+  let (status, perft) = request("POST", "/perft", &format!(r#"{{"fen": "{startpos}", "depth": 3}}"#)).await;
+  assert_eq!(status, 200);
+  assert_eq!(perft["nodes"], 8902);
+  assert_eq!(perft["moves"]["e2e4"], 600);
+
+  let (status, legal_moves) = request(
+    "GET",
+    "/legal-moves?fen=rnbqkbnr%2Fpppppppp%2F8%2F8%2F8%2F8%2FPPPPPPPP%2FRNBQKBNR+w+KQkq+-+0+1",
+    "",
+  )
+  .await;
+  assert_eq!(status, 200);
+  assert_eq!(legal_moves["moves"].as_array().unwrap().len(), 20);
+  assert_eq!(legal_moves["status"], "Ongoing");
+
+  // Errors are JSON too
+  let (status, error) = request("GET", "/legal-moves?fen=hello", "").await;
+  assert_eq!(status, 400);
+  assert!(error["error"].is_string());
+  assert_eq!(
+    request("POST", "/analyse", r#"{"fen": "8/8/8/8/8/8/8/8 w - - 0 1"}"#).await.0,
+    400
+  );
+  assert_eq!(
+    request("POST", "/analyse", &format!(r#"{{"fen": "{startpos}", "depth": 500}}"#))
+      .await
+      .0,
+    400
+  );
+  assert_eq!(request("GET", "/perft", "").await.0, 405);
+  assert_eq!(request("GET", "/", "").await.0, 404);
+}
+
+#[test]
+fn syzygy_errors() {
+  assert!(Tablebases::open("tests/no_such_directory", 7).is_err());
+  // A directory without tables
+  assert!(Tablebases::open("src", 7).is_err());
+
+  // Tables which can't be read aren't probed, the position is searched instead
+  let dir = std::env::temp_dir().join("rusty_chess_broken_syzygy");
+  std::fs::create_dir_all(&dir).unwrap();
+  let table = std::fs::read("tests/syzygy/KQvK.rtbw").unwrap();
+  std::fs::write(dir.join("KQvK.rtbw"), &table[..table.len() / 2]).unwrap();
+  let mut garbage = table.clone();
+  garbage[8..table.len() - 16].fill(0xff);
+  std::fs::write(dir.join("KRvK.rtbw"), garbage).unwrap();
+
+  let tablebases = Tablebases::open(dir.to_str().unwrap(), 7).unwrap();
+  for fen in ["8/8/8/8/8/8/k7/4K2Q w - - 0 1", "8/8/8/8/8/8/k7/4K2R w - - 0 1"] {
+    assert_eq!(tablebases.probe_wdl(&board::Board::from_fen(fen)), None, "{fen}");
+    assert_eq!(tablebases.probe_wdl(&board::Board::from_fen(fen)), None, "{fen}");
+  }
+}
+
+#[test]
+fn syzygy() {
+  let tablebases = Tablebases::open("tests/syzygy", 7).unwrap();
+  assert_eq!(tablebases.max_pieces(), 3);
+  assert_eq!(Tablebases::open("tests/syzygy", 2).unwrap().max_pieces(), 2);
+
+  for (fen, outcome) in [
+    ("8/8/8/8/8/8/k7/4K2Q w - - 0 1", Some(Outcome::Win)),
+    ("8/8/8/8/8/8/k7/4K2Q b - - 0 1", Some(Outcome::Loss)),
+    ("k7/8/8/8/8/8/P7/K7 w - - 0 1", Some(Outcome::Draw)),
+    // Only probed right after a capture or a pawn move
+    ("8/8/8/8/8/8/k7/4K2Q w - - 5 40", None),
+    // Too many pieces
+    ("4k3/8/8/8/8/8/4P3/R3K3 w - - 0 1", None),
+  ] {
+    assert_eq!(tablebases.probe_wdl(&board::Board::from_fen(fen)), outcome, "{fen}");
+  }
+
+  // Giving the queen away isn't one of the winning moves
+  let mut board = board::Board::from_fen("8/8/8/8/8/1k6/8/2Q1K3 w - - 0 1");
+  let (outcome, moves) = tablebases.root_moves(&mut board).unwrap();
+  let moves: Vec<String> = moves.iter().map(|m| m.to_fen()).collect();
+  assert_eq!(outcome, Outcome::Win);
+  assert!(!moves.is_empty() && moves.len() < board.legal_moves().len());
+  assert!(!moves.contains(&"c1b2".to_string()) && !moves.contains(&"c1c2".to_string()));
+
+  // The search only plays those
+  let limits = SearchLimits {
+    tablebases: Some(Arc::new(tablebases)),
+    ..SearchLimits::depth(3)
+  };
+  let mut tt = TranspositionTable::new();
+  let (_, best_move) = iterative_deepening(&mut board, &limits, &mut tt, &mut |_| {});
+  assert!(moves.contains(&best_move.to_fen()));
+}
+
+#[test]
+fn syzygy_fixtures() {
+  use shakmaty::{fen::Fen, Board, CastlingMode, Chess, FromSetup, Piece, Position, Setup};
+  use shakmaty_syzygy::{Dtz, Tablebase, Wdl};
+
+  // The tables in tests/syzygy are read by shakmaty-syzygy, not only by the code which wrote them
+  let mut tablebase = Tablebase::<Chess>::new();
+  assert_eq!(tablebase.add_directory("tests/syzygy").unwrap(), 10);
+  let probe = |pos: &Chess| -> (Wdl, Dtz) {
+    let wdl = tablebase.probe_wdl_after_zeroing(pos).unwrap();
+    (wdl, tablebase.probe_dtz(pos).unwrap().ignore_rounding())
+  };
+
+  // Positions from the test suite of shakmaty-syzygy, with the values of the published tables
+  for (fen, wdl, dtz) in [
+    ("8/5p2/6k1/K7/8/8/8/8 w - - 0 1", Wdl::Loss, -2),
+    ("8/8/8/2K5/5kp1/8/8/8 b - - 0 1", Wdl::Win, 1),
+    ("8/8/8/2R5/1K6/8/5k2/8 w - - 0 1", Wdl::Win, 21),
+    ("8/3k4/8/8/8/8/4P3/3K4 w - - 0 1", Wdl::Draw, 0),
+  ] {
+    let pos: Chess = fen.parse::<Fen>().unwrap().into_position(CastlingMode::Standard).unwrap();
+    assert_eq!(probe(&pos), (wdl, Dtz(dtz)), "{fen}");
+  }
+
+  // The same position with the colors switched
+  let switch_colors = |pos: &Chess| -> Chess {
+    let mut setup = Setup::empty();
+    setup.board = Board::empty();
+    for square in pos.board().occupied() {
+      let piece = pos.board().piece_at(square).unwrap();
+      setup.board.set_piece_at(
+        square.flip_vertical(),
+        Piece {
+          color: !piece.color,
+          ..piece
+        },
+      );
+    }
+    setup.turn = !pos.turn();
+    Chess::from_setup(setup, CastlingMode::Standard).unwrap()
+  };
+
+  // Every position has the value found by the solver. The DTZ of a position with black to move takes a search,
+  // so only some of those are checked
+  for solved in syzygy_fixtures::solve() {
+    for (i, (pos, wdl, dtz)) in solved.positions().enumerate() {
+      if pos.turn().is_white() || i % 8 == 0 {
+        assert_eq!(probe(&pos), (wdl, Dtz(dtz)), "{}", solved.name);
+      } else {
+        assert_eq!(tablebase.probe_wdl_after_zeroing(&pos).unwrap(), wdl, "{}", solved.name);
+      }
+
+      if i % 16 == 0 {
+        assert_eq!(probe(&switch_colors(&pos)), (wdl, Dtz(dtz)), "{}", solved.name);
+      }
+    }
+  }
+}
+
+#[test]
+#[ignore = "writes the tables in tests/syzygy"]
+fn write_syzygy_fixtures() {
+  for solved in syzygy_fixtures::solve() {
+    syzygy_fixtures::write(std::path::Path::new("tests/syzygy"), &solved).unwrap();
+  }
+}
+
+#[tokio::test]
+async fn cli() {
+  let run = |args: &[&str]| {
+    let args: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+    async move { cli::run(&args).await }
+  };
+
+  assert!(run(&["perft", "startpos", "2", "--divide"]).await.is_ok());
+  assert!(run(&["search", "6k1/5ppp/8/8/8/8/5PPP/3R2K1 w - - 0 1", "--depth=2", "d1d8"])
+    .await
+    .is_ok());
+  assert!(run(&["selfplay", "k7/8/1K6/8/8/8/8/7R w - - 0 1", "--depth", "2"])
+    .await
+    .is_ok());
+  assert!(run(&["help", "perft"]).await.is_ok());
+  assert!(run(&["selfplay", "--help"]).await.is_ok());
+
+  for invalid in [
+    &["perft", "startpos"][..],
+    &["perft", "startpos", "0"],
+    &["perft", "hello", "1"],
+    &["search", "startpos", "--depth"],
+    &["search", "startpos", "--depth", "deep"],
+    &["search", "startpos", "--divide"],
+    &["search", "startpos", "e2e5"],
+    &["build-book", "--pgn", "missing.pgn"],
+    &["help", "play"],
+    &["play"],
+  ] {
+    assert!(run(invalid).await.is_err(), "{invalid:?}");
+  }
+}
+
+// Positions reached by playing random legal moves from the perft suite positions
+fn fens() -> impl Strategy<Value = String> {
+  let suite: Vec<String> = perft::parse_suite(perft::SUITE)
+    .unwrap()
+    .into_iter()
+    .map(|position| position.fen)
+    .collect();
+
+  (select(suite), prop::collection::vec(any::<prop::sample::Index>(), 0..20)).prop_map(|(fen, moves)| {
+    let mut board = board::Board::from_fen(&fen);
+    for index in moves {
+      let legal_moves = board.legal_moves();
+      if legal_moves.is_empty() {
+        break;
+      }
+      board.make_move(index.get(&legal_moves));
+    }
+    board.to_fen()
+  })
+}
+
+// Look like fens, but are mostly invalid
+const FEN_LIKE: &str = "[pnbrqkPNBRQK1-9/]{1,72} [wb-] (-|K?Q?k?q?) (-|[a-h][1-8]) [0-9]{1,3} [0-9]{1,3}";
+
+// Any move between two squares, mostly illegal ones
+const ANY_MOVE: &str = "[a-h][1-8][a-h][1-8][qrbnkp]?";
+
+fn fen_and_move() -> impl Strategy<Value = (String, String)> {
+  fens().prop_flat_map(|fen| {
+    let legal_moves: Vec<String> = board::Board::from_fen(&fen)
+      .legal_moves()
+      .iter()
+      .map(|chess_move| chess_move.to_fen())
+      .collect();
+    let chess_move = if legal_moves.is_empty() {
+      ANY_MOVE.boxed()
+    } else {
+      prop_oneof![select(legal_moves), ANY_MOVE].boxed()
+    };
+
+    (Just(fen), chess_move)
+  })
+}
+
+proptest! {
+  #![proptest_config(ProptestConfig::with_cases(1000))]
+
+  #[test]
+  fn fen_parsing(
+    fen in prop_oneof![fens().prop_map(String::into_bytes), FEN_LIKE.prop_map(String::into_bytes), any::<Vec<u8>>()]
+  ) {
+    fuzz::fen(&fen);
+  }
+
+  #[test]
+  fn packet_parsing(
+    fen in fens(),
+    white_time in -1000..100_000isize,
+    black_time in -1000..100_000isize,
+    other in any::<Vec<u8>>()
+  ) {
+    let packet = format!("{fen} {white_time} {black_time}");
+    prop_assert!(tcp::decode_packet(&packet).is_ok());
+
+    fuzz::packet(packet.as_bytes());
+    fuzz::packet(&other);
+  }
+
+  #[test]
+  fn uci_move_parsing((fen, chess_move) in fen_and_move(), other in (FEN_LIKE, ANY_MOVE)) {
+    fuzz::uci_move(format!("{fen}\n{chess_move}").as_bytes());
+    fuzz::uci_move(format!("{}\n{}", other.0, other.1).as_bytes());
+  }
+
+  #[test]
+  fn book_line_parsing(
+    hash in any::<u64>(),
+    chess_move in prop_oneof![ANY_MOVE, Just("0000".to_string())],
+    other in "[0-9a-h|qrbn ]{0,30}"
+  ) {
+    fuzz::book_line(format!("{hash}|{chess_move}").as_bytes());
+    fuzz::book_line(other.as_bytes());
+  }
+}
@@ -0,0 +1,328 @@
+use crate::bitboard::Color;
+use crate::board::Board;
+use crate::config;
+use crate::search::{time_for_move, RootMoves, SearchLimits, SearchSignals, TranspositionTable};
+use crate::search_thread::{SearchEvent, SearchThread};
+use crate::syzygy::{self, Tablebases, MAX_PIECES};
+use std::{
+  fmt::Display,
+  io::{self, BufRead, BufReader, Write},
+  net::TcpStream,
+  sync::{Arc, Mutex},
+  thread::{self, JoinHandle},
+  time::Duration,
+};
+
+const MAX_MULTI_PV: usize = 256;
+
+// A search and the thread printing its output, so that stop and ponderhit can be read meanwhile.
+// The TT is given back when it's done
+struct RunningSearch {
+  signals: SearchSignals,
+  handle: JoinHandle<TranspositionTable>,
+}
+
+impl RunningSearch {
+  fn finish(self) -> TranspositionTable {
+    self.signals.stop();
+    self.handle.join().expect("Search thread panicked")
+  }
+}
+
+// Where answers go. It's shared with the thread printing the search output
+#[derive(Clone)]
+struct Output(Arc<Mutex<dyn Write + Send>>);
+
+impl Output {
+  // Write errors mean the GUI is gone, then the session ends on the next read anyway
+  fn line(&self, text: impl Display) {
+    let mut writer = self.0.lock().expect("Output lock poisoned");
+    writeln!(writer, "{text}").and_then(|_| writer.flush()).ok();
+  }
+}
+
+// Universal Chess Interface: reads commands from stdin and answers on stdout
+pub fn run() {
+  session(io::stdin().lock(), io::stdout());
+}
+
+// The same, but over a connection. It's blocking, so it shouldn't run on the async runtime
+pub fn run_tcp(stream: TcpStream) -> io::Result<()> {
+  let reader = BufReader::new(stream.try_clone()?);
+  session(reader, stream);
+  Ok(())
+}
+
+fn session(input: impl BufRead, output: impl Write + Send + 'static) {
+  let output = Output(Arc::new(Mutex::new(output)));
+  let mut board = Board::default();
+  let mut tt = TranspositionTable::new();
+  let mut multi_pv = 1;
+  let mut syzygy_path = config::get().syzygy.path.clone();
+  let mut probe_limit = config::get().syzygy.probe_limit;
+  let mut tablebases = syzygy::configured();
+  let mut search: Option<RunningSearch> = None;
+
+  for line in input.lines() {
+    let Ok(line) = line else {
+      break;
+    };
+    let tokens: Vec<&str> = line.split_whitespace().collect();
+
+    // These can't be done while searching, so the search is stopped first
+    if matches!(
+      tokens.first(),
+      Some(&"stop" | &"go" | &"position" | &"ucinewgame" | &"setoption")
+    ) {
+      if let Some(running) = search.take() {
+        tt = running.finish();
+      }
+    }
+
+    match tokens.first() {
+      Some(&"uci") => {
+        output.line("id name rusty_chess");
+        output.line("id author Luj8n");
+        output.line(format!("option name MultiPV type spin default 1 min 1 max {MAX_MULTI_PV}"));
+        output.line("option name Ponder type check default false");
+        output.line("option name SyzygyPath type string default <empty>");
+        output.line(format!(
+          "option name SyzygyProbeLimit type spin default {MAX_PIECES} min 0 max {MAX_PIECES}"
+        ));
+        output.line("uciok");
+      }
+      Some(&"isready") => output.line("readyok"),
+      Some(&"ucinewgame") => tt.clear(),
+      Some(&"setoption") => match parse_option(&tokens[1..]) {
+        Some(("MultiPV", value)) => match value.parse::<usize>() {
+          Ok(value) if (1..=MAX_MULTI_PV).contains(&value) => multi_pv = value,
+          _ => output.line(format!("info string invalid MultiPV value '{value}'")),
+        },
+        // Only tells that the GUI will send go ponder, nothing to set up
+        Some(("Ponder", _)) => {}
+        Some(("SyzygyPath", value)) => {
+          syzygy_path = if value == "<empty>" {
+            String::new()
+          } else {
+            value.to_string()
+          };
+          tablebases = load_tablebases(&syzygy_path, probe_limit, &output);
+        }
+        Some(("SyzygyProbeLimit", value)) => match value.parse::<usize>() {
+          Ok(value) if value <= MAX_PIECES => {
+            probe_limit = value;
+            tablebases = load_tablebases(&syzygy_path, probe_limit, &output);
+          }
+          _ => output.line(format!("info string invalid SyzygyProbeLimit value '{value}'")),
+        },
+        _ => output.line(format!("info string unknown option '{line}'")),
+      },
+      Some(&"position") => match parse_position(&tokens[1..]) {
+        Some(new_board) => board = new_board,
+        None => output.line(format!("info string invalid position '{line}'")),
+      },
+      Some(&"go") => search = go(&mut board, &tokens[1..], multi_pv, tablebases.clone(), &mut tt, &output),
+      Some(&"ponderhit") => {
+        if let Some(running) = &search {
+          running.signals.ponderhit();
+        }
+      }
+      Some(&"quit") => break,
+      _ => {}
+    }
+  }
+
+  if let Some(running) = search {
+    running.finish();
+  }
+}
+
+// An empty path turns the tablebases off
+fn load_tablebases(path: &str, probe_limit: usize, output: &Output) -> Option<Arc<Tablebases>> {
+  if path.is_empty() {
+    return None;
+  }
+
+  match Tablebases::open(path, probe_limit) {
+    Ok(tablebases) => {
+      output.line(format!(
+        "info string found syzygy tables up to {} pieces",
+        tablebases.max_pieces()
+      ));
+      Some(Arc::new(tablebases))
+    }
+    Err(error) => {
+      output.line(format!("info string {error}"));
+      None
+    }
+  }
+}
+
+// setoption name <id> [value <x>]
+fn parse_option<'a>(tokens: &[&'a str]) -> Option<(&'a str, &'a str)> {
+  let value_index = tokens.iter().position(|t| *t == "value").unwrap_or(tokens.len());
+
+  if tokens.first() != Some(&"name") || value_index < 2 {
+    return None;
+  }
+
+  // Option names can have spaces, but none of ours do
+  Some((tokens[1], tokens.get(value_index + 1).copied().unwrap_or("")))
+}
+
+// position [startpos | fen <fen>] [moves <move1> ... <moveN>]
+pub fn parse_position(tokens: &[&str]) -> Option<Board> {
+  let moves_index = tokens.iter().position(|t| *t == "moves").unwrap_or(tokens.len());
+
+  let mut board = match *tokens.first()? {
+    "startpos" => Board::default(),
+    "fen" => Board::try_from_fen(&tokens[1..moves_index].join(" ")).ok()?,
+    _ => return None,
+  };
+
+  for move_fen in tokens.iter().skip(moves_index + 1) {
+    let chess_move = board.find_move(move_fen)?;
+    board.make_move(&chess_move);
+  }
+
+  Some(board)
+}
+
+const GO_PARAMETERS: [&str; 12] = [
+  "searchmoves",
+  "ponder",
+  "wtime",
+  "btime",
+  "winc",
+  "binc",
+  "movestogo",
+  "depth",
+  "nodes",
+  "mate",
+  "movetime",
+  "infinite",
+];
+
+pub fn parse_go(
+  tokens: &[&str],
+  board: &mut Board,
+  multi_pv: usize,
+  tablebases: Option<Arc<Tablebases>>,
+) -> Result<SearchLimits, String> {
+  let token = |name: &str| -> Option<&str> {
+    let index = tokens.iter().position(|t| *t == name)?;
+    Some(tokens.get(index + 1).copied().unwrap_or(""))
+  };
+  let invalid = |name: &str, value: &str| format!("invalid {name} value '{value}'");
+
+  // Limits can't be negative, but a clock goes below zero when the time is over
+  let value = |name: &str| -> Result<Option<u64>, String> {
+    token(name)
+      .map(|value| value.parse::<u64>().map_err(|_| invalid(name, value)))
+      .transpose()
+  };
+  let clock = |name: &str| -> Result<Option<isize>, String> {
+    token(name)
+      .map(|value| {
+        value
+          .parse::<isize>()
+          .map(|time| time.max(0))
+          .map_err(|_| invalid(name, value))
+      })
+      .transpose()
+  };
+
+  // searchmoves is followed by moves until the next parameter
+  let search_moves = tokens
+    .iter()
+    .skip_while(|t| **t != "searchmoves")
+    .skip(1)
+    .take_while(|t| !GO_PARAMETERS.contains(t))
+    .map(|move_fen| board.find_move(move_fen).ok_or_else(|| invalid("searchmoves", move_fen)))
+    .collect::<Result<Vec<_>, _>>()?;
+
+  let time_left = if board.side_to_move == Color::White {
+    clock("wtime")?
+  } else {
+    clock("btime")?
+  };
+
+  // A mate in n moves is found by searching 2n - 1 plies deep
+  let mate_depth = match value("mate")? {
+    Some(0) => return Err(invalid("mate", "0")),
+    mate => mate.map(|moves| moves.saturating_mul(2) - 1),
+  };
+  let depth = match (value("depth")?, mate_depth) {
+    (Some(depth), Some(mate_depth)) => Some(depth.min(mate_depth)),
+    (depth, mate_depth) => depth.or(mate_depth),
+  };
+
+  Ok(SearchLimits {
+    time: value("movetime")?.map(|time| time as u128).or(time_left.map(time_for_move)),
+    depth: depth.map(|depth| depth.min(i32::MAX as u64) as i32),
+    nodes: value("nodes")?,
+    multi_pv,
+    root_moves: RootMoves::only(search_moves),
+    signals: if tokens.contains(&"ponder") {
+      SearchSignals::pondering()
+    } else {
+      SearchSignals::default()
+    },
+    tablebases,
+  })
+}
+
+fn go(
+  board: &mut Board,
+  tokens: &[&str],
+  multi_pv: usize,
+  tablebases: Option<Arc<Tablebases>>,
+  tt: &mut TranspositionTable,
+  output: &Output,
+) -> Option<RunningSearch> {
+  if board.legal_moves().is_empty() {
+    output.line("bestmove 0000");
+    return None;
+  }
+
+  let limits = match parse_go(tokens, board, multi_pv, tablebases) {
+    Ok(limits) => limits,
+    Err(error) => {
+      output.line(format!("info string {error}"));
+      return None;
+    }
+  };
+  let signals = limits.signals.clone();
+  // In infinite and ponder mode bestmove can only be sent after stop or ponderhit
+  let infinite = tokens.contains(&"infinite");
+
+  let mut search = SearchThread::spawn(board.clone(), limits, std::mem::take(tt));
+  let output = output.clone();
+
+  // Prints what the search reports, while the main thread keeps reading commands
+  let handle = thread::spawn(move || {
+    while let Some(event) = search.blocking_next_event() {
+      match event {
+        SearchEvent::Info(info) => output.line(info.to_uci()),
+        SearchEvent::Done {
+          best_move,
+          expected_reply,
+          ..
+        } => {
+          while (infinite || search.signals().is_pondering()) && !search.signals().is_stopped() {
+            thread::sleep(Duration::from_millis(1));
+          }
+
+          match expected_reply {
+            Some(reply) => output.line(format!("bestmove {} ponder {}", best_move.to_fen(), reply.to_fen())),
+            None => output.line(format!("bestmove {}", best_move.to_fen())),
+          }
+        }
+      }
+    }
+
+    search.finish()
+  });
+
+  Some(RunningSearch { signals, handle })
+}
@@ -0,0 +1,168 @@
+use rusty_chess::{Board, ChessMove, Color, DrawReason, File, GameStatus, Piece, PieceType, Rank, Square};
+
+fn perft(board: &mut Board, depth: usize) -> u64 {
+  if depth == 0 {
+    return 1;
+  }
+
+  let mut nodes = 0;
+  for chess_move in board.legal_moves() {
+    board.make_move(&chess_move);
+    nodes += perft(board, depth - 1);
+    board.undo_move(&chess_move);
+  }
+  nodes
+}
+
+fn play(board: &mut Board, moves: &[&str]) {
+  for san in moves {
+    let chess_move = board.parse_san(san).unwrap_or_else(|| panic!("{san} isn't legal"));
+    board.make_move(&chess_move);
+  }
+}
+
+#[test]
+fn squares() {
+  let e4: Square = "e4".parse().unwrap();
+  assert_eq!((e4.file(), e4.rank()), (File::E, Rank::R4));
+  assert_eq!(e4, Square::new(File::E, Rank::R4));
+  assert_eq!(e4, Square::E4);
+  assert_eq!(e4.index(), 28);
+  assert_eq!(e4.to_string(), "e4");
+
+  assert_eq!(Square::from_index(0).unwrap().to_string(), "a1");
+  assert_eq!(Square::from_index(63).unwrap().to_string(), "h8");
+  assert!(Square::from_index(64).is_none());
+  assert!(File::new(8).is_none() && Rank::new(8).is_none());
+  assert!("i1".parse::<Square>().is_err());
+  assert!("e9".parse::<Square>().is_err());
+  assert!("e".parse::<Square>().is_err());
+}
+
+#[test]
+fn pieces() {
+  let board = Board::default();
+  let piece_at = |square: &str| board.piece_at(square.parse().unwrap());
+
+  assert_eq!(
+    piece_at("e1"),
+    Some(Piece {
+      color: Color::White,
+      piece_type: PieceType::King
+    })
+  );
+  assert_eq!(piece_at("d8").map(|piece| piece.to_char()), Some('q'));
+  assert_eq!(piece_at("g7").map(|piece| piece.to_char()), Some('p'));
+  assert_eq!(piece_at("b1").map(|piece| piece.to_char()), Some('N'));
+  assert_eq!(piece_at("e4"), None);
+}
+
+#[test]
+fn fen() {
+  let fen = "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1";
+  assert_eq!(Board::from_fen(fen).to_fen(), fen);
+  assert_eq!(
+    Board::default().to_fen(),
+    "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1"
+  );
+
+  assert!(Board::try_from_fen(fen).is_ok());
+  assert!(Board::try_from_fen("").is_err());
+  assert!(Board::try_from_fen("8/8/8/8/8/8/8/8 w - - 0 1").is_err());
+  assert!(Board::try_from_fen("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0").is_err());
+  assert!(Board::try_from_fen("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 300 1").is_err());
+
+  // Fens written differently are read the way to_fen would write them
+  let startpos = Board::default();
+  for other in [
+    "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w qkQK - 0 1",
+    "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 00 01",
+  ] {
+    assert_eq!(Board::try_from_fen(other), Ok(startpos.clone()), "{other}");
+  }
+}
+
+#[test]
+fn move_generation() {
+  let mut board = Board::default();
+  assert_eq!(board.legal_moves().len(), 20);
+  assert_eq!(perft(&mut board, 3), 8902);
+
+  // Kiwipete
+  let mut board = Board::from_fen("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1");
+  assert_eq!(perft(&mut board, 2), 2039);
+}
+
+#[test]
+fn moves() {
+  let mut board = Board::from_fen("r3k2r/1P6/8/3pP3/8/8/8/R3K2R w KQkq d6 0 1");
+
+  let en_passant = board.find_move("e5d6").unwrap();
+  assert_eq!(
+    (en_passant.from().to_string(), en_passant.to().to_string()),
+    ("e5".into(), "d6".into())
+  );
+  assert!(en_passant.is_capture());
+
+  let castling = board.find_move("e1c1").unwrap();
+  assert_eq!(
+    (castling.from().to_string(), castling.to().to_string()),
+    ("e1".into(), "c1".into())
+  );
+
+  let promotion: ChessMove = board.find_move("b7a8n").unwrap();
+  assert_eq!(promotion.promoted_piece(), Some(PieceType::Knight));
+  assert_eq!(promotion.to_string(), "b7a8n");
+  assert!(board.find_move("e5e7").is_none());
+}
+
+#[test]
+fn san() {
+  let mut board = Board::default();
+  let e4 = board.find_move("e2e4").unwrap();
+  assert_eq!(board.to_san(&e4), "e4");
+  let nf3 = board.find_move("g1f3").unwrap();
+  assert_eq!(board.to_san(&nf3), "Nf3");
+
+  // Scholar's mate
+  play(&mut board, &["e4", "e5", "Qh5", "Nc6", "Bc4", "Nf6"]);
+  let mate = board.parse_san("Qxf7#").unwrap();
+  assert_eq!(mate.to_string(), "h5f7");
+  assert_eq!(board.to_san(&mate), "Qxf7#");
+  assert_eq!(board.parse_san("Qxf7"), Some(mate.clone()));
+  board.make_move(&mate);
+  assert_eq!(board.game_status(), GameStatus::Checkmate);
+
+  // Disambiguation by file, by rank and by both
+  let mut board = Board::from_fen("5k2/8/8/8/Q7/8/8/Q2QK2R w K - 0 1");
+  let by_rank = board.find_move("a1a3").unwrap();
+  assert_eq!(board.to_san(&by_rank), "Q1a3+");
+  let by_file = board.find_move("d1b3").unwrap();
+  assert_eq!(board.to_san(&by_file), "Qdb3");
+  let by_both = board.find_move("a1d4").unwrap();
+  assert_eq!(board.to_san(&by_both), "Qa1d4");
+  let castling = board.find_move("e1g1").unwrap();
+  assert_eq!(board.to_san(&castling), "O-O+");
+  assert_eq!(board.parse_san("0-0"), Some(castling));
+
+  let mut board = Board::from_fen("1n2k3/P7/8/8/8/8/8/4K3 w - - 0 1");
+  let promotion = board.find_move("a7b8q").unwrap();
+  assert_eq!(board.to_san(&promotion), "axb8=Q+");
+  assert_eq!(board.parse_san("axb8=Q"), Some(promotion));
+
+  assert!(board.parse_san("Nf3").is_none());
+  assert!(board.parse_san("").is_none());
+}
+
+#[test]
+fn game_status() {
+  let mut board = Board::from_fen("7k/5Q2/6K1/8/8/8/8/8 b - - 0 1");
+  assert_eq!(board.game_status(), GameStatus::Stalemate);
+
+  let mut board = Board::from_fen("8/8/4k3/8/8/3K4/8/8 w - - 0 1");
+  assert_eq!(board.game_status(), GameStatus::Draw(DrawReason::InsufficientMaterial));
+
+  let mut board = Board::default();
+  play(&mut board, &["Nf3", "Nf6", "Ng1", "Ng8", "Nf3", "Nf6", "Ng1", "Ng8"]);
+  assert_eq!(board.game_status(), GameStatus::Draw(DrawReason::Repetition));
+}
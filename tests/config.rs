@@ -0,0 +1,45 @@
+use rusty_chess::{Config, LogLevel};
+
+// A test binary of its own, because it sets environment variables which no other test may read meanwhile.
+// Keep it the only test in here
+#[test]
+fn load() {
+  let args = |args: &[&str]| args.iter().map(|arg| arg.to_string()).collect::<Vec<String>>();
+
+  // Flags are taken out of the args, the rest is left for the command
+  let (config, rest) = Config::load(&args(&[
+    "serve",
+    "--port",
+    "7000",
+    "--log-level=debug",
+    "--book.enabled",
+    "false",
+    "--divide",
+  ]))
+  .unwrap();
+  assert_eq!(rest, args(&["serve", "--divide"]));
+  assert_eq!(config.port, Some(7000));
+  assert_eq!(config.log_level, LogLevel::Debug);
+  assert!(!config.book.enabled);
+
+  // Flags take priority over environment variables
+  std::env::set_var("RUSTY_CHESS_HASH", "16");
+  std::env::set_var("RUSTY_CHESS_TIME__MOVE_OVERHEAD", "50");
+  let (config, _) = Config::load(&args(&["--hash", "32"])).unwrap();
+  std::env::remove_var("RUSTY_CHESS_HASH");
+  std::env::remove_var("RUSTY_CHESS_TIME__MOVE_OVERHEAD");
+  assert_eq!(config.hash, 32);
+  assert_eq!(config.time.move_overhead, 50);
+
+  for invalid in [
+    &["--hash", "0"][..],
+    &["--threads", "2"],
+    &["--port", "abc"],
+    &["--log-level", "loud"],
+    &["--time.min-move-time", "5000", "--time.max-move-time", "1000"],
+    &["--config", "missing.toml"],
+    &["--host"],
+  ] {
+    assert!(Config::load(&args(invalid)).is_err(), "{invalid:?}");
+  }
+}
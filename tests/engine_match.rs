@@ -0,0 +1,124 @@
+use rusty_chess::engine_match::{self, Adjudication, Engine, GameResult, MatchSettings, Score, Sprt, SprtResult};
+use rusty_chess::SearchLimits;
+use std::fs;
+
+#[test]
+fn against_uci_engine() {
+  let dir = std::env::temp_dir();
+  let openings = dir.join("rusty_chess_openings.epd");
+  let pgn = dir.join("rusty_chess_match.pgn");
+  fs::write(&openings, "6k1/5ppp/8/8/8/8/5PPP/R5K1 w - -\n").unwrap();
+
+  // This binary speaking UCI in a subprocess
+  let mut engines = [
+    Engine::new("self").unwrap(),
+    Engine::new(&format!("{} uci", env!("CARGO_BIN_EXE_rusty_chess"))).unwrap(),
+  ];
+  let openings = engine_match::load_openings(openings.to_str()).unwrap();
+  let settings = MatchSettings {
+    games: 2,
+    limits: SearchLimits::depth(2),
+    adjudication: Adjudication::default(),
+    sprt: None,
+    pgn: Some(pgn.to_str().unwrap().to_string()),
+  };
+
+  // White mates in both games
+  let score = engine_match::run(&mut engines, &openings, &settings).unwrap();
+  assert_eq!(
+    score,
+    Score {
+      wins: 1,
+      draws: 0,
+      losses: 1
+    }
+  );
+
+  let pgn = fs::read_to_string(pgn).unwrap();
+  assert_eq!(pgn.matches("[Result \"1-0\"]").count(), 2);
+  assert!(pgn.contains("[FEN \"6k1/5ppp/8/8/8/8/5PPP/R5K1 w - - 0 1\"]"));
+  assert!(pgn.contains("1. Ra8# {checkmate} 1-0"));
+
+  assert!(Engine::new("./not_an_engine").is_err());
+}
+
+#[test]
+fn adjudication() {
+  let mut white = Engine::new("self").unwrap();
+  let mut black = Engine::new("self").unwrap();
+  let adjudication = Adjudication {
+    resign_score: 500,
+    resign_moves: 1,
+    draw_score: 100,
+    draw_moves: 2,
+    draw_move_number: 1,
+    max_moves: 10,
+    mate_moves: 0,
+  };
+  let limits = SearchLimits::depth(3);
+
+  let mut play = |fen| engine_match::play_game(&mut white, &mut black, fen, &limits, &adjudication).unwrap();
+
+  let game = play("7k/8/8/8/8/8/8/1Q1QK3 w - - 0 1");
+  assert_eq!(game.result, GameResult::WhiteWins);
+  assert_eq!(game.termination, "adjudication: black resigns");
+  assert_eq!(game.moves.len(), 2);
+
+  let game = play("4k3/pppp4/8/8/8/8/PPPP4/4K3 w - - 0 1");
+  assert_eq!(game.result, GameResult::Draw);
+  assert_eq!(game.termination, "adjudication: draw");
+
+  let adjudication = Adjudication {
+    mate_moves: 2,
+    ..Adjudication::default()
+  };
+  let game = engine_match::play_game(
+    &mut white,
+    &mut black,
+    "6k1/5ppp/8/8/8/8/5PPP/R5K1 w - - 0 1",
+    &limits,
+    &adjudication,
+  )
+  .unwrap();
+  assert_eq!(game.result, GameResult::WhiteWins);
+  assert_eq!(game.termination, "adjudication: white mates in 1");
+  assert!(game.moves.is_empty());
+}
+
+#[test]
+fn elo_and_sprt() {
+  let even = Score {
+    wins: 30,
+    draws: 40,
+    losses: 30,
+  };
+  assert_eq!(even.elo().0, 0.);
+
+  let (elo, margin) = Score {
+    wins: 60,
+    draws: 20,
+    losses: 20,
+  }
+  .elo();
+  assert!((elo - 147.2).abs() < 0.1, "{elo}");
+  assert!(margin > 50. && margin < 100., "{margin}");
+
+  let sprt = Sprt::default();
+  let (lower, upper) = sprt.bounds();
+  assert!((lower + 2.944).abs() < 0.001 && (upper - 2.944).abs() < 0.001);
+
+  assert_eq!(sprt.llr(&Score::default()), 0.);
+  assert_eq!(sprt.result(&even), SprtResult::Continue);
+  let strong = Score {
+    wins: 600,
+    draws: 200,
+    losses: 200,
+  };
+  assert_eq!(sprt.result(&strong), SprtResult::H1);
+  let weak = Score {
+    wins: 200,
+    draws: 200,
+    losses: 600,
+  };
+  assert_eq!(sprt.result(&weak), SprtResult::H0);
+}
@@ -0,0 +1,64 @@
+use rusty_chess::{mate_in, Board, SearchLimits, Searcher};
+use std::time::Instant;
+
+#[test]
+fn finds_mate() {
+  let mut searcher = Searcher::with_hash_size(16);
+
+  let board = Board::from_fen("6k1/5ppp/8/8/8/8/5PPP/3R2K1 w - - 0 1");
+  let result = searcher.search(&board, &SearchLimits::depth(4)).unwrap();
+  assert_eq!(result.best_move.to_string(), "d1d8");
+  assert_eq!(mate_in(result.score), Some(1));
+  assert_eq!(result.pv.first(), Some(&result.best_move));
+
+  // The board isn't changed by the search
+  assert_eq!(board.to_fen(), "6k1/5ppp/8/8/8/8/5PPP/3R2K1 w - - 0 1");
+}
+
+#[test]
+fn reports_every_depth() {
+  let mut searcher = Searcher::with_hash_size(16);
+  let board = Board::default();
+
+  let mut depths = vec![];
+  let result = searcher
+    .search_with_info(&board, &SearchLimits::depth(4), |info| {
+      assert!(!info.pv.is_empty());
+      depths.push(info.depth);
+    })
+    .unwrap();
+
+  assert_eq!(depths, vec![1, 2, 3, 4]);
+  assert_eq!(result.depth, 4);
+  assert!(result.stats.nodes > 0);
+  assert!(Board::default().find_move(&result.best_move.to_string()).is_some());
+
+  // Every line of every depth
+  let limits = SearchLimits {
+    multi_pv: 3,
+    ..SearchLimits::depth(2)
+  };
+  let mut lines = vec![];
+  searcher.clear();
+  searcher.search_with_info(&board, &limits, |info| lines.push((info.depth, info.multi_pv)));
+  assert_eq!(lines, vec![(1, 1), (1, 2), (1, 3), (2, 1), (2, 2), (2, 3)]);
+}
+
+#[test]
+fn limits() {
+  let mut searcher = Searcher::with_hash_size(16);
+  let board = Board::default();
+
+  let start = Instant::now();
+  assert!(searcher.search(&board, &SearchLimits::time(200)).is_some());
+  assert!(start.elapsed().as_millis() < 2000);
+
+  // Stopped before it starts, it still gives a legal move
+  let limits = SearchLimits::default();
+  limits.signals.stop();
+  let result = searcher.search(&board, &limits).unwrap();
+  assert!(Board::default().find_move(&result.best_move.to_string()).is_some());
+
+  let checkmated = Board::from_fen("rnb1kbnr/pppp1ppp/8/4p3/6Pq/5P2/PPPPP2P/RNBQKBNR w KQkq - 1 3");
+  assert!(searcher.search(&checkmated, &SearchLimits::depth(2)).is_none());
+}